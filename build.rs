@@ -1,5 +1,7 @@
+use std::collections::BTreeMap;
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 fn main() {
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
@@ -22,6 +24,9 @@ fn main() {
     // Configurações específicas do kernel
     println!("cargo:rustc-link-arg=-Map=kernel.map");  // Gera mapa de memória
     
+    // Tabela de formatos do ktrace! para o decodificador no host
+    generate_trace_table(&out_dir);
+    
     // Verifica se o linker script existe
     if !std::path::Path::new("linker.ld").exists() {
         panic!("linker.ld não encontrado! O script do linker é necessário para o build.");
@@ -42,4 +47,120 @@ pub const KERNEL_VERSION: &str = "{}";
             env::var("CARGO_PKG_VERSION").unwrap_or_else(|_| "0.1.0".to_string())
         ),
     ).unwrap();
-}
\ No newline at end of file
+}
+
+// Mesmo hash de trace::format_id (FNV-1a de 32 bits)
+fn format_id(format: &str) -> u32 {
+    format.bytes().fold(0x811C_9DC5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn rust_sources(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            rust_sources(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+}
+
+// Lê o literal de string que começa em `text` (logo após a aspa inicial),
+// resolvendo os escapes como o compilador
+fn parse_string_literal(text: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                '0' => value.push('\0'),
+                '\\' => value.push('\\'),
+                '"' => value.push('"'),
+                '\'' => value.push('\''),
+                'x' => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    value.push(u8::from_str_radix(&hex, 16).ok()? as char);
+                }
+                'u' => {
+                    let escape: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    let code = u32::from_str_radix(escape.trim_start_matches('{'), 16).ok()?;
+                    value.push(char::from_u32(code)?);
+                }
+                // Quebra de linha escapada: ignora o espaço inicial da próxima
+                '\n' => {
+                    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                }
+                _ => return None,
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+fn generate_trace_table(out_dir: &Path) {
+    let mut sources = Vec::new();
+    rust_sources(Path::new("src"), &mut sources);
+    sources.sort();
+
+    let mut table: BTreeMap<u32, (String, String)> = BTreeMap::new();
+
+    for path in &sources {
+        println!("cargo:rerun-if-changed={}", path.display());
+
+        let Ok(source) = fs::read_to_string(path) else {
+            continue;
+        };
+        for (index, _) in source.match_indices("ktrace!(") {
+            // Menções em comentários não são chamadas
+            let line_start = source[..index].rfind('\n').map_or(0, |i| i + 1);
+            let rest = source[index + "ktrace!(".len()..].trim_start();
+            let Some(literal) = rest.strip_prefix('"') else {
+                continue;
+            };
+            if source[line_start..index].trim_start().starts_with("//") {
+                continue;
+            }
+
+            let format = parse_string_literal(literal)
+                .unwrap_or_else(|| panic!("ktrace! com formato inválido em {}", path.display()));
+            let line = source[..index].lines().count();
+            let location = format!("{}:{}", path.display(), line);
+            let id = format_id(&format);
+
+            match table.get(&id) {
+                Some((existing, _)) if *existing != format => {
+                    panic!("colisão de id do ktrace!: {:?} e {:?}", existing, format)
+                }
+                Some(_) => {}
+                None => {
+                    table.insert(id, (format, location));
+                }
+            }
+        }
+    }
+
+    // id<TAB>origem<TAB>formato (com \n e \t escapados)
+    let mut contents = String::from("# id\tlocation\tformat\n");
+    for (id, (format, location)) in &table {
+        let escaped = format.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t");
+        contents.push_str(&format!("{:08x}\t{}\t{}\n", id, location, escaped));
+    }
+
+    fs::write(out_dir.join("trace_table.tsv"), &contents).unwrap();
+
+    // Cópia em local fixo para o decodificador (target/trace_table.tsv)
+    let target_dir = env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".to_string());
+    let _ = fs::create_dir_all(&target_dir);
+    let _ = fs::write(Path::new(&target_dir).join("trace_table.tsv"), &contents);
+}
@@ -0,0 +1,158 @@
+#!/usr/bin/env python3
+# =======================================================
+# Decodificador do trace binário (ktrace!) do Rust MINIX
+#
+# Lê a saída serial do kernel (boot com "trace=binary"), repassa o texto
+# normal como está e converte cada registro binário em uma linha com
+# timestamp e core, usando a tabela gerada pelo build.rs.
+#
+# Uso:
+#   ./scripts/trace_decode.py captura.bin
+#   ./scripts/trace_decode.py --serial /dev/ttyUSB0 --baud 115200
+#   qemu-system-aarch64 ... -serial stdio | ./scripts/trace_decode.py
+# =======================================================
+
+import argparse
+import re
+import sys
+
+FRAME_START = 0xFF
+DEFAULT_TABLE = "target/trace_table.tsv"
+
+
+def load_table(path):
+    table = {}
+    with open(path, encoding="utf-8") as f:
+        for line in f:
+            if line.startswith("#") or not line.strip():
+                continue
+            ident, location, fmt = line.rstrip("\n").split("\t", 2)
+            fmt = fmt.replace("\\t", "\t").replace("\\n", "\n").replace("\\\\", "\\")
+            table[int(ident, 16)] = (location, fmt)
+    return table
+
+
+# Placeholders do Rust ({}, {:x}, {:08x}, {:#x}, {:>10}...) para str.format
+PLACEHOLDER = re.compile(r"\{\{|\}\}|\{([^{}]*)\}")
+
+
+def format_rust(fmt, args):
+    values = iter(args)
+
+    def replace(match):
+        text = match.group(0)
+        if text in ("{{", "}}"):
+            return text[0]
+        spec = match.group(1)
+        value = next(values, None)
+        if value is None:
+            return "<missing>"
+        spec = spec.split(":", 1)[1] if ":" in spec else ""
+        if spec.endswith("?"):
+            spec = spec[:-1]
+        try:
+            return format(value, spec)
+        except ValueError:
+            return str(value)
+
+    return PLACEHOLDER.sub(replace, fmt)
+
+
+class Decoder:
+    def __init__(self, table, out):
+        self.table = table
+        self.out = out
+        self.buffer = bytearray()
+
+    def feed(self, data):
+        self.buffer.extend(data)
+        while self.buffer:
+            start = self.buffer.find(FRAME_START)
+            if start != 0:
+                # Texto comum até o próximo registro
+                end = len(self.buffer) if start < 0 else start
+                self.out.write(self.buffer[:end].decode("utf-8", errors="replace"))
+                del self.buffer[:end]
+                continue
+
+            frame = self.parse_frame()
+            if frame is None:
+                return  # Registro incompleto: espera mais bytes
+            self.out.write(frame + "\n")
+        self.out.flush()
+
+    def parse_frame(self):
+        buf = self.buffer
+        pos = 1
+        if len(buf) < pos + 4:
+            return None
+        ident = int.from_bytes(buf[pos:pos + 4], "little")
+        pos += 4
+
+        timestamp, pos = read_varint(buf, pos)
+        if timestamp is None or pos >= len(buf):
+            return None
+        core, nargs = buf[pos] >> 4, buf[pos] & 0x0F
+        pos += 1
+
+        args = []
+        for _ in range(nargs):
+            value, pos = read_varint(buf, pos)
+            if value is None:
+                return None
+            args.append(value)
+
+        del buf[:pos]
+
+        secs, micros = divmod(timestamp, 1_000_000)
+        prefix = "[{:5}.{:06}][{}]".format(secs, micros, core)
+        entry = self.table.get(ident)
+        if entry is None:
+            return "{} <unknown id {:08x}> {}".format(prefix, ident, args)
+        return "{} T: {}".format(prefix, format_rust(entry[1], args))
+
+
+def read_varint(buf, pos):
+    value = 0
+    shift = 0
+    while pos < len(buf):
+        byte = buf[pos]
+        pos += 1
+        value |= (byte & 0x7F) << shift
+        if byte & 0x80 == 0:
+            return value, pos
+        shift += 7
+    return None, pos
+
+
+def main():
+    parser = argparse.ArgumentParser(description="Decode Rust MINIX binary trace output")
+    parser.add_argument("input", nargs="?", help="captured serial output (default: stdin)")
+    parser.add_argument("--table", default=DEFAULT_TABLE, help="format table from build.rs")
+    parser.add_argument("--serial", help="read from a serial device (needs pyserial)")
+    parser.add_argument("--baud", type=int, default=115200)
+    args = parser.parse_args()
+
+    decoder = Decoder(load_table(args.table), sys.stdout)
+
+    if args.serial:
+        import serial  # pyserial
+
+        with serial.Serial(args.serial, args.baud, timeout=0.1) as port:
+            while True:
+                decoder.feed(port.read(4096))
+    else:
+        stream = open(args.input, "rb") if args.input else sys.stdin.buffer
+        with stream:
+            while True:
+                data = stream.read1(4096) if hasattr(stream, "read1") else stream.read(4096)
+                if not data:
+                    break
+                decoder.feed(data)
+
+
+if __name__ == "__main__":
+    try:
+        main()
+    except KeyboardInterrupt:
+        pass
@@ -1,4 +1,4 @@
-use crate::println;
+use crate::{debug, println};
 use core::arch::asm;
 
 pub fn init() {
@@ -15,7 +15,7 @@ pub fn init() {
     // Obtém ID do core
     let core_id = get_core_id();
     println!("Running on core {}", core_id);
-    println!("DEBUG: Core ID = {}, comparing with 0", core_id);
+    debug!("Core ID = {}, comparing with 0", core_id);
 }
 
 fn current_el() -> u32 {
@@ -30,15 +30,7 @@ fn get_core_id() -> u8 {
     (id & 0xFF) as u8
 }
 
+// Tempo desde o boot em milissegundos
 pub fn get_time() -> u64 {
-        // On AArch64, you can use the system counter
-        let counter: u64;
-        unsafe {
-            core::arch::asm!("mrs {}, cntpct_el0", out(reg) counter);
-        }
-        
-        // Convert counter ticks to milliseconds
-        // You need to know your counter frequency to do this correctly
-        // This example assumes a 1MHz counter
-        counter / 1000
-    }
\ No newline at end of file
+    crate::time::uptime().as_millis() as u64
+}
@@ -1,5 +1,5 @@
 use core::arch::{asm, global_asm};
-use crate::println;
+use crate::{debug, println};
 
 // Vector table for exception handling
 global_asm!(include_str!("exceptions.S"));
@@ -43,14 +43,17 @@ impl ExceptionContext {
 // Exception handlers called from assembly
 #[no_mangle]
 extern "C" fn sync_exception_el1(ctx: &mut ExceptionContext) {
+    // Breakpoints, passo único e acessos do stub do GDB
+    let esr: u64;
+    unsafe { asm!("mrs {}, esr_el1", out(reg) esr) };
+    if crate::gdb::handle_exception(ctx, (esr >> 26) & 0x3F) {
+        return;
+    }
+    
     println!("Synchronous exception at EL1");
     println!("ELR_EL1: 0x{:016x}", ctx.elr_el1);
     println!("SPSR_EL1: 0x{:016x}", ctx.spsr_el1);
     
-    // Read ESR_EL1 to get exception information
-    let esr: u64;
-    unsafe { asm!("mrs {}, esr_el1", out(reg) esr) };
-    
     let exception_class = (esr >> 26) & 0x3F;
     let _instruction_length = (esr >> 25) & 1;
     
@@ -70,9 +73,7 @@ extern "C" fn sync_exception_el1(ctx: &mut ExceptionContext) {
 
 #[no_mangle]
 extern "C" fn irq_exception_el1(_ctx: &mut ExceptionContext) {
-    println!("IRQ exception at EL1");
-    
-    // Handle interrupt
+    // Handle interrupt (counted per IRQ by the GIC, see the "irqs" command)
     crate::drivers::gic::handle_irq();
     
     // IRQ handling is done, return normally
@@ -98,24 +99,23 @@ extern "C" fn serror_exception_el1(ctx: &mut ExceptionContext) {
 
 #[no_mangle]
 extern "C" fn sync_exception_el0(ctx: &mut ExceptionContext) {
-    println!("Synchronous exception from EL0");
-    println!("ELR_EL1: 0x{:016x}", ctx.elr_el1);
-    
     // Read ESR_EL1 to get exception information
     let esr: u64;
     unsafe { asm!("mrs {}, esr_el1", out(reg) esr) };
     
     let exception_class = (esr >> 26) & 0x3F;
-    println!("Exception Class: 0x{:02x}", exception_class);
     
     match exception_class {
         0x15 => {
-            // SVC (System Call)
-            println!("System call from EL0");
+            // SVC (System Call): log only, the console carries the
+            // process's TTY output
+            debug!("System call from EL0 at 0x{:016x}", ctx.elr_el1);
             handle_syscall(ctx);
         }
         _ => {
-            println!("Unhandled EL0 exception");
+            println!("Synchronous exception from EL0");
+            println!("ELR_EL1: 0x{:016x}", ctx.elr_el1);
+            println!("Exception Class: 0x{:02x}", exception_class);
             panic!("Unhandled EL0 exception");
         }
     }
@@ -123,8 +123,6 @@ extern "C" fn sync_exception_el0(ctx: &mut ExceptionContext) {
 
 #[no_mangle]
 extern "C" fn irq_exception_el0(_ctx: &mut ExceptionContext) {
-    println!("IRQ exception from EL0");
-    
     // Handle interrupt
     crate::drivers::gic::handle_irq();
 }
@@ -145,10 +143,14 @@ fn handle_syscall(ctx: &mut ExceptionContext) {
     // System call number is in x8
     let syscall_num = ctx.gpr[8];
     
-    println!("System call #{}", syscall_num);
+    debug!("System call #{}", syscall_num);
     
-    // For now, just return success (0) in x0
-    ctx.gpr[0] = 0;
+    // TTY calls (read/write/ioctl/openat); anything else returns success (0)
+    let args = [ctx.gpr[0], ctx.gpr[1], ctx.gpr[2], ctx.gpr[3]];
+    ctx.gpr[0] = match crate::tty::syscall(syscall_num, args) {
+        Some(value) => value as u64,
+        None => 0,
+    };
 }
 
 // Initialize exception handling
@@ -187,3 +189,30 @@ pub fn interrupts_enabled() -> bool {
     unsafe { asm!("mrs {}, daif", out(reg) daif) };
     (daif & 0x80) == 0 // IRQ mask bit
 }
+
+// Salva DAIF e mascara IRQs, sem imprimir nada (seguro em contexto de IRQ)
+pub fn save_and_disable_interrupts() -> u64 {
+    let daif: u64;
+    unsafe {
+        asm!("mrs {}, daif", out(reg) daif, options(nomem, nostack));
+        asm!("msr daifset, #2", options(nostack));
+    }
+    daif
+}
+
+// Salva DAIF e libera IRQs (ex.: numa syscall que bloqueia: a entrada na
+// exceção mascara as IRQs, e o contexto salvo na pilha permite aninhar)
+pub fn save_and_enable_interrupts() -> u64 {
+    let daif: u64;
+    unsafe {
+        asm!("mrs {}, daif", out(reg) daif, options(nomem, nostack));
+        asm!("msr daifclr, #2", options(nostack));
+    }
+    daif
+}
+
+// Restaura o estado de DAIF salvo por save_and_disable_interrupts ou
+// save_and_enable_interrupts
+pub fn restore_interrupts(daif: u64) {
+    unsafe { asm!("msr daif, {}", in(reg) daif, options(nostack)) };
+}
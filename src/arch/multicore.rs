@@ -1,10 +1,11 @@
-use crate::println;
+use crate::{debug, ktrace, println};
+use crate::time::{self, Duration, Instant};
 use core::arch::asm;
 use core::ptr::write_volatile;
 use core::sync::atomic::{AtomicU8, AtomicU32, AtomicBool, Ordering};
 
 // Configurações para Raspberry Pi 3B+ (BCM2837)
-const MAX_CORES: usize = 4;
+pub const MAX_CORES: usize = 4;
 const MAILBOX_BASE: usize = 0x40000080; // Mailbox base para cores secundários
 
 // Estados globais para gerenciamento de cores
@@ -28,6 +29,15 @@ static CORE_WORKLOAD: [AtomicU32; MAX_CORES] = [
 static CORE_COMMAND: AtomicU32 = AtomicU32::new(0);
 static CORE_SHUTDOWN: AtomicBool = AtomicBool::new(false);
 
+// Tempos limite para sincronização entre cores
+const CORE_WAKE_TIMEOUT: Duration = Duration::from_millis(100);
+const CORES_READY_TIMEOUT: Duration = Duration::from_secs(5);
+const CORE_STOP_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Cores em modo idle acordam periodicamente para verificar novos comandos
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const IDLE_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
 // Comandos para cores
 const CMD_NONE: u32 = 0;
 const CMD_COMPUTE: u32 = 1;
@@ -82,7 +92,7 @@ pub fn get_mpidr() -> u64 {
 
 // Inicialização do sistema multi-core
 pub fn init_multicore() {
-    println!("DEBUG: init_multicore() called");
+    debug!("init_multicore() called");
     
     let core_info = CoreInfo::new();
     
@@ -151,14 +161,9 @@ fn wake_secondary_core(core_id: u8) -> bool {
     }
     
     // Aguardar um pouco para o core acordar
-    for _ in 0..1000000 {
-        if CORES_READY[core_id as usize].load(Ordering::Acquire) {
-            return true;
-        }
-        unsafe { asm!("nop") };
-    }
-    
-    false
+    time::wait_for(CORE_WAKE_TIMEOUT, || {
+        CORES_READY[core_id as usize].load(Ordering::Acquire)
+    })
 }
 
 // Aguardar que todos os cores secundários fiquem prontos
@@ -166,8 +171,9 @@ fn wait_for_secondary_cores() {
     println!("Waiting for secondary cores to become ready...");
     
     let mut ready_count = 1; // Core 0 já pronto
+    let deadline = Instant::now() + CORES_READY_TIMEOUT;
     
-    for _ in 0..5000000 { // Timeout de ~5 segundos
+    while !deadline.has_passed() {
         let mut current_ready = 1;
         
         for core_id in 1..MAX_CORES {
@@ -185,7 +191,7 @@ fn wait_for_secondary_cores() {
             break;
         }
         
-        unsafe { asm!("nop") };
+        core::hint::spin_loop();
     }
     
     CORES_ONLINE.store(ready_count as u8, Ordering::Release);
@@ -237,7 +243,12 @@ fn secondary_core_init() {
         // Instalar tabela de exceções
         asm!("adr x0, exception_vector_table");
         asm!("msr vbar_el1, x0");
-        
+    }
+    
+    // Rotear o timer genérico para este core (fila de timers local)
+    crate::drivers::timer::init_core();
+    
+    unsafe {
         // Habilitar interrupções
         asm!("msr daifclr, #2");
     }
@@ -257,6 +268,8 @@ fn secondary_core_main() -> ! {
     
     let mut counter = 0u64;
     let mut workload_cycles = 0u32;
+    let mut idle_heartbeat = Instant::now();
+    let mut idle_beats = 0u64;
     
     loop {
         // Verificar comando global
@@ -271,15 +284,19 @@ fn secondary_core_main() -> ! {
                 workload_cycles = perform_computation(core_id, workload_cycles);
             },
             CMD_IDLE => {
-                // Modo idle - apenas heartbeat ocasional
-                if counter % 50000000 == 0 {
-                    println!("Core {} idle heartbeat: {}", core_id, counter / 50000000);
+                // Modo idle - dorme até o próximo intervalo de verificação
+                // de comandos, com heartbeat ocasional
+                if idle_heartbeat.has_passed() {
+                    idle_beats += 1;
+                    ktrace!("Core {} idle heartbeat: {}", core_id, idle_beats);
+                    idle_heartbeat = Instant::now() + IDLE_HEARTBEAT_INTERVAL;
                 }
+                time::idle::idle_for(IDLE_POLL_INTERVAL);
             },
             _ => {
                 // Trabalho padrão
                 if counter % 10000000 == 0 {
-                    println!("Core {} heartbeat: {}", core_id, counter / 10000000);
+                    ktrace!("Core {} heartbeat: {}", core_id, counter / 10000000);
                     
                     // Atualizar estatísticas de trabalho
                     CORE_WORKLOAD[core_id as usize].store(workload_cycles, Ordering::Release);
@@ -365,13 +382,11 @@ pub fn shutdown_secondary_cores() {
     
     // Aguardar cores pararem
     for core_id in 1..MAX_CORES {
-        let mut timeout = 1000000;
-        while CORES_READY[core_id].load(Ordering::Acquire) && timeout > 0 {
-            timeout -= 1;
-            unsafe { asm!("nop") };
-        }
+        let stopped = time::wait_for(CORE_STOP_TIMEOUT, || {
+            !CORES_READY[core_id].load(Ordering::Acquire)
+        });
         
-        if timeout == 0 {
+        if !stopped {
             println!("Warning: Core {} did not stop in time", core_id);
         } else {
             println!("Core {} stopped successfully", core_id);
@@ -495,7 +510,11 @@ pub fn is_core_online(core_id: u8) -> bool {
 
 // Synchronization primitives para comunicação entre cores
 pub mod sync {
-    use core::sync::atomic::{AtomicBool, Ordering};
+    use crate::arch::exceptions::{restore_interrupts, save_and_disable_interrupts};
+    use core::cell::UnsafeCell;
+    use core::mem::ManuallyDrop;
+    use core::ops::{Deref, DerefMut};
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
     
     pub struct SpinLock {
         locked: AtomicBool,
@@ -529,4 +548,159 @@ pub mod sync {
     
     // Global spinlock para print thread-safe
     pub static PRINT_LOCK: SpinLock = SpinLock::new();
+    
+    // Mutex que mascara IRQs enquanto está travado.
+    // Pode ser usado tanto do código normal quanto de handlers de interrupção
+    // sem risco de deadlock no mesmo core.
+    pub struct IrqMutex<T> {
+        inner: spin::Mutex<T>,
+    }
+    
+    pub struct IrqMutexGuard<'a, T> {
+        guard: ManuallyDrop<spin::MutexGuard<'a, T>>,
+        daif: u64,
+    }
+    
+    impl<T> IrqMutex<T> {
+        pub const fn new(value: T) -> Self {
+            IrqMutex {
+                inner: spin::Mutex::new(value),
+            }
+        }
+        
+        pub fn lock(&self) -> IrqMutexGuard<'_, T> {
+            let daif = save_and_disable_interrupts();
+            IrqMutexGuard {
+                guard: ManuallyDrop::new(self.inner.lock()),
+                daif,
+            }
+        }
+        
+        #[allow(dead_code)]
+        pub fn try_lock(&self) -> Option<IrqMutexGuard<'_, T>> {
+            let daif = save_and_disable_interrupts();
+            match self.inner.try_lock() {
+                Some(guard) => Some(IrqMutexGuard {
+                    guard: ManuallyDrop::new(guard),
+                    daif,
+                }),
+                None => {
+                    restore_interrupts(daif);
+                    None
+                }
+            }
+        }
+        
+        pub fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+        
+        // Libera o lock à força (só para o caminho de panic, quando o dono
+        // nunca vai soltá-lo)
+        pub unsafe fn force_unlock(&self) {
+            unsafe { self.inner.force_unlock() };
+        }
+    }
+    
+    impl<T> Deref for IrqMutexGuard<'_, T> {
+        type Target = T;
+        
+        fn deref(&self) -> &T {
+            &self.guard
+        }
+    }
+    
+    impl<T> DerefMut for IrqMutexGuard<'_, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            &mut self.guard
+        }
+    }
+    
+    impl<T> Drop for IrqMutexGuard<'_, T> {
+        fn drop(&mut self) {
+            // Libera o lock antes de restaurar as interrupções
+            unsafe { ManuallyDrop::drop(&mut self.guard) };
+            restore_interrupts(self.daif);
+        }
+    }
+    
+    // Buffer circular lock-free com um produtor e um consumidor (SPSC).
+    // N deve ser potência de dois; os índices crescem livremente e são
+    // mascarados no acesso.
+    pub struct RingBuffer<const N: usize> {
+        buffer: UnsafeCell<[u8; N]>,
+        head: AtomicUsize,  // Próxima posição de escrita (produtor)
+        tail: AtomicUsize,  // Próxima posição de leitura (consumidor)
+    }
+    
+    unsafe impl<const N: usize> Sync for RingBuffer<N> {}
+    
+    impl<const N: usize> RingBuffer<N> {
+        pub const fn new() -> Self {
+            assert!(N.is_power_of_two());
+            RingBuffer {
+                buffer: UnsafeCell::new([0; N]),
+                head: AtomicUsize::new(0),
+                tail: AtomicUsize::new(0),
+            }
+        }
+        
+        pub fn len(&self) -> usize {
+            let head = self.head.load(Ordering::Acquire);
+            let tail = self.tail.load(Ordering::Acquire);
+            head.wrapping_sub(tail)
+        }
+        
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+        
+        #[allow(dead_code)]
+        pub fn is_full(&self) -> bool {
+            self.len() >= N
+        }
+        
+        #[allow(dead_code)]
+        pub const fn capacity(&self) -> usize {
+            N
+        }
+        
+        // Lado do produtor
+        pub fn push(&self, byte: u8) -> bool {
+            let head = self.head.load(Ordering::Relaxed);
+            let tail = self.tail.load(Ordering::Acquire);
+            if head.wrapping_sub(tail) >= N {
+                return false;
+            }
+            
+            unsafe { (*self.buffer.get())[head & (N - 1)] = byte };
+            self.head.store(head.wrapping_add(1), Ordering::Release);
+            true
+        }
+        
+        // Lado do consumidor
+        pub fn pop(&self) -> Option<u8> {
+            let tail = self.tail.load(Ordering::Relaxed);
+            let head = self.head.load(Ordering::Acquire);
+            if head == tail {
+                return None;
+            }
+            
+            let byte = unsafe { (*self.buffer.get())[tail & (N - 1)] };
+            self.tail.store(tail.wrapping_add(1), Ordering::Release);
+            Some(byte)
+        }
+        
+        // Lado do consumidor: lê sem remover
+        #[allow(dead_code)]
+        pub fn peek(&self) -> Option<u8> {
+            let tail = self.tail.load(Ordering::Relaxed);
+            let head = self.head.load(Ordering::Acquire);
+            if head == tail {
+                return None;
+            }
+            
+            Some(unsafe { (*self.buffer.get())[tail & (N - 1)] })
+        }
+    }
 }
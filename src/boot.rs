@@ -7,6 +7,9 @@ global_asm!(
     .global secondary_core_entry
 
     _start:
+        // Preservar o endereço do device tree passado pelo firmware
+        mov x19, x0
+        
         // Obter ID do core
         mrs x0, mpidr_el1
         and x0, x0, #0xFF
@@ -48,13 +51,17 @@ global_asm!(
         ldr x0, =__stack_end
         mov sp, x0
 
+        // Console mínimo para as mensagens anteriores ao drivers::init
+        bl earlycon_init
+
         // Limpeza da seção .bss
         ldr x0, =__bss_start
         ldr x1, =__bss_end
         sub x1, x1, x0
         bl memzero
 
-        // Chama o Rust main
+        // Chama o Rust main (x0 = device tree; x19 sobrevive a memzero)
+        mov x0, x19
         bl rust_main
         
     halt:
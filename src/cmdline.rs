@@ -0,0 +1,134 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// Linha de comando do kernel, lida do device tree que o firmware do
+// Raspberry Pi passa em x0 (propriedade "bootargs" do nó /chosen).
+
+const FDT_MAGIC: u32 = 0xD00D_FEED;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+// Limite de sanidade para o tamanho do device tree
+const FDT_MAX_SIZE: usize = 1 << 20;
+
+static CMDLINE_PTR: AtomicUsize = AtomicUsize::new(0);
+static CMDLINE_LEN: AtomicUsize = AtomicUsize::new(0);
+static DTB_ADDRESS: AtomicUsize = AtomicUsize::new(0);
+
+fn read_be32(addr: usize) -> u32 {
+    u32::from_be(unsafe { core::ptr::read_unaligned(addr as *const u32) })
+}
+
+// String terminada em zero dentro de [addr, end)
+fn c_str(addr: usize, end: usize) -> Option<&'static [u8]> {
+    let mut len = 0;
+    while addr + len < end {
+        if unsafe { *((addr + len) as *const u8) } == 0 {
+            return Some(unsafe { core::slice::from_raw_parts(addr as *const u8, len) });
+        }
+        len += 1;
+    }
+    None
+}
+
+fn align4(value: usize) -> usize {
+    (value + 3) & !3
+}
+
+// Procura /chosen/bootargs no blob FDT
+fn find_bootargs(dtb: usize) -> Option<&'static str> {
+    if dtb == 0 || !dtb.is_multiple_of(4) || read_be32(dtb) != FDT_MAGIC {
+        return None;
+    }
+
+    let total_size = read_be32(dtb + 4) as usize;
+    if total_size > FDT_MAX_SIZE {
+        return None;
+    }
+
+    let end = dtb + total_size;
+    let strings = dtb + read_be32(dtb + 12) as usize;
+    let mut offset = dtb + read_be32(dtb + 8) as usize;
+    let mut depth = 0usize;
+    let mut in_chosen = false;
+
+    while offset + 4 <= end {
+        let token = read_be32(offset);
+        offset += 4;
+
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = c_str(offset, end)?;
+                offset = align4(offset + name.len() + 1);
+                depth += 1;
+                // /chosen é filho direto da raiz (profundidade 2)
+                in_chosen = depth == 2 && name == b"chosen";
+            }
+            FDT_END_NODE => {
+                if in_chosen {
+                    return None;
+                }
+                depth = depth.checked_sub(1)?;
+            }
+            FDT_PROP => {
+                let len = read_be32(offset) as usize;
+                let name_offset = read_be32(offset + 4) as usize;
+                let value = offset + 8;
+                offset = align4(value + len);
+
+                if in_chosen && c_str(strings + name_offset, end)? == b"bootargs" {
+                    let bytes = c_str(value, value + len)?;
+                    return core::str::from_utf8(bytes).ok();
+                }
+            }
+            FDT_NOP => {}
+            FDT_END => return None,
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+// Deve ser chamado logo após a limpeza do BSS
+pub fn init(dtb: usize) {
+    DTB_ADDRESS.store(dtb, Ordering::Relaxed);
+
+    if let Some(args) = find_bootargs(dtb) {
+        CMDLINE_PTR.store(args.as_ptr() as usize, Ordering::Relaxed);
+        CMDLINE_LEN.store(args.len(), Ordering::Relaxed);
+    }
+}
+
+#[allow(dead_code)]
+pub fn dtb_address() -> usize {
+    DTB_ADDRESS.load(Ordering::Relaxed)
+}
+
+pub fn get() -> &'static str {
+    let ptr = CMDLINE_PTR.load(Ordering::Relaxed);
+    let len = CMDLINE_LEN.load(Ordering::Relaxed);
+    if ptr == 0 {
+        return "";
+    }
+
+    let bytes = unsafe { core::slice::from_raw_parts(ptr as *const u8, len) };
+    core::str::from_utf8(bytes).unwrap_or("")
+}
+
+// Valor de "nome=valor" (a última ocorrência vence, como no Linux)
+pub fn param(name: &str) -> Option<&'static str> {
+    get()
+        .split_ascii_whitespace()
+        .filter_map(|arg| arg.split_once('='))
+        .filter(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+        .next_back()
+}
+
+// Presença de uma flag sem valor (ex.: "quiet")
+pub fn has_flag(name: &str) -> bool {
+    get().split_ascii_whitespace().any(|arg| arg == name)
+}
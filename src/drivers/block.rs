@@ -0,0 +1,94 @@
+use crate::arch::multicore::sync::IrqMutex;
+use crate::println;
+
+// Generic block device interface. Drivers (EMMC, partitions) implement
+// BlockDevice and register a 'static instance; file systems look devices
+// up by name and only ever see whole blocks addressed by LBA.
+
+pub const SECTOR_SIZE: usize = 512;
+
+const MAX_DEVICES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    // LBA range past the end of the device
+    OutOfRange,
+    // Buffer length is not a multiple of the block size
+    BadBuffer,
+    #[allow(dead_code)]
+    ReadOnly,
+    Timeout,
+    NoDevice,
+    // Controller or card reported an error
+    Io,
+}
+
+pub trait BlockDevice: Sync {
+    fn name(&self) -> &str;
+
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn block_count(&self) -> u64;
+
+    // Read buf.len() / block_size() blocks starting at `lba`
+    fn read_blocks(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError>;
+
+    #[allow(dead_code)]
+    fn write_blocks(&self, lba: u64, buf: &[u8]) -> Result<(), BlockError>;
+
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    fn size_bytes(&self) -> u64 {
+        self.block_count() * self.block_size() as u64
+    }
+}
+
+// Number of blocks in `len` bytes starting at `lba`, checked against the
+// device bounds (shared by the implementations)
+pub fn check_request(device: &dyn BlockDevice, lba: u64, len: usize) -> Result<u64, BlockError> {
+    let block_size = device.block_size();
+    if len == 0 || !len.is_multiple_of(block_size) {
+        return Err(BlockError::BadBuffer);
+    }
+
+    let count = (len / block_size) as u64;
+    match lba.checked_add(count) {
+        Some(end) if end <= device.block_count() => Ok(count),
+        _ => Err(BlockError::OutOfRange),
+    }
+}
+
+static DEVICES: IrqMutex<[Option<&'static dyn BlockDevice>; MAX_DEVICES]> =
+    IrqMutex::new([None; MAX_DEVICES]);
+
+pub fn register(device: &'static dyn BlockDevice) -> Result<(), BlockError> {
+    let mut devices = DEVICES.lock();
+    let slot = devices.iter_mut().find(|slot| slot.is_none()).ok_or(BlockError::NoDevice)?;
+    *slot = Some(device);
+    drop(devices);
+
+    println!(
+        "Block: {} ({} blocks of {} bytes, {} MB)",
+        device.name(),
+        device.block_count(),
+        device.block_size(),
+        device.size_bytes() >> 20
+    );
+    Ok(())
+}
+
+pub fn device(name: &str) -> Option<&'static dyn BlockDevice> {
+    DEVICES.lock().iter().flatten().copied().find(|device| device.name() == name)
+}
+
+// Call `f` for every registered device, in registration order
+pub fn for_each(mut f: impl FnMut(&'static dyn BlockDevice)) {
+    let devices = *DEVICES.lock();
+    for device in devices.iter().flatten() {
+        f(*device);
+    }
+}
@@ -0,0 +1,300 @@
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use crate::arch::multicore::get_core_id;
+use crate::arch::multicore::sync::IrqMutex;
+use crate::cmdline;
+use crate::time::{self, Duration};
+use crate::drivers::{earlycon, fbcon};
+use crate::drivers::mini_uart::{self, MiniUart, MINI_UART};
+use crate::drivers::uart::{self, Uart};
+
+// Console output device used by print!/println!
+pub trait Console: Sync {
+    fn name(&self) -> &'static str;
+    fn write_byte(&self, byte: u8);
+    fn read_byte(&self) -> Option<u8>;
+
+    fn write_bytes(&self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_byte(byte);
+        }
+    }
+}
+
+impl Console for Uart {
+    fn name(&self) -> &'static str {
+        "ttyAMA0 (PL011)"
+    }
+
+    fn write_byte(&self, byte: u8) {
+        Uart::write_byte(self, byte);
+    }
+
+    fn read_byte(&self) -> Option<u8> {
+        uart::read_byte()
+    }
+}
+
+impl Console for MiniUart {
+    fn name(&self) -> &'static str {
+        "ttyS0 (mini UART)"
+    }
+
+    fn write_byte(&self, byte: u8) {
+        MiniUart::write_byte(self, byte);
+    }
+
+    fn read_byte(&self) -> Option<u8> {
+        MiniUart::read_byte(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleDevice {
+    Pl011 = 0,
+    MiniUart = 1,
+}
+
+impl ConsoleDevice {
+    // Names accepted in "console=" (Linux names for the Pi UARTs)
+    pub fn from_name(name: &str) -> Option<ConsoleDevice> {
+        // Strip options such as ",115200"
+        let name = name.split(',').next().unwrap_or(name);
+        match name {
+            "ttyAMA0" | "serial0" | "pl011" => Some(ConsoleDevice::Pl011),
+            "ttyS0" | "ttyS1" | "serial1" | "miniuart" => Some(ConsoleDevice::MiniUart),
+            _ => None,
+        }
+    }
+}
+
+// Build-time default, overridden by "console=" on the command line
+#[cfg(not(feature = "console-mini-uart"))]
+const DEFAULT_DEVICE: ConsoleDevice = ConsoleDevice::Pl011;
+#[cfg(feature = "console-mini-uart")]
+const DEFAULT_DEVICE: ConsoleDevice = ConsoleDevice::MiniUart;
+
+// PL011 until init() brings up the selected device
+static ACTIVE: AtomicU8 = AtomicU8::new(ConsoleDevice::Pl011 as u8);
+
+// Until init() runs, and after a panic had to break the console lock,
+// output goes through the lock-free early console instead
+static READY: AtomicBool = AtomicBool::new(false);
+static EARLY_FALLBACK: AtomicBool = AtomicBool::new(false);
+
+static PL011: Uart = Uart;
+
+pub fn active() -> ConsoleDevice {
+    match ACTIVE.load(Ordering::Relaxed) {
+        1 => ConsoleDevice::MiniUart,
+        _ => ConsoleDevice::Pl011,
+    }
+}
+
+pub fn device() -> &'static dyn Console {
+    device_for(active())
+}
+
+pub fn device_for(console: ConsoleDevice) -> &'static dyn Console {
+    match console {
+        ConsoleDevice::Pl011 => &PL011,
+        ConsoleDevice::MiniUart => &MINI_UART,
+    }
+}
+
+// Input typed on a local keyboard (USB), read as if it came from the
+// active console
+const INPUT_SIZE: usize = 64;
+
+struct InputQueue {
+    data: [u8; INPUT_SIZE],
+    head: usize,
+    len: usize,
+}
+
+static KEYBOARD_INPUT: IrqMutex<InputQueue> = IrqMutex::new(InputQueue {
+    data: [0; INPUT_SIZE],
+    head: 0,
+    len: 0,
+});
+
+// Queue keyboard bytes; what does not fit is dropped
+pub fn push_input(bytes: &[u8]) {
+    let mut queue = KEYBOARD_INPUT.lock();
+    for &byte in bytes {
+        if queue.len == INPUT_SIZE {
+            break;
+        }
+        let tail = (queue.head + queue.len) % INPUT_SIZE;
+        queue.data[tail] = byte;
+        queue.len += 1;
+    }
+}
+
+fn pop_input() -> Option<u8> {
+    let mut queue = KEYBOARD_INPUT.lock();
+    if queue.len == 0 {
+        return None;
+    }
+    let byte = queue.data[queue.head];
+    queue.head = (queue.head + 1) % INPUT_SIZE;
+    queue.len -= 1;
+    Some(byte)
+}
+
+pub fn read_byte() -> Option<u8> {
+    read_byte_from(active())
+}
+
+// Next input byte of a device; the active console also gets the keyboard
+pub fn read_byte_from(console: ConsoleDevice) -> Option<u8> {
+    let keyboard = if console == active() { pop_input() } else { None };
+    keyboard.or_else(|| device_for(console).read_byte())
+}
+
+// Interval between polls while waiting for input (the PL011 RX interrupt
+// wakes the core earlier; the mini UART is only polled)
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Block until a byte arrives, idling the core in between
+pub fn read_byte_blocking() -> u8 {
+    loop {
+        if let Some(byte) = read_byte() {
+            return byte;
+        }
+        time::idle::idle_for(INPUT_POLL_INTERVAL);
+    }
+}
+
+pub struct ConsoleWriter;
+
+impl fmt::Write for ConsoleWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        device().write_bytes(s.as_bytes());
+        fbcon::write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+// IRQ-save lock: an interrupt handler that prints can never spin on a
+// lock held by the code it interrupted, and each call to _print() goes
+// out as a whole, without characters from other cores in between
+pub static CONSOLE: IrqMutex<ConsoleWriter> = IrqMutex::new(ConsoleWriter);
+
+const NO_CORE: u8 = u8::MAX;
+
+// Core currently holding CONSOLE inside _print()
+static OWNER: AtomicU8 = AtomicU8::new(NO_CORE);
+
+// Core that panicked; once set, only it may write to the console
+static PANIC_CORE: AtomicU8 = AtomicU8::new(NO_CORE);
+
+// How long the panic path waits for another core to release the console
+const PANIC_LOCK_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Backend of print!/println!
+pub fn _print(args: fmt::Arguments) {
+    let core = get_core_id();
+    let panic_core = PANIC_CORE.load(Ordering::Acquire);
+    if panic_core != NO_CORE && panic_core != core {
+        return;
+    }
+
+    if use_earlycon() {
+        earlycon::write_fmt(args);
+        fbcon::write_fmt(args);
+        return;
+    }
+
+    let mut console = CONSOLE.lock();
+    OWNER.store(core, Ordering::Relaxed);
+    let _ = console.write_fmt(args);
+    OWNER.store(NO_CORE, Ordering::Relaxed);
+}
+
+fn use_earlycon() -> bool {
+    !READY.load(Ordering::Acquire) || EARLY_FALLBACK.load(Ordering::Relaxed)
+}
+
+// Raw bytes to a given device, serialized with print!/println! (used by
+// the TTY layer, which may drive a UART other than the console). Output
+// to the active console is mirrored on the framebuffer.
+pub fn write_to(console: ConsoleDevice, bytes: &[u8]) {
+    write_bytes(console, bytes, console == active());
+}
+
+// Like write_to(), but never mirrored (binary trace records)
+pub fn write_raw(console: ConsoleDevice, bytes: &[u8]) {
+    write_bytes(console, bytes, false);
+}
+
+fn write_bytes(console: ConsoleDevice, bytes: &[u8], mirror: bool) {
+    let core = get_core_id();
+    let panic_core = PANIC_CORE.load(Ordering::Acquire);
+    if panic_core != NO_CORE && panic_core != core {
+        return;
+    }
+
+    let _console = CONSOLE.lock();
+    OWNER.store(core, Ordering::Relaxed);
+    device_for(console).write_bytes(bytes);
+    if mirror {
+        fbcon::write_bytes(bytes);
+    }
+    OWNER.store(NO_CORE, Ordering::Relaxed);
+}
+
+// Called first thing by the panic handler. Silences the other cores and
+// makes sure the console lock is free: if this core panicked while holding
+// it, or another core does not let go in time, the lock is broken.
+pub fn panic_takeover() {
+    let core = get_core_id();
+    if PANIC_CORE
+        .compare_exchange(NO_CORE, core, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        // Nested panic on the same core, or a second core panicking
+        if PANIC_CORE.load(Ordering::Acquire) != core {
+            loop {
+                core::hint::spin_loop();
+            }
+        }
+    }
+
+    let released = OWNER.load(Ordering::Relaxed) != core
+        && time::wait_for(PANIC_LOCK_TIMEOUT, || !CONSOLE.is_locked());
+
+    if !released {
+        OWNER.store(NO_CORE, Ordering::Relaxed);
+        unsafe { CONSOLE.force_unlock() };
+
+        // The driver may have been stopped mid-write: use the PL011 directly
+        if active() == ConsoleDevice::Pl011 {
+            EARLY_FALLBACK.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fbcon::panic_takeover();
+    uart::panic_takeover();
+}
+
+pub fn select(console: ConsoleDevice) {
+    if console == ConsoleDevice::MiniUart {
+        mini_uart::init();
+    }
+
+    // Switch under the lock so no line is split between devices
+    let _console = CONSOLE.lock();
+    ACTIVE.store(console as u8, Ordering::Relaxed);
+}
+
+pub fn init() {
+    let console = cmdline::param("console")
+        .and_then(ConsoleDevice::from_name)
+        .unwrap_or(DEFAULT_DEVICE);
+
+    select(console);
+
+    // Hand over from the early console
+    READY.store(true, Ordering::Release);
+}
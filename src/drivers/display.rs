@@ -1,55 +1,305 @@
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::arch::multicore::sync::IrqMutex;
+use crate::arch::multicore::{get_core_workload_stats, is_core_online, MAX_CORES};
+use crate::drivers::framebuffer::{self, Color, Framebuffer, CHAR_HEIGHT, CHAR_WIDTH};
+use crate::drivers::{fbcon, gic, timer};
+use crate::memory::allocator::{HeapStats, ALLOCATOR};
 use crate::println;
+use crate::time::{self, queue, Duration};
 
-// Simple display driver for kernel status output
-// This provides a simulated visual display in QEMU
-pub struct Display {
-    line_count: usize,
+// Live system dashboard on the HDMI framebuffer: per-core workload bars
+// with online state, a timer tick-rate graph, an IRQ counter table and
+// heap usage. A periodic timer callback samples the tick rate and asks for
+// a redraw, which runs later in task context (drivers::poll); the text
+// console takes the rest of the screen below it. Without a framebuffer
+// (mailbox failure, no firmware) there is no dashboard and the monitor
+// commands remain the way to look at the same numbers.
+
+const BACKGROUND: Color = Color::rgb(0x10, 0x18, 0x28);
+const HEADER: Color = Color::rgb(0x20, 0x40, 0x80);
+const PANEL: Color = Color::rgb(0x30, 0x30, 0x40);
+const TEXT: Color = Color::WHITE;
+const DIM_TEXT: Color = Color::rgb(0xA0, 0xA0, 0xB0);
+
+const MARGIN: u32 = 16;
+const HEADER_HEIGHT: u32 = 48;
+const BAR_HEIGHT: u32 = 16;
+
+// Per-core panel: label line, then the workload bar
+const CORES_TOP: u32 = HEADER_HEIGHT + MARGIN;
+const CORE_PANEL_HEIGHT: u32 = CHAR_HEIGHT + 4 + BAR_HEIGHT;
+
+// Tick-rate graph (left) and IRQ table (right) share a row
+const GRAPH_TOP: u32 = CORES_TOP + CORE_PANEL_HEIGHT + MARGIN;
+const GRAPH_HEIGHT: u32 = 8 * CHAR_HEIGHT;
+
+const HEAP_TOP: u32 = GRAPH_TOP + GRAPH_HEIGHT + MARGIN;
+
+// The text console starts below the dashboard
+const STATUS_HEIGHT: u32 = HEAP_TOP + CHAR_HEIGHT + 4 + BAR_HEIGHT + MARGIN;
+
+// Smallest mode the layout fits in; below it the whole screen is console
+const MIN_WIDTH: u32 = 640;
+const MIN_HEIGHT: u32 = 480;
+
+const REFRESH_PERIOD: Duration = Duration::from_millis(500);
+
+// Tick-rate samples kept for the graph (one per refresh)
+const HISTORY: usize = 120;
+
+#[derive(Clone, Copy)]
+struct Dashboard {
+    history: [u32; HISTORY],
+    // Next slot to write (the oldest sample once the ring is full)
+    next: usize,
+    samples: usize,
+    last_ticks: u64,
 }
 
-static mut DISPLAY: Display = Display {
-    line_count: 0,
-};
+static DASHBOARD: IrqMutex<Dashboard> = IrqMutex::new(Dashboard {
+    history: [0; HISTORY],
+    next: 0,
+    samples: 0,
+    last_ticks: 0,
+});
+
+// Set by the timer callback, cleared by the redraw
+static REDRAW: AtomicBool = AtomicBool::new(false);
 
-#[allow(static_mut_refs)]
-impl Display {
-    pub fn write_line(&mut self, text: &str) {
-        self.line_count += 1;
-        println!("[DISPLAY LINE {}] {}", self.line_count, text);
+// Fixed-size text for one dashboard label (no allocator needed)
+struct Label {
+    data: [u8; 64],
+    len: usize,
+}
+
+impl Label {
+    fn new() -> Self {
+        Label { data: [0; 64], len: 0 }
     }
-    
-    pub fn clear(&mut self) {
-        self.line_count = 0;
-        println!("[DISPLAY] Screen cleared");
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.data[..self.len]).unwrap_or("")
     }
 }
 
-#[allow(static_mut_refs)]
-pub fn init() {
-    println!("Display driver initialized (simulated mode)");
-    unsafe {
-        DISPLAY.clear();
-        DISPLAY.write_line("=== Rust MINIX Display ===");
-        DISPLAY.write_line("ARM64 Kernel for Raspberry Pi 3B+");
-    }
-}
-
-#[allow(static_mut_refs)]
-pub fn draw_kernel_status(time_ms: u64, tick_count: u64, counter: u64) {
-    unsafe {
-        DISPLAY.write_line("System Status Update:");
-        DISPLAY.write_line("  System running normally");
-        
-        if tick_count > 0 {
-            DISPLAY.write_line("🎉 INTERRUPTS WORKING!");
+impl Write for Label {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let count = s.len().min(self.data.len() - self.len);
+        self.data[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
+
+fn draw_label(fb: &mut Framebuffer, x: u32, y: u32, colors: (Color, Color), args: fmt::Arguments) {
+    let mut label = Label::new();
+    let _ = label.write_fmt(args);
+    fb.draw_text(x, y, label.as_str(), colors.0, colors.1);
+}
+
+// Horizontal bar with a frame, filled to value / max
+fn draw_bar(fb: &mut Framebuffer, x: u32, y: u32, width: u32, fill: (u64, u64), color: Color) {
+    let (value, max) = fill;
+    let filled = (width as u64 * value.min(max) / max.max(1)) as u32;
+
+    fb.fill_rect(x, y, width, BAR_HEIGHT, PANEL);
+    fb.fill_rect(x, y, filled, BAR_HEIGHT, color);
+    fb.draw_rect(x, y, width, BAR_HEIGHT, Color::GREY);
+}
+
+fn draw_background(fb: &mut Framebuffer) {
+    fb.clear(BACKGROUND);
+    fb.fill_rect(0, 0, fb.width(), HEADER_HEIGHT, HEADER);
+    fb.fill_rect(0, HEADER_HEIGHT, fb.width(), 2, Color::WHITE);
+
+    let y = (HEADER_HEIGHT - CHAR_HEIGHT) / 2;
+    fb.draw_text(MARGIN, y, "Rust MINIX - ARM64 kernel for Raspberry Pi 3B+", TEXT, HEADER);
+}
+
+fn draw_uptime(fb: &mut Framebuffer) {
+    let seconds = time::uptime().as_millis() / 1000;
+    let mut label = Label::new();
+    let _ = write!(label, "up {:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    let x = fb.width().saturating_sub(MARGIN + label.len as u32 * CHAR_WIDTH);
+    fb.draw_text(x, (HEADER_HEIGHT - CHAR_HEIGHT) / 2, label.as_str(), TEXT, HEADER);
+}
+
+fn draw_cores(fb: &mut Framebuffer) {
+    let workloads = get_core_workload_stats();
+    let busiest = workloads.iter().copied().max().unwrap_or(0) as u64;
+    let width = (fb.width() - (MAX_CORES as u32 + 1) * MARGIN) / MAX_CORES as u32;
+
+    for (core, &workload) in workloads.iter().enumerate() {
+        let x = MARGIN + core as u32 * (width + MARGIN);
+        let (state, color, bar) = if is_core_online(core as u8) {
+            ("online", Color::GREEN, Color::BLUE)
         } else {
-            DISPLAY.write_line("⏳ Waiting for interrupts...");
+            ("offline", Color::GREY, Color::GREY)
+        };
+
+        // State dot, then "CPUn state workload"
+        fb.fill_rect(x, CORES_TOP, width, CHAR_HEIGHT, BACKGROUND);
+        fb.fill_rect(x, CORES_TOP + 4, 8, 8, color);
+        let label = format_args!("CPU{} {:7} {}", core, state, workload);
+        draw_label(fb, x + 2 * CHAR_WIDTH, CORES_TOP, (TEXT, BACKGROUND), label);
+
+        let bar_y = CORES_TOP + CHAR_HEIGHT + 4;
+        draw_bar(fb, x, bar_y, width, (workload as u64, busiest), bar);
+    }
+}
+
+fn draw_tick_graph(fb: &mut Framebuffer, dashboard: &Dashboard, x: u32, width: u32) {
+    fb.fill_rect(x, GRAPH_TOP, width, GRAPH_HEIGHT, PANEL);
+    fb.draw_rect(x, GRAPH_TOP, width, GRAPH_HEIGHT, Color::GREY);
+
+    // Samples from the oldest to the newest
+    let count = dashboard.samples.min(HISTORY);
+    let first = (dashboard.next + HISTORY - count) % HISTORY;
+    let sample = |index: usize| dashboard.history[(first + index) % HISTORY];
+    let latest = if count > 0 { sample(count - 1) } else { 0 };
+    let peak = (0..count).map(sample).max().unwrap_or(0).max(1);
+
+    let label = format_args!("timer ticks/s: {} (peak {})", latest, peak);
+    draw_label(fb, x + 4, GRAPH_TOP + 4, (TEXT, PANEL), label);
+
+    let plot_top = GRAPH_TOP + CHAR_HEIGHT + 8;
+    let plot_height = GRAPH_TOP + GRAPH_HEIGHT - 4 - plot_top;
+    let plot_width = width - 8;
+    let point = |index: usize| {
+        let px = x + 4 + index as u32 * (plot_width - 1) / (HISTORY as u32 - 1);
+        let py = plot_top + plot_height - 1 - sample(index).min(peak) * (plot_height - 1) / peak;
+        (px as i32, py as i32)
+    };
+
+    for index in 1..count {
+        let (x0, y0) = point(index - 1);
+        let (x1, y1) = point(index);
+        fb.draw_line(x0, y0, x1, y1, Color::GREEN);
+    }
+}
+
+fn draw_irq_table(fb: &mut Framebuffer, x: u32, width: u32) {
+    fb.fill_rect(x, GRAPH_TOP, width, GRAPH_HEIGHT, PANEL);
+    fb.draw_rect(x, GRAPH_TOP, width, GRAPH_HEIGHT, Color::GREY);
+
+    let mut y = GRAPH_TOP + 4;
+    fb.draw_text(x + 4, y, "IRQ      count  name", DIM_TEXT, PANEL);
+    y += CHAR_HEIGHT;
+
+    // Last line is for the spurious counter
+    let bottom = GRAPH_TOP + GRAPH_HEIGHT - 4 - CHAR_HEIGHT;
+    for irq in 0..gic::MAX_IRQS as u32 {
+        let count = gic::irq_count(irq);
+        if count == 0 {
+            continue;
+        }
+        if y + CHAR_HEIGHT > bottom {
+            break;
+        }
+        let label = format_args!("{:3} {:10}  {}", irq, count, gic::irq_name(irq));
+        draw_label(fb, x + 4, y, (TEXT, PANEL), label);
+        y += CHAR_HEIGHT;
+    }
+
+    let label = format_args!("spurious {:7}", gic::spurious_count());
+    draw_label(fb, x + 4, bottom, (DIM_TEXT, PANEL), label);
+}
+
+fn draw_heap(fb: &mut Framebuffer, heap: &HeapStats) {
+    let width = fb.width() - 2 * MARGIN;
+
+    fb.fill_rect(MARGIN, HEAP_TOP, width, CHAR_HEIGHT, BACKGROUND);
+    let label = format_args!(
+        "heap: {} / {} KB, {} allocations, {} failures",
+        heap.used / 1024,
+        heap.size / 1024,
+        heap.allocations,
+        heap.failures
+    );
+    draw_label(fb, MARGIN, HEAP_TOP, (TEXT, BACKGROUND), label);
+
+    let color = if heap.failures > 0 { Color::RED } else { Color::YELLOW };
+    let bar_y = HEAP_TOP + CHAR_HEIGHT + 4;
+    draw_bar(fb, MARGIN, bar_y, width, (heap.used as u64, heap.size as u64), color);
+}
+
+// One panel per framebuffer lock, so IRQs are never masked for a whole
+// redraw. The allocator lock is not IRQ safe: its stats are read first.
+fn draw_dashboard(dashboard: &Dashboard) {
+    let heap = ALLOCATOR.stats();
+
+    framebuffer::with(draw_uptime);
+    framebuffer::with(draw_cores);
+    framebuffer::with(|fb| {
+        let graph_width = (fb.width() - 3 * MARGIN) * 3 / 5;
+        draw_tick_graph(fb, dashboard, MARGIN, graph_width);
+    });
+    framebuffer::with(|fb| {
+        let graph_width = (fb.width() - 3 * MARGIN) * 3 / 5;
+        let table_x = 2 * MARGIN + graph_width;
+        draw_irq_table(fb, table_x, fb.width() - MARGIN - table_x);
+    });
+    framebuffer::with(|fb| draw_heap(fb, &heap));
+}
+
+// Periodic timer callback (IRQ context): sample the tick rate and ask for
+// a redraw
+fn refresh(_id: queue::TimerId, _data: usize) {
+    let mut dashboard = DASHBOARD.lock();
+
+    let ticks = timer::get_tick_count();
+    let delta = ticks.wrapping_sub(dashboard.last_ticks);
+    dashboard.last_ticks = ticks;
+
+    let rate = delta * 1000 / REFRESH_PERIOD.as_millis() as u64;
+    let next = dashboard.next;
+    dashboard.history[next] = rate.min(u32::MAX as u64) as u32;
+    dashboard.next = (next + 1) % HISTORY;
+    dashboard.samples += 1;
+
+    REDRAW.store(true, Ordering::Release);
+}
+
+pub fn redraw_pending() -> bool {
+    REDRAW.load(Ordering::Acquire)
+}
+
+// Redraw requested by the timer, from task context (see drivers::poll)
+pub fn poll() {
+    if REDRAW.swap(false, Ordering::AcqRel) {
+        let dashboard = *DASHBOARD.lock();
+        draw_dashboard(&dashboard);
+    }
+}
+
+pub fn init() {
+    let info = match framebuffer::init() {
+        Ok(_) => framebuffer::with(|fb| (fb.width(), fb.height(), fb.base(), fb.size())),
+        Err(error) => {
+            println!("Display: no framebuffer ({:?}), using console", error);
+            None
         }
-        
-        // Use println! for detailed info since format! needs alloc
-        println!("  System Time: {} ms", time_ms);
-        println!("  Timer Ticks: {}", tick_count);
-        println!("  Loop Counter: {}", counter);
-        
-        DISPLAY.write_line("---");
+    };
+    let Some((width, height, base, size)) = info else {
+        return;
+    };
+
+    // Printed without the framebuffer lock held: the text console mirrors
+    // console output onto the framebuffer
+    println!("Display: {}x{} framebuffer at 0x{:08x} ({} KB)", width, height, base, size / 1024);
+    if width < MIN_WIDTH || height < MIN_HEIGHT {
+        println!("Display: dashboard needs at least {}x{}, console only", MIN_WIDTH, MIN_HEIGHT);
+        fbcon::init(0);
+        return;
+    }
+
+    framebuffer::with(draw_background);
+    fbcon::init(STATUS_HEIGHT);
+
+    DASHBOARD.lock().last_ticks = timer::get_tick_count();
+    if queue::periodic_every(REFRESH_PERIOD, refresh, 0).is_none() {
+        println!("Display: no free timer, dashboard not refreshed");
     }
 }
@@ -0,0 +1,510 @@
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicU32, Ordering};
+use spin::Mutex;
+use crate::arch::exceptions::interrupts_enabled;
+use crate::drivers::{gic, mailbox};
+use crate::println;
+use crate::time::{self, Duration, Instant};
+
+// BCM2835 DMA engine: channels 0-6 are full channels (30-bit length, 2D
+// mode), 7-14 are "lite" channels (16-bit length, half the bandwidth).
+// Channel 15 lives elsewhere and is left to the firmware. A transfer is a
+// chain of 32-byte aligned control blocks that the engine fetches from
+// memory on its own.
+//
+// The engine sees memory through the VideoCore bus: RAM is given with the
+// uncached 0xC0000000 alias (the ARM data cache is off, so there is
+// nothing to clean or invalidate) and peripheral registers at 0x7E000000.
+const DMA_BASE: usize = 0x3F00_7000;
+const CHANNEL_STRIDE: usize = 0x100;
+
+const DMA_INT_STATUS: usize = DMA_BASE + 0xFE0;
+const DMA_ENABLE: usize = DMA_BASE + 0xFF0;
+
+// Per-channel registers
+const CS: usize = 0x00;
+const CONBLK_AD: usize = 0x04;
+const DEBUG: usize = 0x20;
+
+// CS bits
+const CS_ACTIVE: u32 = 1 << 0;
+const CS_END: u32 = 1 << 1;
+const CS_INT: u32 = 1 << 2;
+const CS_ERROR: u32 = 1 << 8;
+const CS_WAIT_FOR_OUTSTANDING_WRITES: u32 = 1 << 28;
+const CS_RESET: u32 = 1 << 31;
+
+// TI (transfer information) bits
+const TI_INTEN: u32 = 1 << 0;
+const TI_WAIT_RESP: u32 = 1 << 3;
+const TI_DEST_INC: u32 = 1 << 4;
+const TI_DEST_WIDTH_128: u32 = 1 << 5;
+const TI_DEST_DREQ: u32 = 1 << 6;
+const TI_SRC_INC: u32 = 1 << 8;
+const TI_SRC_WIDTH_128: u32 = 1 << 9;
+const TI_SRC_DREQ: u32 = 1 << 10;
+const TI_BURST_SHIFT: u32 = 12;
+const TI_PERMAP_SHIFT: u32 = 16;
+
+// DEBUG error bits (write 1 to clear)
+const DEBUG_ERRORS: u32 = 0b111;
+
+const CHANNELS: usize = 15;
+const FIRST_LITE: usize = 7;
+const CHANNEL_MASK: u32 = (1 << CHANNELS) - 1;
+
+// Used when the firmware does not answer (what it normally reports)
+const DEFAULT_CHANNEL_MASK: u32 = 0x7F35;
+
+const FULL_MAX_LENGTH: usize = (1 << 30) - 1;
+const LITE_MAX_LENGTH: usize = 0xFFFF;
+// Chunk size for copies on lite channels, keeping later chunks aligned
+const LITE_CHUNK: usize = 0xFFF0;
+
+const MEMCPY_BURST: u32 = 4;
+
+// Control blocks built on the stack for one memcpy round
+const MAX_CHAIN: usize = 16;
+
+// RAM the engine can reach through the bus alias (peripherals start here)
+const RAM_LIMIT: usize = 0x3F00_0000;
+const BUS_RAM_UNCACHED: u32 = 0xC000_0000;
+const PERIPHERAL_BASE: usize = 0x3F00_0000;
+const PERIPHERAL_END: usize = 0x4000_0000;
+const BUS_PERIPHERAL_BASE: u32 = 0x7E00_0000;
+
+const MEMCPY_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Idle slice while waiting for the completion IRQ
+const WAIT_SLICE: Duration = Duration::from_millis(1);
+
+// Peripherals that pace a transfer through their DREQ line
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dreq {
+    Dsi = 1,
+    PcmTx = 2,
+    PcmRx = 3,
+    Smi = 4,
+    Pwm = 5,
+    SpiTx = 6,
+    SpiRx = 7,
+    SlaveTx = 8,
+    SlaveRx = 9,
+    Emmc = 11,
+    UartTx = 12,
+    SdHost = 13,
+    UartRx = 14,
+    Hdmi = 17,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    // A lite channel if one is free, keeping full channels for big copies
+    Any,
+    Full,
+    #[allow(dead_code)]
+    Lite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmaError {
+    // No free channel of the requested kind
+    NoChannel,
+    // Address the engine cannot reach (or an empty chain)
+    BadAddress,
+    // Zero length, too long for the channel or mismatched buffers
+    BadLength,
+    Timeout,
+    // The engine stopped with an error (DEBUG register bits)
+    Bus(u32),
+    // The benchmark copy does not match its source
+    Corrupted,
+}
+
+// Control block as read by the engine; `next` is filled in by Channel::start
+#[repr(C, align(32))]
+#[derive(Debug, Clone, Copy)]
+pub struct ControlBlock {
+    ti: u32,
+    source: u32,
+    dest: u32,
+    length: u32,
+    stride: u32,
+    next: u32,
+    _reserved: [u32; 2],
+}
+
+pub fn ram_bus_address(address: usize) -> Result<u32, DmaError> {
+    if address < RAM_LIMIT {
+        Ok(address as u32 | BUS_RAM_UNCACHED)
+    } else {
+        Err(DmaError::BadAddress)
+    }
+}
+
+fn peripheral_bus_address(register: usize) -> Result<u32, DmaError> {
+    if (PERIPHERAL_BASE..PERIPHERAL_END).contains(&register) {
+        Ok((register - PERIPHERAL_BASE) as u32 + BUS_PERIPHERAL_BASE)
+    } else {
+        Err(DmaError::BadAddress)
+    }
+}
+
+impl ControlBlock {
+    const EMPTY: ControlBlock = ControlBlock {
+        ti: 0,
+        source: 0,
+        dest: 0,
+        length: 0,
+        stride: 0,
+        next: 0,
+        _reserved: [0; 2],
+    };
+
+    fn new(ti: u32, source: u32, dest: u32, length: usize) -> Result<Self, DmaError> {
+        if length == 0 || length > FULL_MAX_LENGTH {
+            return Err(DmaError::BadLength);
+        }
+        Ok(ControlBlock { ti, source, dest, length: length as u32, ..Self::EMPTY })
+    }
+
+    // Memory to memory copy. The block keeps raw bus addresses: both
+    // buffers must stay alive until the transfer is over.
+    pub fn memcpy(dest: &mut [u8], source: &[u8]) -> Result<Self, DmaError> {
+        if dest.len() != source.len() {
+            return Err(DmaError::BadLength);
+        }
+
+        let (dest_address, source_address) = (dest.as_mut_ptr() as usize, source.as_ptr() as usize);
+        let mut ti = TI_SRC_INC | TI_DEST_INC | TI_WAIT_RESP | MEMCPY_BURST << TI_BURST_SHIFT;
+        if dest_address.is_multiple_of(16) && source_address.is_multiple_of(16) {
+            ti |= TI_SRC_WIDTH_128 | TI_DEST_WIDTH_128;
+        }
+        Self::new(ti, ram_bus_address(source_address)?, ram_bus_address(dest_address)?, source.len())
+    }
+
+    // Memory to a peripheral FIFO register, paced by the peripheral's DREQ
+    #[allow(dead_code)]
+    pub fn to_peripheral(register: usize, source: &[u8], dreq: Dreq) -> Result<Self, DmaError> {
+        let ti = TI_SRC_INC | TI_DEST_DREQ | TI_WAIT_RESP | (dreq as u32) << TI_PERMAP_SHIFT;
+        let source_address = ram_bus_address(source.as_ptr() as usize)?;
+        Self::new(ti, source_address, peripheral_bus_address(register)?, source.len())
+    }
+
+    // Peripheral FIFO register to memory, paced by the peripheral's DREQ
+    #[allow(dead_code)]
+    pub fn from_peripheral(register: usize, dest: &mut [u8], dreq: Dreq) -> Result<Self, DmaError> {
+        let ti = TI_DEST_INC | TI_SRC_DREQ | TI_WAIT_RESP | (dreq as u32) << TI_PERMAP_SHIFT;
+        let dest_address = ram_bus_address(dest.as_mut_ptr() as usize)?;
+        Self::new(ti, peripheral_bus_address(register)?, dest_address, dest.len())
+    }
+
+    pub fn length(&self) -> usize {
+        self.length as usize
+    }
+}
+
+// Channels the firmware leaves to us, and the ones handed out
+static USABLE: AtomicU32 = AtomicU32::new(0);
+static ALLOCATED: AtomicU32 = AtomicU32::new(0);
+
+// CS bits latched by the completion IRQ, per channel
+static EVENTS: [AtomicU32; CHANNELS] = [const { AtomicU32::new(0) }; CHANNELS];
+
+fn register(channel: usize, offset: usize) -> *mut u32 {
+    (DMA_BASE + channel * CHANNEL_STRIDE + offset) as *mut u32
+}
+
+fn read(channel: usize, offset: usize) -> u32 {
+    unsafe { read_volatile(register(channel, offset)) }
+}
+
+fn write(channel: usize, offset: usize, value: u32) {
+    unsafe { write_volatile(register(channel, offset), value) }
+}
+
+// Channels 0-10 have their own IRQ, 11-14 share IRQ 27
+fn channel_irq(channel: usize) -> u32 {
+    16 + channel.min(11) as u32
+}
+
+fn wait_until(timeout: Duration, mut condition: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if condition() {
+            return true;
+        }
+        if deadline.has_passed() {
+            return false;
+        }
+        if interrupts_enabled() {
+            time::idle::idle_until(deadline.min(Instant::now() + WAIT_SLICE));
+        } else {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+// An allocated channel, released when dropped
+pub struct Channel {
+    index: usize,
+}
+
+impl Channel {
+    #[allow(dead_code)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn is_lite(&self) -> bool {
+        self.index >= FIRST_LITE
+    }
+
+    // Longest single control block the channel accepts
+    pub fn max_length(&self) -> usize {
+        if self.is_lite() { LITE_MAX_LENGTH } else { FULL_MAX_LENGTH }
+    }
+
+    fn reset(&self) {
+        write(self.index, CS, CS_RESET);
+        write(self.index, DEBUG, DEBUG_ERRORS);
+        EVENTS[self.index].store(0, Ordering::Release);
+    }
+
+    // Link the blocks into a chain (IRQ on the last one) and start it.
+    //
+    // Safety: the blocks and every buffer they point to must stay alive
+    // and untouched until the channel is idle again (wait or abort).
+    pub unsafe fn start(&mut self, blocks: &mut [ControlBlock]) -> Result<(), DmaError> {
+        let Some(first) = blocks.first() else {
+            return Err(DmaError::BadAddress);
+        };
+        if blocks.iter().any(|block| block.length() == 0 || block.length() > self.max_length()) {
+            return Err(DmaError::BadLength);
+        }
+        let first = ram_bus_address(first as *const ControlBlock as usize)?;
+
+        let count = blocks.len();
+        for index in 0..count {
+            let next = match blocks.get(index + 1) {
+                Some(block) => ram_bus_address(block as *const ControlBlock as usize)?,
+                None => 0,
+            };
+            let block = &mut blocks[index];
+            block.next = next;
+            block.ti = if next == 0 { block.ti | TI_INTEN } else { block.ti & !TI_INTEN };
+        }
+
+        self.reset();
+        // The chain must be in memory before the engine fetches it
+        core::arch::asm!("dsb sy", options(nostack));
+        write(self.index, CONBLK_AD, first);
+        write(self.index, CS, CS_ACTIVE | CS_WAIT_FOR_OUTSTANDING_WRITES);
+        Ok(())
+    }
+
+    // The whole chain is done once the engine has loaded the null link
+    pub fn is_busy(&self) -> bool {
+        read(self.index, CS) & CS_ACTIVE != 0 || read(self.index, CONBLK_AD) != 0
+    }
+
+    fn error(&self) -> Option<u32> {
+        let status = read(self.index, CS) | EVENTS[self.index].load(Ordering::Acquire);
+        (status & CS_ERROR != 0).then(|| read(self.index, DEBUG) & DEBUG_ERRORS)
+    }
+
+    pub fn wait(&mut self, timeout: Duration) -> Result<(), DmaError> {
+        let mut error = None;
+        let done = wait_until(timeout, || {
+            error = self.error();
+            error.is_some() || !self.is_busy()
+        });
+
+        if let Some(bits) = error {
+            self.abort();
+            return Err(DmaError::Bus(bits));
+        }
+        if !done {
+            self.abort();
+            return Err(DmaError::Timeout);
+        }
+        unsafe { core::arch::asm!("dsb sy", options(nostack)) };
+        Ok(())
+    }
+
+    pub fn abort(&mut self) {
+        self.reset();
+    }
+
+    // Run a chain to completion
+    pub fn run(&mut self, blocks: &mut [ControlBlock], timeout: Duration) -> Result<(), DmaError> {
+        unsafe { self.start(blocks)? };
+        self.wait(timeout)
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        if self.is_busy() {
+            self.abort();
+        }
+        ALLOCATED.fetch_and(!(1 << self.index), Ordering::AcqRel);
+    }
+}
+
+pub fn allocate(kind: ChannelKind) -> Result<Channel, DmaError> {
+    let lite = CHANNEL_MASK & !((1 << FIRST_LITE) - 1);
+    let candidates = USABLE.load(Ordering::Acquire)
+        & match kind {
+            ChannelKind::Any | ChannelKind::Lite => lite,
+            ChannelKind::Full => !lite,
+        };
+
+    let mut allocated = ALLOCATED.load(Ordering::Acquire);
+    loop {
+        let free = candidates & !allocated;
+        if free == 0 {
+            return match kind {
+                ChannelKind::Any => allocate(ChannelKind::Full),
+                _ => Err(DmaError::NoChannel),
+            };
+        }
+
+        let index = free.trailing_zeros() as usize;
+        match ALLOCATED.compare_exchange(allocated, allocated | 1 << index, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => return Ok(Channel { index }),
+            Err(current) => allocated = current,
+        }
+    }
+}
+
+// Copy `source` into `dest` with the DMA engine, as a chain of up to
+// MAX_CHAIN blocks per round
+pub fn memcpy(dest: &mut [u8], source: &[u8]) -> Result<(), DmaError> {
+    if dest.len() != source.len() {
+        return Err(DmaError::BadLength);
+    }
+    if source.is_empty() {
+        return Ok(());
+    }
+
+    let mut channel = allocate(ChannelKind::Full).or_else(|_| allocate(ChannelKind::Any))?;
+    let chunk = if channel.is_lite() { LITE_CHUNK } else { FULL_MAX_LENGTH & !0xF };
+
+    let mut blocks = [ControlBlock::EMPTY; MAX_CHAIN];
+    let mut sources = source.chunks(chunk);
+    let mut dests = dest.chunks_mut(chunk);
+    loop {
+        let mut count = 0;
+        for (block, (dest, source)) in blocks.iter_mut().zip(dests.by_ref().zip(sources.by_ref())) {
+            *block = ControlBlock::memcpy(dest, source)?;
+            count += 1;
+        }
+        if count == 0 {
+            return Ok(());
+        }
+        channel.run(&mut blocks[..count], MEMCPY_TIMEOUT)?;
+    }
+}
+
+// Completion IRQ, shared by every channel: latch and acknowledge. Only
+// channels allocated here are touched (the firmware owns the others), and
+// ACTIVE is written back as read so a running transfer is not paused.
+fn dma_interrupt_handler() {
+    let ours = USABLE.load(Ordering::Acquire) & ALLOCATED.load(Ordering::Acquire);
+    let pending = unsafe { read_volatile(DMA_INT_STATUS as *const u32) } & ours;
+    for channel in (0..CHANNELS).filter(|channel| pending & (1 << channel) != 0) {
+        let status = read(channel, CS);
+        write(channel, CS, CS_INT | CS_END | (status & CS_ACTIVE));
+        EVENTS[channel].fetch_or(status, Ordering::AcqRel);
+    }
+}
+
+const BENCH_BUFFER: usize = 64 * 1024;
+
+#[repr(C, align(32))]
+struct BenchBuffers {
+    source: [u8; BENCH_BUFFER],
+    dest: [u8; BENCH_BUFFER],
+}
+
+static BENCH: Mutex<BenchBuffers> = Mutex::new(BenchBuffers {
+    source: [0; BENCH_BUFFER],
+    dest: [0; BENCH_BUFFER],
+});
+
+#[derive(Debug, Clone, Copy)]
+pub struct BenchResult {
+    pub bytes: usize,
+    pub rounds: u32,
+    pub cpu: Duration,
+    pub dma: Duration,
+}
+
+pub fn bench_max_bytes() -> usize {
+    BENCH_BUFFER
+}
+
+// Time `rounds` copies of `bytes` bytes with the CPU and with the engine,
+// checking the DMA result
+pub fn benchmark(bytes: usize, rounds: u32) -> Result<BenchResult, DmaError> {
+    if bytes == 0 || bytes > BENCH_BUFFER || rounds == 0 {
+        return Err(DmaError::BadLength);
+    }
+
+    let mut buffers = BENCH.lock();
+    let BenchBuffers { source, dest } = &mut *buffers;
+    let (source, dest) = (&mut source[..bytes], &mut dest[..bytes]);
+    for (index, byte) in source.iter_mut().enumerate() {
+        *byte = (index * 7 + index / 256) as u8;
+    }
+
+    let start = Instant::now();
+    for _ in 0..rounds {
+        dest.copy_from_slice(core::hint::black_box(&*source));
+        core::hint::black_box(&mut *dest);
+    }
+    let cpu = start.elapsed();
+
+    dest.fill(0);
+    let start = Instant::now();
+    for _ in 0..rounds {
+        memcpy(dest, source)?;
+    }
+    let dma = start.elapsed();
+
+    if dest != source {
+        return Err(DmaError::Corrupted);
+    }
+    Ok(BenchResult { bytes, rounds, cpu, dma })
+}
+
+// (usable, allocated) channel masks
+pub fn channels() -> (u32, u32) {
+    (USABLE.load(Ordering::Acquire), ALLOCATED.load(Ordering::Acquire))
+}
+
+pub fn init() {
+    let mask = mailbox::dma_channels().unwrap_or(DEFAULT_CHANNEL_MASK) & CHANNEL_MASK;
+
+    for channel in (0..CHANNELS).filter(|channel| mask & (1 << channel) != 0) {
+        write(channel, CS, CS_RESET);
+        write(channel, DEBUG, DEBUG_ERRORS);
+        gic::register_irq_handler(channel_irq(channel), dma_interrupt_handler);
+        gic::enable_irq(channel_irq(channel));
+    }
+    unsafe {
+        let enabled = read_volatile(DMA_ENABLE as *const u32);
+        write_volatile(DMA_ENABLE as *mut u32, enabled | mask);
+    }
+    USABLE.store(mask, Ordering::Release);
+
+    let lite = (mask >> FIRST_LITE).count_ones();
+    println!(
+        "DMA: channel mask 0x{:04x} ({} full, {} lite)",
+        mask,
+        mask.count_ones() - lite,
+        lite
+    );
+}
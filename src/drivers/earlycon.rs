@@ -0,0 +1,74 @@
+use core::fmt;
+use core::ptr::{read_volatile, write_volatile};
+
+// Early console: a lock-free, poll-only PL011 writer usable from boot.rs
+// and rust_main before drivers::init(). It touches nothing but the UART
+// registers (no statics, no locks), so it also works before the BSS is
+// cleared and after the console lock was lost in a panic.
+
+const UART_BASE: usize = 0x3F20_1000;
+
+const UART_DR: usize = UART_BASE;
+const UART_FR: usize = UART_BASE + 0x18;
+const UART_IBRD: usize = UART_BASE + 0x24;
+const UART_FBRD: usize = UART_BASE + 0x28;
+const UART_LCR_H: usize = UART_BASE + 0x2C;
+const UART_CR: usize = UART_BASE + 0x30;
+
+const FR_BUSY: u32 = 1 << 3;
+const FR_TXFF: u32 = 1 << 5;
+
+// 115200 baud from the 48 MHz reference clock, 8N1 with FIFOs
+const IBRD_115200: u32 = 26;
+const FBRD_115200: u32 = 3;
+const LCR_H_8N1_FIFO: u32 = (0b11 << 5) | (1 << 4);
+const CR_ENABLE_TX_RX: u32 = (1 << 0) | (1 << 8) | (1 << 9);
+
+// Called from boot.rs right after the stack is set up. If the firmware
+// already enabled the UART its settings are kept.
+#[no_mangle]
+pub extern "C" fn earlycon_init() {
+    unsafe {
+        if read_volatile(UART_CR as *const u32) & 1 != 0 {
+            return;
+        }
+
+        write_volatile(UART_CR as *mut u32, 0);
+        write_volatile(UART_IBRD as *mut u32, IBRD_115200);
+        write_volatile(UART_FBRD as *mut u32, FBRD_115200);
+        write_volatile(UART_LCR_H as *mut u32, LCR_H_8N1_FIFO);
+        write_volatile(UART_CR as *mut u32, CR_ENABLE_TX_RX);
+    }
+}
+
+pub fn write_byte(byte: u8) {
+    unsafe {
+        while read_volatile(UART_FR as *const u32) & FR_TXFF != 0 {}
+        write_volatile(UART_DR as *mut u32, byte as u32);
+    }
+}
+
+pub fn write_str(s: &str) {
+    for byte in s.bytes() {
+        write_byte(byte);
+    }
+}
+
+// Wait until the last character left the shift register (before the full
+// driver reprograms the UART)
+pub fn flush() {
+    unsafe { while read_volatile(UART_FR as *const u32) & FR_BUSY != 0 {} }
+}
+
+pub struct EarlyWriter;
+
+impl fmt::Write for EarlyWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_str(s);
+        Ok(())
+    }
+}
+
+pub fn write_fmt(args: fmt::Arguments) {
+    let _ = fmt::Write::write_fmt(&mut EarlyWriter, args);
+}
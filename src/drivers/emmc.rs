@@ -0,0 +1,578 @@
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicU32, Ordering};
+use spin::Mutex;
+use crate::arch::exceptions::interrupts_enabled;
+use crate::drivers::block::{self, BlockDevice, BlockError, SECTOR_SIZE};
+use crate::drivers::gpio::{self, Function, Pull};
+use crate::drivers::mailbox::{self, ClockId, PowerDevice};
+use crate::drivers::gic;
+use crate::println;
+use crate::time::{self, Duration, Instant};
+
+// Arasan SDHCI controller (EMMC) driving the SD card slot. Commands and
+// data completion are signalled by the EMMC interrupt; the data itself
+// moves by PIO through the DATA register. While IRQs are masked (during
+// boot) the same status bits are polled instead.
+
+const EMMC_BASE: usize = 0x3F30_0000;
+
+const EMMC_BLKSIZECNT: usize = EMMC_BASE + 0x04;
+const EMMC_ARG1: usize = EMMC_BASE + 0x08;
+const EMMC_CMDTM: usize = EMMC_BASE + 0x0C;
+const EMMC_RESP0: usize = EMMC_BASE + 0x10;
+const EMMC_DATA: usize = EMMC_BASE + 0x20;
+const EMMC_STATUS: usize = EMMC_BASE + 0x24;
+const EMMC_CONTROL0: usize = EMMC_BASE + 0x28;
+const EMMC_CONTROL1: usize = EMMC_BASE + 0x2C;
+const EMMC_INTERRUPT: usize = EMMC_BASE + 0x30;
+const EMMC_IRPT_MASK: usize = EMMC_BASE + 0x34;
+const EMMC_IRPT_EN: usize = EMMC_BASE + 0x38;
+
+pub const IRQ_EMMC: u32 = 62;
+
+// CONTROL0
+const C0_HCTL_DWIDTH: u32 = 1 << 1;
+const C0_HCTL_HS_EN: u32 = 1 << 2;
+
+// CONTROL1
+const C1_CLK_INTLEN: u32 = 1 << 0;
+const C1_CLK_STABLE: u32 = 1 << 1;
+const C1_CLK_EN: u32 = 1 << 2;
+const C1_CLK_FREQ_MASK: u32 = 0xFFC0;
+const C1_TOUNIT_MAX: u32 = 0xE << 16;
+const C1_SRST_HC: u32 = 1 << 24;
+const C1_SRST_CMD: u32 = 1 << 25;
+const C1_SRST_DATA: u32 = 1 << 26;
+
+// STATUS
+const SR_CMD_INHIBIT: u32 = 1 << 0;
+const SR_DAT_INHIBIT: u32 = 1 << 1;
+
+// INTERRUPT / IRPT_MASK / IRPT_EN
+const INT_CMD_DONE: u32 = 1 << 0;
+const INT_DATA_DONE: u32 = 1 << 1;
+const INT_WRITE_RDY: u32 = 1 << 4;
+const INT_READ_RDY: u32 = 1 << 5;
+const INT_ERROR_MASK: u32 = 0xFFFF_8000;
+
+// Raised to the GIC; the buffer-ready flags are polled during PIO
+const INT_SIGNALLED: u32 = INT_CMD_DONE | INT_DATA_DONE | INT_ERROR_MASK;
+
+// CMDTM: response type, checks and transfer mode
+const CMD_RSPNS_136: u32 = 1 << 16;
+const CMD_RSPNS_48: u32 = 2 << 16;
+const CMD_RSPNS_48B: u32 = 3 << 16;
+const CMD_CRCCHK_EN: u32 = 1 << 19;
+const CMD_IXCHK_EN: u32 = 1 << 20;
+const CMD_ISDATA: u32 = 1 << 21;
+const TM_BLKCNT_EN: u32 = 1 << 1;
+const TM_AUTO_CMD12: u32 = 1 << 2;
+const TM_DAT_DIR_READ: u32 = 1 << 4;
+const TM_MULTI_BLOCK: u32 = 1 << 5;
+
+const R1: u32 = CMD_RSPNS_48 | CMD_CRCCHK_EN | CMD_IXCHK_EN;
+const R1B: u32 = CMD_RSPNS_48B | CMD_CRCCHK_EN | CMD_IXCHK_EN;
+const R2: u32 = CMD_RSPNS_136 | CMD_CRCCHK_EN;
+const R3: u32 = CMD_RSPNS_48;
+const READ: u32 = CMD_ISDATA | TM_DAT_DIR_READ;
+const MULTI: u32 = TM_BLKCNT_EN | TM_MULTI_BLOCK | TM_AUTO_CMD12;
+
+const fn command(index: u32, flags: u32) -> u32 {
+    index << 24 | flags
+}
+
+const CMD_GO_IDLE: u32 = command(0, 0);
+const CMD_ALL_SEND_CID: u32 = command(2, R2);
+const CMD_SEND_REL_ADDR: u32 = command(3, R1);
+const CMD_SWITCH_FUNC: u32 = command(6, R1 | READ);
+const CMD_SELECT_CARD: u32 = command(7, R1B);
+const CMD_SEND_IF_COND: u32 = command(8, R1);
+const CMD_SEND_CSD: u32 = command(9, R2);
+const CMD_SET_BLOCKLEN: u32 = command(16, R1);
+const CMD_READ_SINGLE: u32 = command(17, R1 | READ);
+const CMD_READ_MULTI: u32 = command(18, R1 | READ | MULTI);
+const CMD_WRITE_SINGLE: u32 = command(24, R1 | CMD_ISDATA);
+const CMD_WRITE_MULTI: u32 = command(25, R1 | CMD_ISDATA | MULTI);
+const CMD_APP_CMD: u32 = command(55, R1);
+const ACMD_SET_BUS_WIDTH: u32 = command(6, R1);
+const ACMD_SEND_OP_COND: u32 = command(41, R3);
+const ACMD_SEND_SCR: u32 = command(51, R1 | READ);
+
+// CMD8 argument: 2.7-3.6 V, check pattern 0xAA
+const IF_COND_PATTERN: u32 = 0x1AA;
+
+// ACMD41 / OCR
+const OCR_VOLTAGE_WINDOW: u32 = 0x00FF_8000;
+const OCR_HCS: u32 = 1 << 30;
+const OCR_BUSY: u32 = 1 << 31;
+
+// CMD6: switch function group 1 to high speed
+const SWITCH_HIGH_SPEED: u32 = 0x80FF_FFF1;
+
+const IDENT_CLOCK_HZ: u32 = 400_000;
+const DEFAULT_CLOCK_HZ: u32 = 25_000_000;
+const HIGH_SPEED_CLOCK_HZ: u32 = 50_000_000;
+
+// Used when the firmware does not report the EMMC clock
+const DEFAULT_BASE_CLOCK_HZ: u32 = 100_000_000;
+
+// The block count field of BLKSIZECNT is 16 bits wide
+const MAX_BLOCKS_PER_TRANSFER: u64 = 0xFFFF;
+
+const RESET_TIMEOUT: Duration = Duration::from_millis(100);
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(100);
+const DATA_TIMEOUT: Duration = Duration::from_millis(1000);
+const POWER_UP_TIMEOUT: Duration = Duration::from_millis(1000);
+
+// Longest idle between status checks when waiting with IRQs enabled (the
+// EMMC interrupt may be taken by another core)
+const WAIT_SLICE: Duration = Duration::from_millis(1);
+
+// GPIO 47 is card detect, 48-53 are CLK, CMD and DAT0-3 (ALT3)
+const CARD_DETECT_PIN: u8 = 47;
+const SD_PINS: core::ops::RangeInclusive<u8> = 48..=53;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmmcError {
+    Timeout,
+    // Error bits of the INTERRUPT register for the command index
+    Command { index: u8, status: u32 },
+}
+
+impl From<EmmcError> for BlockError {
+    fn from(error: EmmcError) -> Self {
+        match error {
+            EmmcError::Timeout => BlockError::Timeout,
+            _ => BlockError::Io,
+        }
+    }
+}
+
+// Status bits collected by the interrupt handler
+static PENDING: AtomicU32 = AtomicU32::new(0);
+
+fn read(register: usize) -> u32 {
+    unsafe { read_volatile(register as *const u32) }
+}
+
+fn write(register: usize, value: u32) {
+    unsafe { write_volatile(register as *mut u32, value) }
+}
+
+fn emmc_interrupt_handler() {
+    let status = read(EMMC_INTERRUPT) & INT_SIGNALLED;
+    write(EMMC_INTERRUPT, status);
+    PENDING.fetch_or(status, Ordering::AcqRel);
+}
+
+// Wait until `condition` holds: idle between checks when the interrupt can
+// wake us, spin otherwise
+fn wait_until(timeout: Duration, mut condition: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if condition() {
+            return true;
+        }
+        if deadline.has_passed() {
+            return false;
+        }
+        if interrupts_enabled() {
+            time::idle::idle_until(deadline.min(Instant::now() + WAIT_SLICE));
+        } else {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Card {
+    // Relative card address, already shifted into bits 31:16
+    rca: u32,
+    // SDHC/SDXC cards are addressed by block, SDSC cards by byte
+    high_capacity: bool,
+    blocks: u64,
+    bus_width: u8,
+    high_speed: bool,
+}
+
+struct Controller {
+    base_clock: u32,
+    card: Option<Card>,
+}
+
+impl Controller {
+    // Reset the CMD and DAT state machines after an error
+    fn reset_lines(&self) {
+        write(EMMC_CONTROL1, read(EMMC_CONTROL1) | C1_SRST_CMD | C1_SRST_DATA);
+        time::wait_for(RESET_TIMEOUT, || read(EMMC_CONTROL1) & (C1_SRST_CMD | C1_SRST_DATA) == 0);
+    }
+
+    // Wait for all bits of `mask`, or fail on the first error bit. Status
+    // bits outside `mask` stay pending for the next wait.
+    fn wait_status(&self, mask: u32, index: u8, timeout: Duration) -> Result<(), EmmcError> {
+        let mut seen = 0;
+        let done = wait_until(timeout, || {
+            // Also polled, for when the IRQ cannot be taken
+            let polled = read(EMMC_INTERRUPT) & INT_SIGNALLED;
+            if polled != 0 {
+                write(EMMC_INTERRUPT, polled);
+            }
+            seen |= PENDING.swap(0, Ordering::AcqRel) | polled;
+            seen & mask == mask || seen & INT_ERROR_MASK != 0
+        });
+
+        if seen & INT_ERROR_MASK != 0 {
+            self.reset_lines();
+            return Err(EmmcError::Command { index, status: seen & INT_ERROR_MASK });
+        }
+        PENDING.fetch_or(seen & !mask, Ordering::AcqRel);
+        if done {
+            Ok(())
+        } else {
+            self.reset_lines();
+            Err(EmmcError::Timeout)
+        }
+    }
+
+    // Poll a buffer-ready flag (PIO), checking for errors on the way
+    fn wait_buffer(&self, flag: u32, index: u8) -> Result<(), EmmcError> {
+        let mut status = 0;
+        let ready = time::wait_for(DATA_TIMEOUT, || {
+            status = read(EMMC_INTERRUPT) | PENDING.load(Ordering::Acquire);
+            status & (flag | INT_ERROR_MASK) != 0
+        });
+
+        if status & INT_ERROR_MASK != 0 {
+            PENDING.fetch_and(!INT_ERROR_MASK, Ordering::AcqRel);
+            self.reset_lines();
+            return Err(EmmcError::Command { index, status: status & INT_ERROR_MASK });
+        }
+        if !ready {
+            self.reset_lines();
+            return Err(EmmcError::Timeout);
+        }
+        write(EMMC_INTERRUPT, flag);
+        Ok(())
+    }
+
+    fn send_command(&self, command: u32, argument: u32) -> Result<[u32; 4], EmmcError> {
+        let index = (command >> 24) as u8;
+        let busy = command & CMD_RSPNS_48B == CMD_RSPNS_48B;
+        let mut inhibit = SR_CMD_INHIBIT;
+        if busy || command & CMD_ISDATA != 0 {
+            inhibit |= SR_DAT_INHIBIT;
+        }
+        if !time::wait_for(COMMAND_TIMEOUT, || read(EMMC_STATUS) & inhibit == 0) {
+            self.reset_lines();
+            return Err(EmmcError::Timeout);
+        }
+
+        // Drop status left over from an earlier command
+        write(EMMC_INTERRUPT, INT_SIGNALLED);
+        PENDING.store(0, Ordering::Release);
+
+        write(EMMC_ARG1, argument);
+        write(EMMC_CMDTM, command);
+        self.wait_status(INT_CMD_DONE, index, COMMAND_TIMEOUT)?;
+
+        let mut response = [0; 4];
+        for (word, value) in response.iter_mut().enumerate() {
+            *value = read(EMMC_RESP0 + word * 4);
+        }
+
+        // R1b: the card signals the end of busy with DATA_DONE
+        if busy && command & CMD_ISDATA == 0 {
+            self.wait_status(INT_DATA_DONE, index, DATA_TIMEOUT)?;
+        }
+        Ok(response)
+    }
+
+    fn app_command(&self, command: u32, argument: u32) -> Result<[u32; 4], EmmcError> {
+        let rca = self.card.map_or(0, |card| card.rca);
+        self.send_command(CMD_APP_CMD, rca)?;
+        self.send_command(command, argument)
+    }
+
+    fn set_block_size_count(&self, block_size: usize, count: usize) {
+        write(EMMC_BLKSIZECNT, (count as u32) << 16 | block_size as u32);
+    }
+
+    // Data read: the command, one buffer-ready wait per block, then the
+    // transfer-complete interrupt
+    fn read_data(&self, command: u32, argument: u32, buf: &mut [u8], block_size: usize) -> Result<(), EmmcError> {
+        let index = (command >> 24) as u8;
+        self.set_block_size_count(block_size, buf.len() / block_size);
+        self.send_command(command, argument)?;
+
+        for block in buf.chunks_exact_mut(block_size) {
+            self.wait_buffer(INT_READ_RDY, index)?;
+            for word in block.chunks_exact_mut(4) {
+                word.copy_from_slice(&read(EMMC_DATA).to_le_bytes());
+            }
+        }
+        self.wait_status(INT_DATA_DONE, index, DATA_TIMEOUT)
+    }
+
+    fn write_data(&self, command: u32, argument: u32, buf: &[u8]) -> Result<(), EmmcError> {
+        let index = (command >> 24) as u8;
+        self.set_block_size_count(SECTOR_SIZE, buf.len() / SECTOR_SIZE);
+        self.send_command(command, argument)?;
+
+        for block in buf.chunks_exact(SECTOR_SIZE) {
+            self.wait_buffer(INT_WRITE_RDY, index)?;
+            for word in block.chunks_exact(4) {
+                write(EMMC_DATA, u32::from_le_bytes([word[0], word[1], word[2], word[3]]));
+            }
+        }
+        self.wait_status(INT_DATA_DONE, index, DATA_TIMEOUT)
+    }
+
+    // SD clock = base / (2 * divisor), 10-bit divisor (SDHCI 3.0)
+    fn set_clock(&self, hz: u32) -> Result<(), EmmcError> {
+        if !time::wait_for(COMMAND_TIMEOUT, || read(EMMC_STATUS) & (SR_CMD_INHIBIT | SR_DAT_INHIBIT) == 0) {
+            return Err(EmmcError::Timeout);
+        }
+
+        let mut control = read(EMMC_CONTROL1) & !C1_CLK_EN;
+        write(EMMC_CONTROL1, control);
+        time::udelay(10);
+
+        let divisor = self.base_clock.div_ceil(2 * hz).min(0x3FF);
+        control &= !C1_CLK_FREQ_MASK;
+        control |= (divisor & 0xFF) << 8 | (divisor >> 8) << 6;
+        write(EMMC_CONTROL1, control);
+        time::udelay(10);
+
+        write(EMMC_CONTROL1, control | C1_CLK_EN);
+        if !time::wait_for(RESET_TIMEOUT, || read(EMMC_CONTROL1) & C1_CLK_STABLE != 0) {
+            return Err(EmmcError::Timeout);
+        }
+        Ok(())
+    }
+
+    fn reset_host(&self) -> Result<(), EmmcError> {
+        write(EMMC_CONTROL0, 0);
+        write(EMMC_CONTROL1, C1_SRST_HC);
+        if !time::wait_for(RESET_TIMEOUT, || read(EMMC_CONTROL1) & C1_SRST_HC == 0) {
+            return Err(EmmcError::Timeout);
+        }
+
+        write(EMMC_CONTROL1, C1_CLK_INTLEN | C1_TOUNIT_MAX);
+        self.set_clock(IDENT_CLOCK_HZ)?;
+
+        // Every status bit visible, only completion and errors raise the IRQ
+        write(EMMC_IRPT_MASK, u32::MAX);
+        write(EMMC_IRPT_EN, INT_SIGNALLED);
+        write(EMMC_INTERRUPT, u32::MAX);
+        PENDING.store(0, Ordering::Release);
+        Ok(())
+    }
+
+    // Card identification (SD physical layer spec, section 4.2) followed
+    // by the switch to 4-bit bus and high speed
+    fn identify(&mut self) -> Result<Card, EmmcError> {
+        self.card = None;
+        self.reset_host()?;
+
+        self.send_command(CMD_GO_IDLE, 0)?;
+
+        // Version 2.00 cards echo the check pattern; older ones time out
+        let version2 = match self.send_command(CMD_SEND_IF_COND, IF_COND_PATTERN) {
+            Ok(response) => response[0] & 0xFFF == IF_COND_PATTERN,
+            Err(_) => false,
+        };
+
+        let argument = OCR_VOLTAGE_WINDOW | if version2 { OCR_HCS } else { 0 };
+        let deadline = Instant::now() + POWER_UP_TIMEOUT;
+        let ocr = loop {
+            let ocr = self.app_command(ACMD_SEND_OP_COND, argument)?[0];
+            if ocr & OCR_BUSY != 0 {
+                break ocr;
+            }
+            if deadline.has_passed() {
+                return Err(EmmcError::Timeout);
+            }
+            time::mdelay(10);
+        };
+
+        self.send_command(CMD_ALL_SEND_CID, 0)?;
+        let rca = self.send_command(CMD_SEND_REL_ADDR, 0)?[0] & 0xFFFF_0000;
+        let blocks = capacity_blocks(self.send_command(CMD_SEND_CSD, rca)?);
+        self.send_command(CMD_SELECT_CARD, rca)?;
+
+        let mut card = Card {
+            rca,
+            high_capacity: ocr & OCR_HCS != 0,
+            blocks,
+            bus_width: 1,
+            high_speed: false,
+        };
+        self.card = Some(card);
+        self.set_clock(DEFAULT_CLOCK_HZ)?;
+
+        if !card.high_capacity {
+            self.send_command(CMD_SET_BLOCKLEN, SECTOR_SIZE as u32)?;
+        }
+
+        // SCR: supported bus widths and spec version (big-endian bytes)
+        let mut scr = [0u8; 8];
+        self.send_command(CMD_APP_CMD, rca)?;
+        self.read_data(ACMD_SEND_SCR, 0, &mut scr, 8)?;
+
+        if scr[1] & 0x04 != 0 {
+            self.app_command(ACMD_SET_BUS_WIDTH, 2)?;
+            write(EMMC_CONTROL0, read(EMMC_CONTROL0) | C0_HCTL_DWIDTH);
+            card.bus_width = 4;
+        }
+
+        // CMD6 exists from spec version 1.10 on
+        if scr[0] & 0x0F >= 1 {
+            let mut status = [0u8; 64];
+            self.read_data(CMD_SWITCH_FUNC, SWITCH_HIGH_SPEED, &mut status, 64)?;
+            // Function group 1 result, bits 379:376
+            if status[16] & 0x0F == 1 {
+                write(EMMC_CONTROL0, read(EMMC_CONTROL0) | C0_HCTL_HS_EN);
+                self.set_clock(HIGH_SPEED_CLOCK_HZ)?;
+                card.high_speed = true;
+            }
+        }
+
+        self.card = Some(card);
+        Ok(card)
+    }
+
+    fn card(&self) -> Result<Card, BlockError> {
+        self.card.ok_or(BlockError::NoDevice)
+    }
+
+    fn read_blocks(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        let card = self.card()?;
+        let mut lba = lba;
+        for chunk in buf.chunks_mut(MAX_BLOCKS_PER_TRANSFER as usize * SECTOR_SIZE) {
+            let command = if chunk.len() == SECTOR_SIZE { CMD_READ_SINGLE } else { CMD_READ_MULTI };
+            self.read_data(command, card.address(lba), chunk, SECTOR_SIZE)?;
+            lba += (chunk.len() / SECTOR_SIZE) as u64;
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        let card = self.card()?;
+        let mut lba = lba;
+        for chunk in buf.chunks(MAX_BLOCKS_PER_TRANSFER as usize * SECTOR_SIZE) {
+            let command = if chunk.len() == SECTOR_SIZE { CMD_WRITE_SINGLE } else { CMD_WRITE_MULTI };
+            self.write_data(command, card.address(lba), chunk)?;
+            lba += (chunk.len() / SECTOR_SIZE) as u64;
+        }
+        Ok(())
+    }
+}
+
+impl Card {
+    fn address(&self, lba: u64) -> u32 {
+        if self.high_capacity {
+            lba as u32
+        } else {
+            (lba * SECTOR_SIZE as u64) as u32
+        }
+    }
+}
+
+// Card size in 512-byte blocks from the CSD. The controller drops the CRC
+// byte of R2 responses, so CSD bit n is bit n - 8 of RESP0-3.
+fn capacity_blocks(response: [u32; 4]) -> u64 {
+    let csd = (response[3] as u128) << 96
+        | (response[2] as u128) << 64
+        | (response[1] as u128) << 32
+        | response[0] as u128;
+    let bits = |high: u32, low: u32| ((csd >> (low - 8)) & ((1u128 << (high - low + 1)) - 1)) as u64;
+
+    match bits(127, 126) {
+        // CSD 2.0 (SDHC/SDXC): (C_SIZE + 1) * 512 KB
+        1 => (bits(69, 48) + 1) * 1024,
+        // CSD 1.0: (C_SIZE + 1) * 2^(C_SIZE_MULT + 2) * 2^READ_BL_LEN bytes
+        _ => {
+            let c_size = bits(73, 62);
+            let multiplier = bits(49, 47);
+            let block_length = bits(83, 80);
+            ((c_size + 1) << (multiplier + 2) << block_length) / SECTOR_SIZE as u64
+        }
+    }
+}
+
+pub struct Emmc {
+    controller: Mutex<Controller>,
+}
+
+impl BlockDevice for Emmc {
+    fn name(&self) -> &str {
+        "mmcblk0"
+    }
+
+    fn block_count(&self) -> u64 {
+        self.controller.lock().card.map_or(0, |card| card.blocks)
+    }
+
+    fn read_blocks(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        block::check_request(self, lba, buf.len())?;
+        self.controller.lock().read_blocks(lba, buf)
+    }
+
+    fn write_blocks(&self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        block::check_request(self, lba, buf.len())?;
+        self.controller.lock().write_blocks(lba, buf)
+    }
+}
+
+// Plain spin lock (not IrqMutex): the completion interrupt must be able
+// to arrive while a transfer holds the controller
+pub static EMMC: Emmc = Emmc {
+    controller: Mutex::new(Controller {
+        base_clock: DEFAULT_BASE_CLOCK_HZ,
+        card: None,
+    }),
+};
+
+// Route the SD slot pins to the EMMC controller
+fn setup_gpio() {
+    // All pins are valid, these cannot fail
+    let _ = gpio::set_function(CARD_DETECT_PIN, Function::Input);
+    let _ = gpio::set_pull(CARD_DETECT_PIN, Pull::Up);
+    for pin in SD_PINS {
+        let _ = gpio::set_function(pin, Function::Alt3);
+        let _ = gpio::set_pull(pin, Pull::Up);
+    }
+}
+
+pub fn init() {
+    setup_gpio();
+
+    // The firmware may keep the card powered off; QEMU has no power tag
+    let _ = mailbox::set_power_state(PowerDevice::SdCard, true, true);
+
+    gic::register_irq_handler(IRQ_EMMC, emmc_interrupt_handler);
+    gic::enable_irq(IRQ_EMMC);
+
+    let result = {
+        let mut controller = EMMC.controller.lock();
+        if let Ok(hz) = mailbox::clock_rate(ClockId::Emmc) {
+            if hz != 0 {
+                controller.base_clock = hz;
+            }
+        }
+        controller.identify()
+    };
+
+    match result {
+        Ok(card) => {
+            println!(
+                "EMMC: {} card, {}-bit bus, {}",
+                if card.high_capacity { "SDHC/SDXC" } else { "SDSC" },
+                card.bus_width,
+                if card.high_speed { "high speed (50 MHz)" } else { "default speed (25 MHz)" }
+            );
+            let _ = block::register(&EMMC);
+        }
+        Err(error) => println!("EMMC: no card ({:?})", error),
+    }
+}
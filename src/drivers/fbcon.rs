@@ -0,0 +1,404 @@
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::arch::multicore::sync::IrqMutex;
+use crate::drivers::framebuffer::{self, Color, Framebuffer, CHAR_HEIGHT, CHAR_WIDTH};
+use crate::time::{self, Duration};
+
+// Text console on the framebuffer. It mirrors what goes to the UART
+// console (print!/println!, TTY output), scrolls, and understands the
+// subset of ANSI/VT100 sequences the kernel and simple programs use:
+//   ESC [ n A/B/C/D     cursor up/down/forward/back
+//   ESC [ r ; c H/f     cursor position (1-based)
+//   ESC [ n J / n K     erase display / line (0 to end, 1 to cursor, 2 all)
+//   ESC [ ... m         colours: 0, 1, 22, 30-37, 39, 40-47, 49, 90-97, 100-107
+//   ESC [ s / u         save / restore cursor
+//   ESC c               reset
+// Non-ASCII characters are drawn as '?' (the font only covers ASCII).
+
+// VGA colours: 0-7 normal, 8-15 bright
+const PALETTE: [Color; 16] = [
+    Color::rgb(0x00, 0x00, 0x00),
+    Color::rgb(0xAA, 0x00, 0x00),
+    Color::rgb(0x00, 0xAA, 0x00),
+    Color::rgb(0xAA, 0x55, 0x00),
+    Color::rgb(0x00, 0x00, 0xAA),
+    Color::rgb(0xAA, 0x00, 0xAA),
+    Color::rgb(0x00, 0xAA, 0xAA),
+    Color::rgb(0xAA, 0xAA, 0xAA),
+    Color::rgb(0x55, 0x55, 0x55),
+    Color::rgb(0xFF, 0x55, 0x55),
+    Color::rgb(0x55, 0xFF, 0x55),
+    Color::rgb(0xFF, 0xFF, 0x55),
+    Color::rgb(0x55, 0x55, 0xFF),
+    Color::rgb(0xFF, 0x55, 0xFF),
+    Color::rgb(0x55, 0xFF, 0xFF),
+    Color::rgb(0xFF, 0xFF, 0xFF),
+];
+
+const DEFAULT_FG: u8 = 7;
+const DEFAULT_BG: u8 = 0;
+const BRIGHT: u8 = 8;
+
+const MAX_PARAMS: usize = 8;
+const TAB_WIDTH: u32 = 8;
+
+// Cursor: the two bottom pixel lines of the cell, inverted
+const CURSOR_HEIGHT: u32 = 2;
+
+// How long the panic path waits for another core to finish drawing
+const PANIC_LOCK_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Normal,
+    Escape,
+    Csi,
+    // Continuation bytes left in a UTF-8 sequence
+    Utf8(u8),
+}
+
+struct FbCon {
+    // First pixel line of the text area (the top of the screen may be
+    // used by the status display)
+    top: u32,
+    cols: u32,
+    rows: u32,
+    col: u32,
+    row: u32,
+    saved: (u32, u32),
+    fg: u8,
+    bg: u8,
+    bold: bool,
+    state: State,
+    params: [u16; MAX_PARAMS],
+    param_index: usize,
+    cursor_drawn: bool,
+}
+
+impl FbCon {
+    const fn new() -> Self {
+        FbCon {
+            top: 0,
+            cols: 0,
+            rows: 0,
+            col: 0,
+            row: 0,
+            saved: (0, 0),
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            bold: false,
+            state: State::Normal,
+            params: [0; MAX_PARAMS],
+            param_index: 0,
+            cursor_drawn: false,
+        }
+    }
+
+    fn fg_color(&self) -> Color {
+        let bright = if self.bold && self.fg < BRIGHT { BRIGHT } else { 0 };
+        PALETTE[(self.fg + bright) as usize]
+    }
+
+    fn bg_color(&self) -> Color {
+        PALETTE[self.bg as usize]
+    }
+
+    fn cell_x(&self, col: u32) -> u32 {
+        col * CHAR_WIDTH
+    }
+
+    fn cell_y(&self, row: u32) -> u32 {
+        self.top + row * CHAR_HEIGHT
+    }
+
+    fn reset_attributes(&mut self) {
+        self.fg = DEFAULT_FG;
+        self.bg = DEFAULT_BG;
+        self.bold = false;
+    }
+
+    fn toggle_cursor(&mut self, fb: &mut Framebuffer) {
+        let col = self.col.min(self.cols - 1);
+        let y = self.cell_y(self.row) + CHAR_HEIGHT - CURSOR_HEIGHT;
+        fb.invert_rect(self.cell_x(col), y, CHAR_WIDTH, CURSOR_HEIGHT);
+        self.cursor_drawn = !self.cursor_drawn;
+    }
+
+    fn hide_cursor(&mut self, fb: &mut Framebuffer) {
+        if self.cursor_drawn {
+            self.toggle_cursor(fb);
+        }
+    }
+
+    fn show_cursor(&mut self, fb: &mut Framebuffer) {
+        if !self.cursor_drawn {
+            self.toggle_cursor(fb);
+        }
+    }
+
+    // Clear columns [from, to) of a row with the current background
+    fn erase(&self, fb: &mut Framebuffer, row: u32, from: u32, to: u32) {
+        if from < to {
+            let y = self.cell_y(row);
+            fb.fill_rect(self.cell_x(from), y, (to - from) * CHAR_WIDTH, CHAR_HEIGHT, self.bg_color());
+        }
+    }
+
+    fn erase_rows(&self, fb: &mut Framebuffer, from: u32, to: u32) {
+        for row in from..to {
+            self.erase(fb, row, 0, self.cols);
+        }
+    }
+
+    fn scroll(&mut self, fb: &mut Framebuffer) {
+        let width = self.cols * CHAR_WIDTH;
+        let height = (self.rows - 1) * CHAR_HEIGHT;
+        fb.copy_rect(0, self.top + CHAR_HEIGHT, width, height, 0, self.top);
+        self.erase(fb, self.rows - 1, 0, self.cols);
+    }
+
+    fn newline(&mut self, fb: &mut Framebuffer) {
+        self.col = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+        } else {
+            self.scroll(fb);
+        }
+    }
+
+    fn put(&mut self, fb: &mut Framebuffer, byte: u8) {
+        // Wrap only when the next character arrives, so a line that fills
+        // the screen width exactly does not leave an empty line behind
+        if self.col >= self.cols {
+            self.newline(fb);
+        }
+        let (x, y) = (self.cell_x(self.col), self.cell_y(self.row));
+        fb.draw_char(x, y, byte, self.fg_color(), self.bg_color());
+        self.col += 1;
+    }
+
+    fn feed(&mut self, fb: &mut Framebuffer, byte: u8) {
+        match self.state {
+            State::Normal => self.feed_normal(fb, byte),
+            State::Escape => {
+                self.state = State::Normal;
+                match byte {
+                    b'[' => {
+                        self.params = [0; MAX_PARAMS];
+                        self.param_index = 0;
+                        self.state = State::Csi;
+                    }
+                    b'c' => self.reset(fb),
+                    _ => {}
+                }
+            }
+            State::Csi => match byte {
+                b'0'..=b'9' => {
+                    let param = &mut self.params[self.param_index];
+                    *param = param.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                }
+                b';' => self.param_index = (self.param_index + 1).min(MAX_PARAMS - 1),
+                // Private marker ("ESC [ ?"), ignored
+                b'?' => {}
+                0x40..=0x7E => {
+                    self.state = State::Normal;
+                    self.execute(fb, byte);
+                }
+                _ => self.state = State::Normal,
+            },
+            State::Utf8(remaining) => {
+                if byte & 0xC0 == 0x80 {
+                    self.state = if remaining > 1 { State::Utf8(remaining - 1) } else { State::Normal };
+                } else {
+                    // Truncated sequence: start over with this byte
+                    self.state = State::Normal;
+                    self.feed_normal(fb, byte);
+                }
+            }
+        }
+    }
+
+    fn feed_normal(&mut self, fb: &mut Framebuffer, byte: u8) {
+        match byte {
+            0x1B => self.state = State::Escape,
+            b'\n' => self.newline(fb),
+            b'\r' => self.col = 0,
+            0x08 => self.col = self.col.min(self.cols).saturating_sub(1),
+            b'\t' => {
+                let next = (self.col / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.col = next.min(self.cols - 1);
+            }
+            0x20..=0x7E => self.put(fb, byte),
+            // UTF-8 lead bytes: one replacement character per code point
+            0xC0..=0xDF => {
+                self.put(fb, b'?');
+                self.state = State::Utf8(1);
+            }
+            0xE0..=0xEF => {
+                self.put(fb, b'?');
+                self.state = State::Utf8(2);
+            }
+            0xF0..=0xF7 => {
+                self.put(fb, b'?');
+                self.state = State::Utf8(3);
+            }
+            _ => {}
+        }
+    }
+
+    // Parameter `index`, with 0 / missing meaning `default`
+    fn param(&self, index: usize, default: u32) -> u32 {
+        match self.params[index] {
+            0 => default,
+            value => value as u32,
+        }
+    }
+
+    fn execute(&mut self, fb: &mut Framebuffer, command: u8) {
+        let last_col = self.cols - 1;
+        let last_row = self.rows - 1;
+
+        match command {
+            b'A' => self.row = self.row.saturating_sub(self.param(0, 1)),
+            b'B' => self.row = (self.row + self.param(0, 1)).min(last_row),
+            b'C' => self.col = (self.col + self.param(0, 1)).min(last_col),
+            b'D' => self.col = self.col.min(last_col).saturating_sub(self.param(0, 1)),
+            b'H' | b'f' => {
+                self.row = (self.param(0, 1) - 1).min(last_row);
+                self.col = (self.param(1, 1) - 1).min(last_col);
+            }
+            b'J' => match self.params[0] {
+                0 => {
+                    self.erase(fb, self.row, self.col, self.cols);
+                    self.erase_rows(fb, self.row + 1, self.rows);
+                }
+                1 => {
+                    self.erase_rows(fb, 0, self.row);
+                    self.erase(fb, self.row, 0, (self.col + 1).min(self.cols));
+                }
+                _ => self.erase_rows(fb, 0, self.rows),
+            },
+            b'K' => match self.params[0] {
+                0 => self.erase(fb, self.row, self.col, self.cols),
+                1 => self.erase(fb, self.row, 0, (self.col + 1).min(self.cols)),
+                _ => self.erase(fb, self.row, 0, self.cols),
+            },
+            b'm' => {
+                for index in 0..=self.param_index {
+                    self.select_graphic_rendition(self.params[index] as u8);
+                }
+            }
+            b's' => self.saved = (self.col, self.row),
+            b'u' => (self.col, self.row) = self.saved,
+            _ => {}
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, code: u8) {
+        match code {
+            0 => self.reset_attributes(),
+            1 => self.bold = true,
+            22 => self.bold = false,
+            30..=37 => self.fg = code - 30,
+            39 => self.fg = DEFAULT_FG,
+            40..=47 => self.bg = code - 40,
+            49 => self.bg = DEFAULT_BG,
+            90..=97 => self.fg = code - 90 + BRIGHT,
+            100..=107 => self.bg = code - 100 + BRIGHT,
+            _ => {}
+        }
+    }
+
+    fn reset(&mut self, fb: &mut Framebuffer) {
+        self.reset_attributes();
+        self.state = State::Normal;
+        self.erase_rows(fb, 0, self.rows);
+        self.col = 0;
+        self.row = 0;
+        self.saved = (0, 0);
+    }
+}
+
+static FBCON: IrqMutex<FbCon> = IrqMutex::new(FbCon::new());
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+pub fn write_bytes(bytes: &[u8]) {
+    if !enabled() {
+        return;
+    }
+
+    let mut con = FBCON.lock();
+    framebuffer::with(|fb| {
+        con.hide_cursor(fb);
+        for &byte in bytes {
+            con.feed(fb, byte);
+        }
+        con.show_cursor(fb);
+    });
+}
+
+struct FbConWriter;
+
+impl fmt::Write for FbConWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+pub fn write_fmt(args: fmt::Arguments) {
+    if enabled() {
+        let _ = fmt::Write::write_fmt(&mut FbConWriter, args);
+    }
+}
+
+// Called from console::panic_takeover(): make sure the panic message can
+// be drawn even if this or another core was stopped while drawing, and
+// drop any half-parsed escape sequence
+pub fn panic_takeover() {
+    if !enabled() {
+        return;
+    }
+
+    if !time::wait_for(PANIC_LOCK_TIMEOUT, || !FBCON.is_locked()) {
+        unsafe { FBCON.force_unlock() };
+    }
+    framebuffer::panic_takeover();
+
+    let mut con = FBCON.lock();
+    con.state = State::Normal;
+    con.reset_attributes();
+}
+
+// Use the framebuffer below pixel line `top` as a text console. The
+// kernel log written so far is replayed, so boot messages show up too.
+pub fn init(top: u32) {
+    let Some((width, height)) = framebuffer::with(|fb| (fb.width(), fb.height())) else {
+        return;
+    };
+    // Room for at least one character cell
+    if width < CHAR_WIDTH || top + CHAR_HEIGHT > height {
+        return;
+    }
+
+    {
+        let mut con = FBCON.lock();
+        con.top = top;
+        con.cols = width / CHAR_WIDTH;
+        con.rows = (height - top) / CHAR_HEIGHT;
+        framebuffer::with(|fb| con.reset(fb));
+    }
+    ENABLED.store(true, Ordering::Release);
+
+    crate::log::dmesg(|text| write_bytes(text.as_bytes()));
+
+    let (cols, rows) = {
+        let con = FBCON.lock();
+        (con.cols, con.rows)
+    };
+    crate::println!("Framebuffer console: {}x{} characters", cols, rows);
+}
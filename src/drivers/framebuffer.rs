@@ -0,0 +1,367 @@
+use crate::arch::multicore::sync::IrqMutex;
+use crate::drivers::font;
+use crate::drivers::mailbox::{MailboxError, Message};
+use crate::time::{self, Duration};
+
+// Framebuffer allocated by the VideoCore through the mailbox property
+// interface, with a small pixel-level drawing API (32 bits per pixel)
+
+const TAG_ALLOCATE_BUFFER: u32 = 0x0004_0001;
+const TAG_GET_PITCH: u32 = 0x0004_0008;
+const TAG_SET_PHYSICAL_SIZE: u32 = 0x0004_8003;
+const TAG_SET_VIRTUAL_SIZE: u32 = 0x0004_8004;
+const TAG_SET_DEPTH: u32 = 0x0004_8005;
+const TAG_SET_PIXEL_ORDER: u32 = 0x0004_8006;
+const TAG_SET_VIRTUAL_OFFSET: u32 = 0x0004_8009;
+
+const DEPTH: u32 = 32;
+const BUFFER_ALIGN: u32 = 4096;
+
+// The firmware returns a VideoCore bus address
+const BUS_ADDRESS_MASK: u32 = 0x3FFF_FFFF;
+
+// Text cell: the 8x8 font with every row drawn twice
+pub const CHAR_WIDTH: u32 = font::WIDTH;
+pub const CHAR_HEIGHT: u32 = font::HEIGHT * 2;
+
+pub const DEFAULT_WIDTH: u32 = 1024;
+pub const DEFAULT_HEIGHT: u32 = 768;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelOrder {
+    Bgr = 0,
+    Rgb = 1,
+}
+
+// Colour as 0xRRGGBB, converted to the framebuffer pixel order on write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub u32);
+
+impl Color {
+    #[allow(dead_code)]
+    pub const BLACK: Color = Color(0x000000);
+    pub const WHITE: Color = Color(0xFFFFFF);
+    pub const GREY: Color = Color(0x808080);
+    pub const RED: Color = Color(0xCC3333);
+    pub const GREEN: Color = Color(0x33CC33);
+    pub const YELLOW: Color = Color(0xCCCC33);
+    pub const BLUE: Color = Color(0x3366CC);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color((r as u32) << 16 | (g as u32) << 8 | b as u32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferError {
+    Mailbox(MailboxError),
+    // The firmware accepted the request but gave no usable buffer
+    NoBuffer,
+    UnsupportedDepth(u32),
+}
+
+impl From<MailboxError> for FramebufferError {
+    fn from(error: MailboxError) -> Self {
+        FramebufferError::Mailbox(error)
+    }
+}
+
+pub struct Framebuffer {
+    base: usize,
+    size: usize,
+    width: u32,
+    height: u32,
+    // Bytes per line (may be larger than width * 4)
+    pitch: u32,
+    order: PixelOrder,
+}
+
+impl Framebuffer {
+    // Ask the firmware for a width x height, 32 bpp framebuffer
+    pub fn allocate(width: u32, height: u32) -> Result<Framebuffer, FramebufferError> {
+        let mut message = Message::new();
+        let physical = message.add_tag(TAG_SET_PHYSICAL_SIZE, &[width, height], 2)?;
+        message.add_tag(TAG_SET_VIRTUAL_SIZE, &[width, height], 2)?;
+        message.add_tag(TAG_SET_VIRTUAL_OFFSET, &[0, 0], 2)?;
+        let depth = message.add_tag(TAG_SET_DEPTH, &[DEPTH], 1)?;
+        let order = message.add_tag(TAG_SET_PIXEL_ORDER, &[PixelOrder::Rgb as u32], 1)?;
+        let buffer = message.add_tag(TAG_ALLOCATE_BUFFER, &[BUFFER_ALIGN], 2)?;
+        let pitch = message.add_tag(TAG_GET_PITCH, &[], 1)?;
+        message.send()?;
+
+        let (width, height) = match message.response(physical)? {
+            [width, height, ..] => (*width, *height),
+            _ => return Err(FramebufferError::NoBuffer),
+        };
+        let depth = message.response(depth)?[0];
+        if depth != DEPTH {
+            return Err(FramebufferError::UnsupportedDepth(depth));
+        }
+
+        let order = match message.response(order)?[0] {
+            0 => PixelOrder::Bgr,
+            _ => PixelOrder::Rgb,
+        };
+        let (base, size) = match message.response(buffer)? {
+            [base, size, ..] if *base != 0 && *size != 0 => {
+                ((base & BUS_ADDRESS_MASK) as usize, *size as usize)
+            }
+            _ => return Err(FramebufferError::NoBuffer),
+        };
+        let pitch = match message.response(pitch)? {
+            [pitch, ..] if *pitch != 0 => *pitch,
+            _ => width * 4,
+        };
+
+        Ok(Framebuffer {
+            base,
+            size,
+            width,
+            height,
+            pitch,
+            order,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[allow(dead_code)]
+    pub fn pitch(&self) -> u32 {
+        self.pitch
+    }
+
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn pixel_value(&self, color: Color) -> u32 {
+        match self.order {
+            PixelOrder::Rgb => color.0,
+            PixelOrder::Bgr => {
+                let Color(rgb) = color;
+                (rgb & 0x00FF00) | (rgb >> 16 & 0xFF) | (rgb & 0xFF) << 16
+            }
+        }
+    }
+
+    fn row(&self, y: u32) -> *mut u32 {
+        (self.base + y as usize * self.pitch as usize) as *mut u32
+    }
+
+    pub fn put_pixel(&mut self, x: u32, y: u32, color: Color) {
+        if x < self.width && y < self.height {
+            let value = self.pixel_value(color);
+            unsafe { self.row(y).add(x as usize).write_volatile(value) };
+        }
+    }
+
+    // Clip a rectangle to the screen; None if nothing is left
+    fn clip(&self, x: u32, y: u32, w: u32, h: u32) -> Option<(u32, u32, u32, u32)> {
+        if x >= self.width || y >= self.height || w == 0 || h == 0 {
+            return None;
+        }
+        Some((x, y, w.min(self.width - x), h.min(self.height - y)))
+    }
+
+    pub fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Color) {
+        let Some((x, y, w, h)) = self.clip(x, y, w, h) else {
+            return;
+        };
+
+        let value = self.pixel_value(color);
+        for line in y..y + h {
+            let row = self.row(line);
+            for column in x..x + w {
+                unsafe { row.add(column as usize).write_volatile(value) };
+            }
+        }
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        self.fill_rect(0, 0, self.width, self.height, color);
+    }
+
+    // Outline of a rectangle
+    pub fn draw_rect(&mut self, x: u32, y: u32, w: u32, h: u32, color: Color) {
+        if w == 0 || h == 0 {
+            return;
+        }
+        self.fill_rect(x, y, w, 1, color);
+        self.fill_rect(x, y + h - 1, w, 1, color);
+        self.fill_rect(x, y, 1, h, color);
+        self.fill_rect(x + w - 1, y, 1, h, color);
+    }
+
+    // Bresenham line; points outside the screen are skipped
+    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: Color) {
+        let (mut x, mut y) = (x0, y0);
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let step_x = if x0 < x1 { 1 } else { -1 };
+        let step_y = if y0 < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        loop {
+            if x >= 0 && y >= 0 {
+                self.put_pixel(x as u32, y as u32, color);
+            }
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    // Invert the colours of a block (XOR, so drawing it twice restores it)
+    pub fn invert_rect(&mut self, x: u32, y: u32, w: u32, h: u32) {
+        let Some((x, y, w, h)) = self.clip(x, y, w, h) else {
+            return;
+        };
+
+        for line in y..y + h {
+            let row = self.row(line);
+            for column in x..x + w {
+                unsafe {
+                    let pixel = row.add(column as usize);
+                    pixel.write_volatile(pixel.read_volatile() ^ 0x00FF_FFFF);
+                }
+            }
+        }
+    }
+
+    // One character cell (CHAR_WIDTH x CHAR_HEIGHT) at pixel position x, y
+    pub fn draw_char(&mut self, x: u32, y: u32, byte: u8, fg: Color, bg: Color) {
+        if x + CHAR_WIDTH > self.width || y + CHAR_HEIGHT > self.height {
+            return;
+        }
+
+        let (fg, bg) = (self.pixel_value(fg), self.pixel_value(bg));
+        for (index, bits) in font::glyph(byte).iter().enumerate() {
+            for repeat in 0..2 {
+                let row = self.row(y + index as u32 * 2 + repeat);
+                for column in 0..CHAR_WIDTH {
+                    let value = if bits & (1 << column) != 0 { fg } else { bg };
+                    unsafe { row.add((x + column) as usize).write_volatile(value) };
+                }
+            }
+        }
+    }
+
+    // A line of ASCII text; returns the x position after the last character
+    pub fn draw_text(&mut self, x: u32, y: u32, text: &str, fg: Color, bg: Color) -> u32 {
+        let mut x = x;
+        for byte in text.bytes() {
+            self.draw_char(x, y, byte, fg, bg);
+            x += CHAR_WIDTH;
+        }
+        x
+    }
+
+    // Copy a w x h block of 0xRRGGBB pixels (`stride` pixels per source line)
+    #[allow(dead_code)]
+    pub fn blit(&mut self, x: u32, y: u32, w: u32, h: u32, pixels: &[u32], stride: usize) {
+        let Some((x, y, clipped_w, clipped_h)) = self.clip(x, y, w, h) else {
+            return;
+        };
+
+        for line in 0..clipped_h {
+            let source = line as usize * stride;
+            let Some(source) = pixels.get(source..source + clipped_w as usize) else {
+                return;
+            };
+            let row = self.row(y + line);
+            for (column, &pixel) in source.iter().enumerate() {
+                let value = self.pixel_value(Color(pixel));
+                unsafe { row.add(x as usize + column).write_volatile(value) };
+            }
+        }
+    }
+
+    // Move a block of the screen (used for scrolling). Overlapping blocks
+    // are copied in the safe direction.
+    pub fn copy_rect(&mut self, src_x: u32, src_y: u32, w: u32, h: u32, dst_x: u32, dst_y: u32) {
+        let Some((src_x, src_y, w, h)) = self.clip(src_x, src_y, w, h) else {
+            return;
+        };
+        let Some((dst_x, dst_y, w, h)) = self.clip(dst_x, dst_y, w, h) else {
+            return;
+        };
+
+        let copy_line = |fb: &mut Framebuffer, line: u32| unsafe {
+            core::ptr::copy(
+                fb.row(src_y + line).add(src_x as usize),
+                fb.row(dst_y + line).add(dst_x as usize),
+                w as usize,
+            );
+        };
+
+        if dst_y <= src_y {
+            for line in 0..h {
+                copy_line(self, line);
+            }
+        } else {
+            for line in (0..h).rev() {
+                copy_line(self, line);
+            }
+        }
+    }
+}
+
+static FRAMEBUFFER: IrqMutex<Option<Framebuffer>> = IrqMutex::new(None);
+
+// Run `f` with the framebuffer, if one was allocated
+pub fn with<R>(f: impl FnOnce(&mut Framebuffer) -> R) -> Option<R> {
+    FRAMEBUFFER.lock().as_mut().map(f)
+}
+
+#[allow(dead_code)]
+pub fn available() -> bool {
+    FRAMEBUFFER.lock().is_some()
+}
+
+// How long the panic path waits for another core to finish drawing
+const PANIC_LOCK_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Break the framebuffer lock if its owner does not release it in time
+// (the panicking core may have been stopped in the middle of a drawing)
+pub fn panic_takeover() {
+    if !time::wait_for(PANIC_LOCK_TIMEOUT, || !FRAMEBUFFER.is_locked()) {
+        unsafe { FRAMEBUFFER.force_unlock() };
+    }
+}
+
+// Size from "video=WIDTHxHEIGHT" on the command line
+fn requested_size() -> (u32, u32) {
+    crate::cmdline::param("video")
+        .and_then(|mode| mode.split_once('x'))
+        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+        .unwrap_or((DEFAULT_WIDTH, DEFAULT_HEIGHT))
+}
+
+pub fn init() -> Result<(u32, u32), FramebufferError> {
+    let (width, height) = requested_size();
+    let framebuffer = Framebuffer::allocate(width, height)?;
+    let size = (framebuffer.width(), framebuffer.height());
+
+    *FRAMEBUFFER.lock() = Some(framebuffer);
+    Ok(size)
+}
+
@@ -1,4 +1,6 @@
 use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicU32, Ordering};
+use crate::arch::multicore::{get_core_id, MAX_CORES};
 use crate::println;
 
 // BCM2837 interrupt controller base addresses
@@ -25,9 +27,15 @@ const DISABLE_BASIC_IRQS: usize = INTERRUPT_BASE + 0x224;
 #[allow(dead_code)]
 const LOCAL_CONTROL: usize = LOCAL_INTERRUPT_BASE + 0x00;
 const LOCAL_IRQ_PENDING: usize = LOCAL_INTERRUPT_BASE + 0x60;
+#[allow(dead_code)]
 const LOCAL_IRQ_ENABLE: usize = LOCAL_INTERRUPT_BASE + 0x64;
 const LOCAL_IRQ_DISABLE: usize = LOCAL_INTERRUPT_BASE + 0x68;
 
+// Per-core registers of the local controller (one word per core)
+const CORE_TIMER_IRQ_CONTROL: usize = LOCAL_INTERRUPT_BASE + 0x40;
+const CORE_MAILBOX_IRQ_CONTROL: usize = LOCAL_INTERRUPT_BASE + 0x50;
+const CORE_IRQ_SOURCE: usize = LOCAL_INTERRUPT_BASE + 0x60;
+
 // Timer interrupt registers
 const LOCAL_TIMER_IRQ_CONTROL: usize = LOCAL_INTERRUPT_BASE + 0x40;
 const LOCAL_TIMER_IRQ_CLEAR: usize = LOCAL_INTERRUPT_BASE + 0x44;
@@ -36,9 +44,12 @@ const LOCAL_TIMER_IRQ_RELOAD: usize = LOCAL_INTERRUPT_BASE + 0x48;
 const LOCAL_TIMER_IRQ_VALUE: usize = LOCAL_INTERRUPT_BASE + 0x4C;
 
 // Interrupt numbers
+// Local IRQs 64-71 follow the bit order of the per-core IRQ source register:
+// 0 = CNTPS, 1 = CNTPNS, 2 = CNTHP, 3 = CNTV, 4-7 = mailboxes 0-3
 pub const IRQ_UART: u32 = 57;
-pub const IRQ_TIMER: u32 = 64;  // Local timer
-pub const IRQ_MAILBOX: u32 = 65;
+pub const IRQ_TIMER: u32 = 64;  // Generic timer (secure physical)
+pub const IRQ_TIMER_NS: u32 = 65;  // Generic timer (non-secure physical)
+pub const IRQ_MAILBOX: u32 = 68;
 
 // Interrupt types
 #[derive(Debug, Clone, Copy)]
@@ -46,6 +57,7 @@ pub enum InterruptType {
     Timer,
     Uart,
     Mailbox,
+    Peripheral(u32),
     Unknown(u32),
 }
 
@@ -56,6 +68,14 @@ pub type InterruptHandler = fn();
 static mut TIMER_HANDLER: Option<InterruptHandler> = None;
 static mut UART_HANDLER: Option<InterruptHandler> = None;
 
+// Handlers for the remaining IRQs, indexed by IRQ number
+pub const MAX_IRQS: usize = 72;
+static mut IRQ_HANDLERS: [Option<InterruptHandler>; MAX_IRQS] = [None; MAX_IRQS];
+
+// Number of times each IRQ was taken, plus spurious interrupts
+static IRQ_COUNTS: [AtomicU32; MAX_IRQS] = [const { AtomicU32::new(0) }; MAX_IRQS];
+static SPURIOUS_COUNT: AtomicU32 = AtomicU32::new(0);
+
 pub struct Gic {
     // GIC state can be stored here if needed
 }
@@ -74,6 +94,11 @@ impl Gic {
             write_volatile(DISABLE_IRQS_2 as *mut u32, 0xFFFFFFFF);
             write_volatile(DISABLE_BASIC_IRQS as *mut u32, 0xFFFFFFFF);
             write_volatile(LOCAL_IRQ_DISABLE as *mut u32, 0xFFFFFFFF);
+            
+            for core in 0..MAX_CORES {
+                write_volatile(core_register(CORE_TIMER_IRQ_CONTROL, core) as *mut u32, 0);
+                write_volatile(core_register(CORE_MAILBOX_IRQ_CONTROL, core) as *mut u32, 0);
+            }
         }
         
         // Clear any pending interrupts
@@ -101,11 +126,8 @@ impl Gic {
                 println!("Enabled IRQ {} (bank 2)", irq);
             }
             64..=71 => {
-                // Local interrupts (64-71)
-                let bit = 1 << (irq - 64);
-                unsafe {
-                    write_volatile(LOCAL_IRQ_ENABLE as *mut u32, bit);
-                }
+                // Local interrupts (64-71) are routed per core
+                self.set_local_irq(irq, get_core_id() as usize, true);
                 println!("Enabled local IRQ {}", irq);
             }
             _ => {
@@ -130,10 +152,7 @@ impl Gic {
                 }
             }
             64..=71 => {
-                let bit = 1 << (irq - 64);
-                unsafe {
-                    write_volatile(LOCAL_IRQ_DISABLE as *mut u32, bit);
-                }
+                self.set_local_irq(irq, get_core_id() as usize, false);
             }
             _ => {
                 println!("Invalid IRQ number: {}", irq);
@@ -141,6 +160,21 @@ impl Gic {
         }
     }
 
+    // Enable or disable a local IRQ (64-71) for the given core
+    pub fn set_local_irq(&self, irq: u32, core: usize, enable: bool) {
+        let (register, bit) = match irq {
+            64..=67 => (core_register(CORE_TIMER_IRQ_CONTROL, core), 1 << (irq - 64)),
+            68..=71 => (core_register(CORE_MAILBOX_IRQ_CONTROL, core), 1 << (irq - 68)),
+            _ => return,
+        };
+        
+        unsafe {
+            let value = read_volatile(register as *const u32);
+            let value = if enable { value | bit } else { value & !bit };
+            write_volatile(register as *mut u32, value);
+        }
+    }
+
     fn clear_pending_interrupts(&self) {
         // Clear any pending interrupts by reading the pending registers
         unsafe {
@@ -153,8 +187,9 @@ impl Gic {
 
     fn get_pending_irq(&self) -> Option<u32> {
         unsafe {
-            // Check local interrupts first (higher priority)
-            let local_pending = read_volatile(LOCAL_IRQ_PENDING as *const u32);
+            // Check local interrupts of this core first (higher priority)
+            let source = core_register(CORE_IRQ_SOURCE, get_core_id() as usize);
+            let local_pending = read_volatile(source as *const u32);
             if local_pending != 0 {
                 for i in 0..8 {
                     if (local_pending & (1 << i)) != 0 {
@@ -199,23 +234,29 @@ impl Gic {
 
     pub fn handle_irq(&self) -> Option<InterruptType> {
         if let Some(irq_num) = self.get_pending_irq() {
+            if let Some(count) = IRQ_COUNTS.get(irq_num as usize) {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+            crate::random::add_interrupt_timestamp(irq_num);
+
             let interrupt_type = match irq_num {
-                IRQ_TIMER => InterruptType::Timer,
+                IRQ_TIMER | IRQ_TIMER_NS => InterruptType::Timer,
                 IRQ_UART => InterruptType::Uart,
                 IRQ_MAILBOX => InterruptType::Mailbox,
+                _ if registered_handler(irq_num).is_some() => InterruptType::Peripheral(irq_num),
                 _ => InterruptType::Unknown(irq_num),
             };
 
             // Call the appropriate handler
             match interrupt_type {
                 InterruptType::Timer => {
+                    // The handler clears the generic timer by reprogramming
+                    // or masking its comparator
                     unsafe {
                         if let Some(handler) = TIMER_HANDLER {
                             handler();
                         }
                     }
-                    // Clear timer interrupt
-                    self.clear_timer_interrupt();
                 }
                 InterruptType::Uart => {
                     unsafe {
@@ -227,6 +268,11 @@ impl Gic {
                 InterruptType::Mailbox => {
                     println!("Mailbox interrupt");
                 }
+                InterruptType::Peripheral(num) => {
+                    if let Some(handler) = registered_handler(num) {
+                        handler();
+                    }
+                }
                 InterruptType::Unknown(num) => {
                     println!("Unknown interrupt: {}", num);
                 }
@@ -238,6 +284,7 @@ impl Gic {
         }
     }
 
+    #[allow(dead_code)]
     pub fn setup_timer(&self, interval_us: u32) {
         println!("Setting up local timer with interval {} us", interval_us);
         
@@ -260,6 +307,7 @@ impl Gic {
         println!("Local timer configured and enabled");
     }
 
+    #[allow(dead_code)]
     fn clear_timer_interrupt(&self) {
         unsafe {
             // Clear timer interrupt by writing to clear register
@@ -268,6 +316,15 @@ impl Gic {
     }
 }
 
+#[allow(static_mut_refs)]
+fn registered_handler(irq: u32) -> Option<InterruptHandler> {
+    unsafe { IRQ_HANDLERS.get(irq as usize).copied().flatten() }
+}
+
+fn core_register(base: usize, core: usize) -> usize {
+    base + core * 4
+}
+
 // Global GIC instance
 static mut GIC: Option<Gic> = None;
 
@@ -294,11 +351,36 @@ pub fn handle_irq() {
         if let Some(_interrupt_type) = gic.handle_irq() {
             // Interrupt handled successfully
         } else {
+            SPURIOUS_COUNT.fetch_add(1, Ordering::Relaxed);
             println!("Spurious interrupt");
         }
     }
 }
 
+pub fn irq_count(irq: u32) -> u32 {
+    IRQ_COUNTS
+        .get(irq as usize)
+        .map_or(0, |count| count.load(Ordering::Relaxed))
+}
+
+pub fn spurious_count() -> u32 {
+    SPURIOUS_COUNT.load(Ordering::Relaxed)
+}
+
+// Short name for the IRQs the kernel knows about
+pub fn irq_name(irq: u32) -> &'static str {
+    match irq {
+        IRQ_TIMER | IRQ_TIMER_NS => "generic timer",
+        IRQ_UART => "uart (pl011)",
+        IRQ_MAILBOX => "core mailbox",
+        16..=27 => "dma",
+        49..=52 => "gpio",
+        62 => "emmc",
+        _ if registered_handler(irq).is_some() => "peripheral",
+        _ => "",
+    }
+}
+
 // Register interrupt handlers
 pub fn register_timer_handler(handler: InterruptHandler) {
     unsafe {
@@ -307,7 +389,6 @@ pub fn register_timer_handler(handler: InterruptHandler) {
     println!("Timer interrupt handler registered");
 }
 
-#[allow(dead_code)]
 pub fn register_uart_handler(handler: InterruptHandler) {
     unsafe {
         UART_HANDLER = Some(handler);
@@ -315,7 +396,20 @@ pub fn register_uart_handler(handler: InterruptHandler) {
     println!("UART interrupt handler registered");
 }
 
+// Register a handler for any other IRQ (peripherals, GPIO banks, DMA...)
+pub fn register_irq_handler(irq: u32, handler: InterruptHandler) {
+    if (irq as usize) < MAX_IRQS {
+        unsafe {
+            IRQ_HANDLERS[irq as usize] = Some(handler);
+        }
+        println!("IRQ {} handler registered", irq);
+    } else {
+        println!("Invalid IRQ number: {}", irq);
+    }
+}
+
 // Setup timer with callback
+#[allow(dead_code)]
 #[allow(static_mut_refs)]
 pub fn setup_timer(interval_us: u32) {
     if let Some(gic) = unsafe { GIC.as_ref() } {
@@ -339,3 +433,11 @@ pub fn disable_irq(irq: u32) {
         gic.disable_irq(irq);
     }
 }
+
+// Route a local IRQ to the calling core
+#[allow(static_mut_refs)]
+pub fn enable_local_irq(irq: u32) {
+    if let Some(gic) = unsafe { GIC.as_ref() } {
+        gic.set_local_irq(irq, get_core_id() as usize, true);
+    }
+}
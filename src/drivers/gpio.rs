@@ -0,0 +1,290 @@
+use core::ptr::{read_volatile, write_volatile};
+use crate::arch::multicore::sync::IrqMutex;
+use crate::drivers::gic;
+use crate::println;
+use crate::time;
+
+// BCM2837 GPIO block: 54 pins in two banks of 32-bit registers
+const GPIO_BASE: usize = 0x3F20_0000;
+
+const GPFSEL0: usize = GPIO_BASE;
+const GPSET0: usize = GPIO_BASE + 0x1C;
+const GPCLR0: usize = GPIO_BASE + 0x28;
+const GPLEV0: usize = GPIO_BASE + 0x34;
+const GPEDS0: usize = GPIO_BASE + 0x40;
+const GPREN0: usize = GPIO_BASE + 0x4C;
+const GPFEN0: usize = GPIO_BASE + 0x58;
+const GPHEN0: usize = GPIO_BASE + 0x64;
+const GPLEN0: usize = GPIO_BASE + 0x70;
+const GPPUD: usize = GPIO_BASE + 0x94;
+const GPPUDCLK0: usize = GPIO_BASE + 0x98;
+
+pub const PIN_COUNT: u8 = 54;
+
+// Detect-enable registers, one bit per pin per register bank
+const DETECT_REGISTERS: [usize; 4] = [GPREN0, GPFEN0, GPHEN0, GPLEN0];
+
+// Bank interrupts: 49 = pins 0-27, 50 = pins 28-45, 51 = pins 46-53.
+// IRQ 52 (any bank) would only duplicate them and stays disabled.
+pub const IRQ_GPIO_BANK0: u32 = 49;
+pub const IRQ_GPIO_BANK1: u32 = 50;
+pub const IRQ_GPIO_BANK2: u32 = 51;
+#[allow(dead_code)]
+pub const IRQ_GPIO_ANY: u32 = 52;
+
+// GPPUD needs 150 core cycles of setup and hold around the clock pulse
+const PULL_SETUP_US: u64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Input = 0b000,
+    Output = 0b001,
+    Alt0 = 0b100,
+    Alt1 = 0b101,
+    Alt2 = 0b110,
+    Alt3 = 0b111,
+    Alt4 = 0b011,
+    Alt5 = 0b010,
+}
+
+impl Function {
+    fn from_bits(bits: u32) -> Function {
+        match bits & 0b111 {
+            0b000 => Function::Input,
+            0b001 => Function::Output,
+            0b100 => Function::Alt0,
+            0b101 => Function::Alt1,
+            0b110 => Function::Alt2,
+            0b111 => Function::Alt3,
+            0b011 => Function::Alt4,
+            _ => Function::Alt5,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pull {
+    None = 0,
+    Down = 1,
+    Up = 2,
+}
+
+// Event that sets a pin's detect status and raises the bank IRQ
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Rising,
+    Falling,
+    BothEdges,
+    // Level triggers stay pending while the level holds: the callback has
+    // to remove the cause or disable the interrupt
+    High,
+    Low,
+}
+
+impl Trigger {
+    // Which of GPREN/GPFEN/GPHEN/GPLEN get the pin's bit
+    fn detect_mask(self) -> [bool; 4] {
+        match self {
+            Trigger::Rising => [true, false, false, false],
+            Trigger::Falling => [false, true, false, false],
+            Trigger::BothEdges => [true, true, false, false],
+            Trigger::High => [false, false, true, false],
+            Trigger::Low => [false, false, false, true],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpioError {
+    InvalidPin(u8),
+}
+
+// Called in IRQ context with the pin that saw the event
+pub type PinCallback = fn(u8);
+
+// Serializes read-modify-write of the shared registers and the pull-up
+// sequence, and holds the per-pin callbacks
+struct GpioState {
+    callbacks: [Option<PinCallback>; PIN_COUNT as usize],
+}
+
+static STATE: IrqMutex<GpioState> = IrqMutex::new(GpioState {
+    callbacks: [None; PIN_COUNT as usize],
+});
+
+fn check(pin: u8) -> Result<(), GpioError> {
+    if pin < PIN_COUNT {
+        Ok(())
+    } else {
+        Err(GpioError::InvalidPin(pin))
+    }
+}
+
+// Register of a bank (0 for pins 0-31, 1 for 32-53) and the pin's bit
+fn bank_register(base: usize, pin: u8) -> (usize, u32) {
+    (base + (pin as usize / 32) * 4, 1 << (pin % 32))
+}
+
+pub fn set_function(pin: u8, function: Function) -> Result<(), GpioError> {
+    check(pin)?;
+    let register = GPFSEL0 + (pin as usize / 10) * 4;
+    let shift = (pin as u32 % 10) * 3;
+
+    let _state = STATE.lock();
+    unsafe {
+        let mut value = read_volatile(register as *const u32);
+        value &= !(0b111 << shift);
+        value |= (function as u32) << shift;
+        write_volatile(register as *mut u32, value);
+    }
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub fn function(pin: u8) -> Result<Function, GpioError> {
+    check(pin)?;
+    let register = GPFSEL0 + (pin as usize / 10) * 4;
+    let value = unsafe { read_volatile(register as *const u32) };
+    Ok(Function::from_bits(value >> ((pin as u32 % 10) * 3)))
+}
+
+// Drive an output pin high (GPSET/GPCLR are write-1, no lock needed)
+#[allow(dead_code)]
+pub fn set(pin: u8) -> Result<(), GpioError> {
+    check(pin)?;
+    let (register, bit) = bank_register(GPSET0, pin);
+    unsafe { write_volatile(register as *mut u32, bit) };
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub fn clear(pin: u8) -> Result<(), GpioError> {
+    check(pin)?;
+    let (register, bit) = bank_register(GPCLR0, pin);
+    unsafe { write_volatile(register as *mut u32, bit) };
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub fn write(pin: u8, high: bool) -> Result<(), GpioError> {
+    if high {
+        set(pin)
+    } else {
+        clear(pin)
+    }
+}
+
+#[allow(dead_code)]
+pub fn level(pin: u8) -> Result<bool, GpioError> {
+    check(pin)?;
+    let (register, bit) = bank_register(GPLEV0, pin);
+    Ok(unsafe { read_volatile(register as *const u32) } & bit != 0)
+}
+
+// Program the pull-up/down control: set GPPUD, clock it into the pin with
+// GPPUDCLK, then remove both
+pub fn set_pull(pin: u8, pull: Pull) -> Result<(), GpioError> {
+    check(pin)?;
+    let (clock, bit) = bank_register(GPPUDCLK0, pin);
+
+    let _state = STATE.lock();
+    unsafe {
+        write_volatile(GPPUD as *mut u32, pull as u32);
+        time::udelay(PULL_SETUP_US);
+        write_volatile(clock as *mut u32, bit);
+        time::udelay(PULL_SETUP_US);
+        write_volatile(GPPUD as *mut u32, 0);
+        write_volatile(clock as *mut u32, 0);
+    }
+    Ok(())
+}
+
+fn set_detect(pin: u8, mask: [bool; 4]) {
+    for (base, enabled) in DETECT_REGISTERS.iter().zip(mask) {
+        let (register, bit) = bank_register(*base, pin);
+        unsafe {
+            let value = read_volatile(register as *const u32);
+            let value = if enabled { value | bit } else { value & !bit };
+            write_volatile(register as *mut u32, value);
+        }
+    }
+}
+
+fn clear_event(pin: u8) {
+    let (register, bit) = bank_register(GPEDS0, pin);
+    unsafe { write_volatile(register as *mut u32, bit) };
+}
+
+// Call `callback` from the bank IRQ whenever `trigger` happens on `pin`
+#[allow(dead_code)]
+pub fn enable_interrupt(pin: u8, trigger: Trigger, callback: PinCallback) -> Result<(), GpioError> {
+    check(pin)?;
+
+    let mut state = STATE.lock();
+    state.callbacks[pin as usize] = Some(callback);
+    clear_event(pin);
+    set_detect(pin, trigger.detect_mask());
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub fn disable_interrupt(pin: u8) -> Result<(), GpioError> {
+    check(pin)?;
+
+    let mut state = STATE.lock();
+    set_detect(pin, [false; 4]);
+    clear_event(pin);
+    state.callbacks[pin as usize] = None;
+    Ok(())
+}
+
+// Shared by the three bank IRQs: acknowledge every pending event and run
+// the callbacks with the lock released
+fn gpio_interrupt_handler() {
+    let mut pending = [0u32; 2];
+    let mut callbacks = [None; PIN_COUNT as usize];
+    {
+        let state = STATE.lock();
+        for (bank, events) in pending.iter_mut().enumerate() {
+            let register = GPEDS0 + bank * 4;
+            unsafe {
+                *events = read_volatile(register as *const u32);
+                write_volatile(register as *mut u32, *events);
+            }
+        }
+        callbacks.copy_from_slice(&state.callbacks);
+    }
+
+    for pin in 0..PIN_COUNT {
+        if pending[pin as usize / 32] & (1 << (pin % 32)) != 0 {
+            if let Some(callback) = callbacks[pin as usize] {
+                callback(pin);
+            }
+        }
+    }
+}
+
+pub fn init() {
+    // Start with every event detector off and nothing pending
+    {
+        let _state = STATE.lock();
+        for bank in 0..2 {
+            let offset = bank * 4;
+            unsafe {
+                for base in DETECT_REGISTERS {
+                    write_volatile((base + offset) as *mut u32, 0);
+                }
+                write_volatile((GPEDS0 + offset) as *mut u32, u32::MAX);
+            }
+        }
+    }
+
+    for irq in [IRQ_GPIO_BANK0, IRQ_GPIO_BANK1, IRQ_GPIO_BANK2] {
+        gic::register_irq_handler(irq, gpio_interrupt_handler);
+        gic::enable_irq(irq);
+    }
+
+    println!("GPIO: {} pins, bank IRQs {}-{}", PIN_COUNT, IRQ_GPIO_BANK0, IRQ_GPIO_BANK2);
+}
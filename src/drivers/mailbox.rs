@@ -0,0 +1,359 @@
+use core::ptr::{read_volatile, write_volatile};
+use spin::Mutex;
+use crate::drivers::uart;
+use crate::println;
+use crate::time::{self, Duration};
+
+// BCM2835 VideoCore mailbox 0, property interface (channel 8)
+const MAILBOX_BASE: usize = 0x3F00_B880;
+
+const MAILBOX_READ: usize = MAILBOX_BASE;
+const MAILBOX_STATUS: usize = MAILBOX_BASE + 0x18;
+const MAILBOX_WRITE: usize = MAILBOX_BASE + 0x20;
+
+const STATUS_FULL: u32 = 1 << 31;
+const STATUS_EMPTY: u32 = 1 << 30;
+
+const CHANNEL_PROPERTY: u32 = 8;
+
+// Buffer and tag request/response codes
+const CODE_REQUEST: u32 = 0x0000_0000;
+const CODE_SUCCESS: u32 = 0x8000_0000;
+const TAG_RESPONSE: u32 = 1 << 31;
+const TAG_END: u32 = 0;
+
+// Property tags
+const TAG_FIRMWARE_REVISION: u32 = 0x0000_0001;
+const TAG_BOARD_MODEL: u32 = 0x0001_0001;
+const TAG_BOARD_REVISION: u32 = 0x0001_0002;
+const TAG_MAC_ADDRESS: u32 = 0x0001_0003;
+const TAG_BOARD_SERIAL: u32 = 0x0001_0004;
+const TAG_ARM_MEMORY: u32 = 0x0001_0005;
+const TAG_VC_MEMORY: u32 = 0x0001_0006;
+const TAG_GET_POWER_STATE: u32 = 0x0002_0001;
+const TAG_SET_POWER_STATE: u32 = 0x0002_8001;
+const TAG_GET_CLOCK_RATE: u32 = 0x0003_0002;
+const TAG_GET_MAX_CLOCK_RATE: u32 = 0x0003_0004;
+const TAG_GET_TEMPERATURE: u32 = 0x0003_0006;
+const TAG_GET_MIN_CLOCK_RATE: u32 = 0x0003_0007;
+const TAG_GET_MAX_TEMPERATURE: u32 = 0x0003_000A;
+const TAG_SET_CLOCK_RATE: u32 = 0x0003_8002;
+const TAG_GET_DMA_CHANNELS: u32 = 0x0006_0001;
+
+// Power state bits
+const POWER_ON: u32 = 1 << 0;
+const POWER_WAIT: u32 = 1 << 1;
+const POWER_NO_DEVICE: u32 = 1 << 1;
+
+const BUFFER_WORDS: usize = 256;
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MailboxError {
+    // The tags do not fit in the property buffer
+    BufferFull,
+    // The firmware did not answer in time
+    Timeout,
+    // The firmware rejected the whole buffer
+    RequestFailed,
+    // The tag was not answered (unknown tag or bad value)
+    TagFailed(u32),
+    // The device does not exist (power state tags)
+    NoDevice,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum ClockId {
+    Emmc = 1,
+    Uart = 2,
+    Arm = 3,
+    Core = 4,
+    V3d = 5,
+    H264 = 6,
+    Isp = 7,
+    Sdram = 8,
+    Pixel = 9,
+    Pwm = 10,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum PowerDevice {
+    SdCard = 0,
+    Uart0 = 1,
+    Uart1 = 2,
+    UsbHcd = 3,
+    I2c0 = 4,
+    I2c1 = 5,
+    I2c2 = 6,
+    Spi = 7,
+    Ccp2tx = 8,
+}
+
+// Tag position in a message, returned by Message::add_tag
+#[derive(Debug, Clone, Copy)]
+pub struct Tag {
+    offset: usize,
+    id: u32,
+}
+
+// Property message builder. The buffer lives on the caller's stack and is
+// 16-byte aligned, since the low 4 bits of its address carry the channel.
+#[repr(C, align(16))]
+pub struct Message {
+    words: [u32; BUFFER_WORDS],
+    // Next free word (the end tag is written by send)
+    len: usize,
+}
+
+impl Message {
+    pub const fn new() -> Self {
+        let mut words = [0; BUFFER_WORDS];
+        words[1] = CODE_REQUEST;
+        Message { words, len: 2 }
+    }
+
+    // Append a tag with its request values and room for `response_words`
+    // response values
+    pub fn add_tag(&mut self, id: u32, request: &[u32], response_words: usize) -> Result<Tag, MailboxError> {
+        let value_words = request.len().max(response_words);
+        // Header (3 words) + values + end tag
+        if self.len + 3 + value_words + 1 > BUFFER_WORDS {
+            return Err(MailboxError::BufferFull);
+        }
+
+        let offset = self.len;
+        self.words[offset] = id;
+        self.words[offset + 1] = (value_words * 4) as u32;
+        self.words[offset + 2] = 0;
+        self.words[offset + 3..offset + 3 + request.len()].copy_from_slice(request);
+        self.words[offset + 3 + request.len()..offset + 3 + value_words].fill(0);
+        self.len += 3 + value_words;
+
+        Ok(Tag { offset, id })
+    }
+
+    // Hand the buffer to the firmware and wait for the answer
+    pub fn send(&mut self) -> Result<(), MailboxError> {
+        self.words[self.len] = TAG_END;
+        self.words[0] = ((self.len + 1) * 4) as u32;
+        self.words[1] = CODE_REQUEST;
+
+        let address = self.words.as_ptr() as usize as u32;
+        let _mailbox = MAILBOX_LOCK.lock();
+
+        call(address | CHANNEL_PROPERTY)?;
+
+        match unsafe { read_volatile(&self.words[1]) } {
+            CODE_SUCCESS => Ok(()),
+            _ => Err(MailboxError::RequestFailed),
+        }
+    }
+
+    // Response values of a tag after send()
+    pub fn response(&self, tag: Tag) -> Result<&[u32], MailboxError> {
+        let header = &self.words[tag.offset..tag.offset + 3];
+        let code = unsafe { read_volatile(&header[2]) };
+        if header[0] != tag.id || code & TAG_RESPONSE == 0 {
+            return Err(MailboxError::TagFailed(tag.id));
+        }
+
+        let buffer_words = header[1] as usize / 4;
+        let response_words = ((code & !TAG_RESPONSE) as usize).div_ceil(4).min(buffer_words);
+        let start = tag.offset + 3;
+        Ok(&self.words[start..start + response_words])
+    }
+}
+
+// One transaction on the hardware at a time
+static MAILBOX_LOCK: Mutex<()> = Mutex::new(());
+
+fn call(message: u32) -> Result<(), MailboxError> {
+    let status = || unsafe { read_volatile(MAILBOX_STATUS as *const u32) };
+
+    if !time::wait_for(RESPONSE_TIMEOUT, || status() & STATUS_FULL == 0) {
+        return Err(MailboxError::Timeout);
+    }
+
+    // Buffer contents must reach memory before the VideoCore reads it
+    unsafe {
+        core::arch::asm!("dsb sy", options(nostack));
+        write_volatile(MAILBOX_WRITE as *mut u32, message);
+    }
+
+    // Other channels may answer first: skip their messages
+    let answered = time::wait_for(RESPONSE_TIMEOUT, || {
+        status() & STATUS_EMPTY == 0
+            && unsafe { read_volatile(MAILBOX_READ as *const u32) } == message
+    });
+    unsafe { core::arch::asm!("dsb sy", options(nostack)) };
+
+    if answered { Ok(()) } else { Err(MailboxError::Timeout) }
+}
+
+// Single-tag request helper
+fn query<const N: usize>(id: u32, request: &[u32]) -> Result<[u32; N], MailboxError> {
+    let mut message = Message::new();
+    let tag = message.add_tag(id, request, N)?;
+    message.send()?;
+
+    let response = message.response(tag)?;
+    if response.len() < N {
+        return Err(MailboxError::TagFailed(id));
+    }
+
+    let mut values = [0; N];
+    values.copy_from_slice(&response[..N]);
+    Ok(values)
+}
+
+pub fn firmware_revision() -> Result<u32, MailboxError> {
+    query::<1>(TAG_FIRMWARE_REVISION, &[]).map(|[revision]| revision)
+}
+
+pub fn board_model() -> Result<u32, MailboxError> {
+    query::<1>(TAG_BOARD_MODEL, &[]).map(|[model]| model)
+}
+
+pub fn board_revision() -> Result<u32, MailboxError> {
+    query::<1>(TAG_BOARD_REVISION, &[]).map(|[revision]| revision)
+}
+
+pub fn board_serial() -> Result<u64, MailboxError> {
+    query::<2>(TAG_BOARD_SERIAL, &[]).map(|[low, high]| (high as u64) << 32 | low as u64)
+}
+
+pub fn mac_address() -> Result<[u8; 6], MailboxError> {
+    let words = query::<2>(TAG_MAC_ADDRESS, &[])?;
+    let bytes = [words[0].to_le_bytes(), words[1].to_le_bytes()];
+    Ok([bytes[0][0], bytes[0][1], bytes[0][2], bytes[0][3], bytes[1][0], bytes[1][1]])
+}
+
+// (base, size) of the memory given to the ARM
+pub fn arm_memory() -> Result<(u32, u32), MailboxError> {
+    query::<2>(TAG_ARM_MEMORY, &[]).map(|[base, size]| (base, size))
+}
+
+// (base, size) of the memory kept by the VideoCore
+pub fn vc_memory() -> Result<(u32, u32), MailboxError> {
+    query::<2>(TAG_VC_MEMORY, &[]).map(|[base, size]| (base, size))
+}
+
+pub fn clock_rate(clock: ClockId) -> Result<u32, MailboxError> {
+    query::<2>(TAG_GET_CLOCK_RATE, &[clock as u32]).map(|[_, rate]| rate)
+}
+
+pub fn max_clock_rate(clock: ClockId) -> Result<u32, MailboxError> {
+    query::<2>(TAG_GET_MAX_CLOCK_RATE, &[clock as u32]).map(|[_, rate]| rate)
+}
+
+#[allow(dead_code)]
+pub fn min_clock_rate(clock: ClockId) -> Result<u32, MailboxError> {
+    query::<2>(TAG_GET_MIN_CLOCK_RATE, &[clock as u32]).map(|[_, rate]| rate)
+}
+
+// Returns the rate actually set by the firmware
+#[allow(dead_code)]
+pub fn set_clock_rate(clock: ClockId, hz: u32, skip_turbo: bool) -> Result<u32, MailboxError> {
+    let rate = query::<2>(TAG_SET_CLOCK_RATE, &[clock as u32, hz, skip_turbo as u32])
+        .map(|[_, rate]| rate)?;
+
+    // The PL011 divisor depends on its reference clock
+    if clock == ClockId::Uart && rate != 0 {
+        let _ = uart::set_clock_hz(rate);
+    }
+    Ok(rate)
+}
+
+// SoC temperature in millidegrees Celsius
+pub fn temperature() -> Result<u32, MailboxError> {
+    query::<2>(TAG_GET_TEMPERATURE, &[0]).map(|[_, value]| value)
+}
+
+pub fn max_temperature() -> Result<u32, MailboxError> {
+    query::<2>(TAG_GET_MAX_TEMPERATURE, &[0]).map(|[_, value]| value)
+}
+
+#[allow(dead_code)]
+pub fn power_state(device: PowerDevice) -> Result<bool, MailboxError> {
+    let [_, state] = query::<2>(TAG_GET_POWER_STATE, &[device as u32])?;
+    if state & POWER_NO_DEVICE != 0 {
+        return Err(MailboxError::NoDevice);
+    }
+    Ok(state & POWER_ON != 0)
+}
+
+// Power a device on or off, optionally waiting for it to become stable
+pub fn set_power_state(device: PowerDevice, on: bool, wait: bool) -> Result<bool, MailboxError> {
+    let mut state = if on { POWER_ON } else { 0 };
+    if wait {
+        state |= POWER_WAIT;
+    }
+
+    let [_, state] = query::<2>(TAG_SET_POWER_STATE, &[device as u32, state])?;
+    if state & POWER_NO_DEVICE != 0 {
+        return Err(MailboxError::NoDevice);
+    }
+    Ok(state & POWER_ON != 0)
+}
+
+// Bit mask of the DMA channels the firmware leaves to the ARM
+pub fn dma_channels() -> Result<u32, MailboxError> {
+    query::<1>(TAG_GET_DMA_CHANNELS, &[]).map(|[mask]| mask)
+}
+
+pub fn init() {
+    match (board_model(), board_revision(), firmware_revision()) {
+        (Ok(model), Ok(revision), Ok(firmware)) => println!(
+            "Mailbox: board model 0x{:x}, revision 0x{:06x}, firmware 0x{:08x}",
+            model, revision, firmware
+        ),
+        (Err(error), _, _) | (_, Err(error), _) | (_, _, Err(error)) => {
+            println!("Mailbox: firmware not responding ({:?})", error);
+            return;
+        }
+    }
+
+    if let Ok(serial) = board_serial() {
+        println!("Mailbox: serial {:016x}", serial);
+    }
+    if let Ok(mac) = mac_address() {
+        println!(
+            "Mailbox: MAC {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+        );
+    }
+    if let (Ok((arm_base, arm_size)), Ok((vc_base, vc_size))) = (arm_memory(), vc_memory()) {
+        println!(
+            "Mailbox: ARM memory 0x{:08x} ({} MB), VC memory 0x{:08x} ({} MB)",
+            arm_base,
+            arm_size >> 20,
+            vc_base,
+            vc_size >> 20
+        );
+    }
+    if let (Ok(arm), Ok(arm_max), Ok(core)) =
+        (clock_rate(ClockId::Arm), max_clock_rate(ClockId::Arm), clock_rate(ClockId::Core))
+    {
+        println!(
+            "Mailbox: ARM clock {} MHz (max {} MHz), core clock {} MHz",
+            arm / 1_000_000,
+            arm_max / 1_000_000,
+            core / 1_000_000
+        );
+    }
+    if let (Ok(temp), Ok(max)) = (temperature(), max_temperature()) {
+        println!("Mailbox: SoC temperature {}.{:03} C (max {} C)", temp / 1000, temp % 1000, max / 1000);
+    }
+
+    // Use the real PL011 reference clock for the baud divisor
+    if let Ok(hz) = clock_rate(ClockId::Uart) {
+        if hz != 0 && hz != uart::clock_hz() {
+            println!("Mailbox: UART clock {} Hz, updating divisor", hz);
+            if uart::set_clock_hz(hz).is_err() {
+                println!("Mailbox: UART clock out of range, keeping {} Hz", uart::clock_hz());
+            }
+        }
+    }
+}
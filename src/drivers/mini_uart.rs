@@ -0,0 +1,110 @@
+use core::fmt;
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::drivers::gpio::{self, Function, Pull};
+
+// BCM2837 auxiliary peripherals: mini UART (UART1)
+const AUX_BASE: usize = 0x3F21_5000;
+
+const AUX_ENABLES: usize = AUX_BASE + 0x04;
+const AUX_MU_IO: usize = AUX_BASE + 0x40;
+const AUX_MU_IER: usize = AUX_BASE + 0x44;
+const AUX_MU_IIR: usize = AUX_BASE + 0x48;
+const AUX_MU_LCR: usize = AUX_BASE + 0x4C;
+const AUX_MU_MCR: usize = AUX_BASE + 0x50;
+const AUX_MU_LSR: usize = AUX_BASE + 0x54;
+const AUX_MU_CNTL: usize = AUX_BASE + 0x60;
+const AUX_MU_BAUD: usize = AUX_BASE + 0x68;
+
+// Pins routed to the mini UART in ALT5
+const TXD1_PIN: u8 = 14;
+const RXD1_PIN: u8 = 15;
+
+// LSR bits
+const LSR_DATA_READY: u32 = 1 << 0;
+const LSR_TX_EMPTY: u32 = 1 << 5;
+
+// The mini UART is clocked from the VPU core clock
+const CORE_CLOCK_HZ: u32 = 250_000_000;
+const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+pub struct MiniUart;
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+impl MiniUart {
+    pub fn init(&self) {
+        setup_gpio();
+
+        unsafe {
+            // Enable the mini UART block, keep it quiet while configuring
+            let enables = read_volatile(AUX_ENABLES as *const u32);
+            write_volatile(AUX_ENABLES as *mut u32, enables | 1);
+            write_volatile(AUX_MU_CNTL as *mut u32, 0);
+            write_volatile(AUX_MU_IER as *mut u32, 0);
+
+            // 8 bits, RTS high, clear FIFOs
+            write_volatile(AUX_MU_LCR as *mut u32, 0b11);
+            write_volatile(AUX_MU_MCR as *mut u32, 0);
+            write_volatile(AUX_MU_IIR as *mut u32, 0b110);
+
+            write_volatile(AUX_MU_BAUD as *mut u32, baud_register(DEFAULT_BAUD_RATE));
+
+            // Enable TX and RX
+            write_volatile(AUX_MU_CNTL as *mut u32, 0b11);
+        }
+
+        INITIALIZED.store(true, Ordering::Release);
+    }
+
+    pub fn write_byte(&self, byte: u8) {
+        unsafe {
+            while read_volatile(AUX_MU_LSR as *const u32) & LSR_TX_EMPTY == 0 {}
+            write_volatile(AUX_MU_IO as *mut u32, byte as u32);
+        }
+    }
+
+    pub fn read_byte(&self) -> Option<u8> {
+        unsafe {
+            if read_volatile(AUX_MU_LSR as *const u32) & LSR_DATA_READY != 0 {
+                Some(read_volatile(AUX_MU_IO as *const u32) as u8)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+impl fmt::Write for MiniUart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+// baud = core_clock / (8 * (reg + 1))
+fn baud_register(baud_rate: u32) -> u32 {
+    (CORE_CLOCK_HZ / (8 * baud_rate)).saturating_sub(1)
+}
+
+// GPIO 14 (TXD1) and 15 (RXD1) in ALT5, pull-up/down disabled
+fn setup_gpio() {
+    for pin in [TXD1_PIN, RXD1_PIN] {
+        // Both pins are valid, these cannot fail
+        let _ = gpio::set_function(pin, Function::Alt5);
+        let _ = gpio::set_pull(pin, Pull::None);
+    }
+}
+
+pub static MINI_UART: MiniUart = MiniUart;
+
+pub fn init() {
+    MINI_UART.init();
+}
+
+// The TX-empty poll never finishes on a disabled block
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::Acquire)
+}
@@ -1,14 +1,56 @@
+pub mod earlycon;
 pub mod uart;
+pub mod mini_uart;
+pub mod console;
 pub mod gic;
+pub mod gpio;
 pub mod timer;
+pub mod systimer;
+pub mod font;
+pub mod framebuffer;
+pub mod fbcon;
 pub mod display;
+pub mod mailbox;
+pub mod dma;
+pub mod rng;
+pub mod watchdog;
+pub mod block;
+pub mod emmc;
+pub mod partition;
+pub mod usb;
+pub mod usb_keyboard;
 
 use crate::println;
 
 pub fn init() {
+    // Nothing from the early console may be cut off by the reconfiguration
+    earlycon::flush();
     uart::init();
+    console::init();
     gic::init();
+    gpio::init();
+    uart::init_interrupts();
+    systimer::init();
     timer::init();
+    mailbox::init();
+    dma::init();
+    rng::init();
     display::init();
+    emmc::init();
+    partition::init();
+    usb::init();
+    watchdog::init();
     println!("Drivers initialized");
-}
\ No newline at end of file
+}
+
+// Deferred driver work, flagged by timer callbacks and done here in task
+// context: from the main loop and from the idle path (time::idle)
+pub fn poll() {
+    usb_keyboard::poll();
+    display::poll();
+}
+
+// Work flagged and not done yet: the idle path does not sleep on it
+pub fn poll_pending() -> bool {
+    usb_keyboard::poll_pending() || display::redraw_pending()
+}
@@ -0,0 +1,453 @@
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Once;
+use crate::drivers::block::{self, BlockDevice, BlockError, SECTOR_SIZE};
+use crate::println;
+
+// Partition discovery on block devices: MBR (primary partitions and the
+// EBR chain of an extended partition) and GPT (header and entry array
+// checked against their CRC32, backup header used if the primary one is
+// damaged). Every partition found is registered as a block device of its
+// own ("mmcblk0p1", ...) that maps LBAs into the parent and rejects
+// accesses outside the partition.
+
+// The block registry holds at most 16 devices, disks included
+const MAX_PARTITIONS: usize = 16;
+const MAX_NAME: usize = 16;
+
+// Bounds the EBR chain walk (a corrupt chain could loop)
+const MAX_LOGICAL: u32 = 64;
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const MBR_TABLE_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+
+const MBR_TYPE_EMPTY: u8 = 0x00;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+const MBR_EXTENDED_TYPES: [u8; 3] = [0x05, 0x0F, 0x85];
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_HEADER_MIN_SIZE: usize = 92;
+const GPT_ENTRY_MIN_SIZE: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKind {
+    // MBR system id byte
+    Mbr(u8),
+    // GPT partition type GUID (on-disk byte order)
+    Gpt([u8; 16]),
+}
+
+impl PartitionKind {
+    fn description(&self) -> &'static str {
+        match *self {
+            PartitionKind::Mbr(0x01) => "FAT12",
+            PartitionKind::Mbr(0x04 | 0x06 | 0x0E) => "FAT16",
+            PartitionKind::Mbr(0x0B | 0x0C) => "FAT32",
+            PartitionKind::Mbr(0x07) => "NTFS/exFAT",
+            PartitionKind::Mbr(0x82) => "Linux swap",
+            PartitionKind::Mbr(0x83) => "Linux",
+            PartitionKind::Mbr(_) => "unknown",
+            PartitionKind::Gpt(guid) => match guid_string(&guid).as_str() {
+                "C12A7328-F81F-11D2-BA4B-00A0C93EC93B" => "EFI system",
+                "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7" => "basic data",
+                "0FC63DAF-8483-4772-8E79-3D69D8477DE4" => "Linux filesystem",
+                "0657FD6D-A4AB-43C4-84E5-0933C84B4F4F" => "Linux swap",
+                _ => "unknown",
+            },
+        }
+    }
+}
+
+impl fmt::Display for PartitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartitionKind::Mbr(id) => write!(f, "type 0x{:02x} ({})", id, self.description()),
+            PartitionKind::Gpt(guid) => write!(f, "{} ({})", guid_string(guid).as_str(), self.description()),
+        }
+    }
+}
+
+// GUID text form: the first three fields are little-endian on disk
+struct GuidString([u8; 36]);
+
+impl GuidString {
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.0).unwrap_or("")
+    }
+}
+
+fn guid_string(guid: &[u8; 16]) -> GuidString {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    const ORDER: [usize; 16] = [3, 2, 1, 0, 5, 4, 7, 6, 8, 9, 10, 11, 12, 13, 14, 15];
+
+    let mut text = [b'-'; 36];
+    let mut position = 0;
+    for (index, &byte) in ORDER.iter().enumerate() {
+        if matches!(index, 4 | 6 | 8 | 10) {
+            position += 1;
+        }
+        text[position] = HEX[(guid[byte] >> 4) as usize];
+        text[position + 1] = HEX[(guid[byte] & 0xF) as usize];
+        position += 2;
+    }
+    GuidString(text)
+}
+
+#[derive(Clone, Copy)]
+struct PartitionInfo {
+    parent: &'static dyn BlockDevice,
+    start: u64,
+    blocks: u64,
+    name: [u8; MAX_NAME],
+    name_len: usize,
+}
+
+// A partition slot, filled once when the partition is discovered
+pub struct Partition {
+    info: Once<PartitionInfo>,
+}
+
+impl Partition {
+    const fn new() -> Self {
+        Partition { info: Once::new() }
+    }
+
+    fn info(&self) -> Result<&PartitionInfo, BlockError> {
+        self.info.get().ok_or(BlockError::NoDevice)
+    }
+}
+
+impl BlockDevice for Partition {
+    fn name(&self) -> &str {
+        self.info
+            .get()
+            .map_or("", |info| core::str::from_utf8(&info.name[..info.name_len]).unwrap_or(""))
+    }
+
+    fn block_size(&self) -> usize {
+        self.info.get().map_or(SECTOR_SIZE, |info| info.parent.block_size())
+    }
+
+    fn block_count(&self) -> u64 {
+        self.info.get().map_or(0, |info| info.blocks)
+    }
+
+    fn read_blocks(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        block::check_request(self, lba, buf.len())?;
+        let info = self.info()?;
+        info.parent.read_blocks(info.start + lba, buf)
+    }
+
+    fn write_blocks(&self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        block::check_request(self, lba, buf.len())?;
+        let info = self.info()?;
+        info.parent.write_blocks(info.start + lba, buf)
+    }
+
+    fn read_only(&self) -> bool {
+        self.info.get().is_some_and(|info| info.parent.read_only())
+    }
+}
+
+static PARTITIONS: [Partition; MAX_PARTITIONS] = [const { Partition::new() }; MAX_PARTITIONS];
+static NEXT_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+// Name for partition `number` of `parent`: "mmcblk0" -> "mmcblk0p1"
+fn partition_name(parent: &str, number: u32) -> ([u8; MAX_NAME], usize) {
+    struct Name([u8; MAX_NAME], usize);
+
+    impl fmt::Write for Name {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let count = s.len().min(MAX_NAME - self.1);
+            self.0[self.1..self.1 + count].copy_from_slice(&s.as_bytes()[..count]);
+            self.1 += count;
+            Ok(())
+        }
+    }
+
+    let mut name = Name([0; MAX_NAME], 0);
+    let _ = fmt::Write::write_fmt(&mut name, format_args!("{}p{}", parent, number));
+    (name.0, name.1)
+}
+
+// Bounds-check a partition against its parent and register it
+fn add(parent: &'static dyn BlockDevice, number: u32, start: u64, blocks: u64, kind: PartitionKind) {
+    let fits = start
+        .checked_add(blocks)
+        .is_some_and(|end| start > 0 && end <= parent.block_count());
+    if blocks == 0 || !fits {
+        println!(
+            "Partition {} of {}: blocks {}+{} outside the device, ignored",
+            number,
+            parent.name(),
+            start,
+            blocks
+        );
+        return;
+    }
+
+    let slot = NEXT_SLOT.fetch_add(1, Ordering::AcqRel);
+    let Some(partition) = PARTITIONS.get(slot) else {
+        println!("Partition {} of {}: table full, ignored", number, parent.name());
+        return;
+    };
+
+    let (name, name_len) = partition_name(parent.name(), number);
+    partition.info.call_once(|| PartitionInfo {
+        parent,
+        start,
+        blocks,
+        name,
+        name_len,
+    });
+
+    println!("  {}: start {}, {} blocks, {}", partition.name(), start, blocks, kind);
+    if block::register(partition).is_err() {
+        println!("  {}: no free block device slot", partition.name());
+    }
+}
+
+fn read_sector(device: &dyn BlockDevice, lba: u64, sector: &mut [u8; SECTOR_SIZE]) -> Result<(), BlockError> {
+    device.read_blocks(lba, sector)
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn le_u64(bytes: &[u8], offset: usize) -> u64 {
+    le_u32(bytes, offset) as u64 | (le_u32(bytes, offset + 4) as u64) << 32
+}
+
+#[derive(Clone, Copy)]
+struct MbrEntry {
+    kind: u8,
+    start: u64,
+    blocks: u64,
+}
+
+fn mbr_entries(sector: &[u8; SECTOR_SIZE]) -> Option<[MbrEntry; 4]> {
+    if sector[510..512] != MBR_SIGNATURE {
+        return None;
+    }
+
+    let mut entries = [MbrEntry { kind: 0, start: 0, blocks: 0 }; 4];
+    for (index, entry) in entries.iter_mut().enumerate() {
+        let raw = &sector[MBR_TABLE_OFFSET + index * MBR_ENTRY_SIZE..][..MBR_ENTRY_SIZE];
+        *entry = MbrEntry {
+            kind: raw[4],
+            start: le_u32(raw, 8) as u64,
+            blocks: le_u32(raw, 12) as u64,
+        };
+    }
+    Some(entries)
+}
+
+// Logical partitions: each EBR holds the partition (relative to the EBR)
+// and a link to the next EBR (relative to the start of the extended one)
+fn scan_extended(device: &'static dyn BlockDevice, extended_start: u64) -> Result<(), BlockError> {
+    let mut sector = [0u8; SECTOR_SIZE];
+    let mut ebr = extended_start;
+
+    for number in 5..5 + MAX_LOGICAL {
+        read_sector(device, ebr, &mut sector)?;
+        let Some(entries) = mbr_entries(&sector) else {
+            println!("  {}: bad EBR signature at block {}", device.name(), ebr);
+            return Ok(());
+        };
+
+        let logical = entries[0];
+        if logical.kind != MBR_TYPE_EMPTY {
+            add(device, number, ebr + logical.start, logical.blocks, PartitionKind::Mbr(logical.kind));
+        }
+
+        let next = entries[1];
+        if next.kind == MBR_TYPE_EMPTY || next.start == 0 {
+            return Ok(());
+        }
+        ebr = extended_start + next.start;
+    }
+
+    println!("  {}: EBR chain longer than {} entries, stopped", device.name(), MAX_LOGICAL);
+    Ok(())
+}
+
+fn scan_mbr(device: &'static dyn BlockDevice, entries: &[MbrEntry; 4]) -> Result<(), BlockError> {
+    println!("Partitions on {}: MBR", device.name());
+
+    for (index, entry) in entries.iter().enumerate() {
+        let number = index as u32 + 1;
+        match entry.kind {
+            MBR_TYPE_EMPTY => {}
+            kind if MBR_EXTENDED_TYPES.contains(&kind) => scan_extended(device, entry.start)?,
+            kind => add(device, number, entry.start, entry.blocks, PartitionKind::Mbr(kind)),
+        }
+    }
+    Ok(())
+}
+
+// CRC-32 (IEEE 802.3, reflected), as used by GPT
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+// Running CRC: start with !0, feed data, finish with !crc
+fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    data.iter()
+        .fold(crc, |crc, &byte| CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0, data)
+}
+
+#[derive(Clone, Copy)]
+struct GptHeader {
+    entries_lba: u64,
+    entry_count: u32,
+    entry_size: usize,
+    entries_crc: u32,
+    backup_lba: u64,
+}
+
+fn read_gpt_header(device: &dyn BlockDevice, lba: u64) -> Result<Option<GptHeader>, BlockError> {
+    let mut sector = [0u8; SECTOR_SIZE];
+    read_sector(device, lba, &mut sector)?;
+
+    if &sector[0..8] != GPT_SIGNATURE {
+        return Ok(None);
+    }
+    let size = le_u32(&sector, 12) as usize;
+    if !(GPT_HEADER_MIN_SIZE..=SECTOR_SIZE).contains(&size) {
+        return Ok(None);
+    }
+
+    // The header CRC is computed with its own field zeroed
+    let stored_crc = le_u32(&sector, 16);
+    sector[16..20].fill(0);
+    if crc32(&sector[..size]) != stored_crc || le_u64(&sector, 24) != lba {
+        return Ok(None);
+    }
+
+    let entry_size = le_u32(&sector, 84) as usize;
+    if entry_size < GPT_ENTRY_MIN_SIZE || !entry_size.is_power_of_two() || entry_size > SECTOR_SIZE {
+        return Ok(None);
+    }
+
+    Ok(Some(GptHeader {
+        entries_lba: le_u64(&sector, 72),
+        entry_count: le_u32(&sector, 80),
+        entry_size,
+        entries_crc: le_u32(&sector, 88),
+        backup_lba: le_u64(&sector, 32),
+    }))
+}
+
+// Walk the entry array one sector at a time (no allocator), calling
+// `f(number, entry)` for every entry
+fn for_each_gpt_entry(
+    device: &dyn BlockDevice,
+    header: &GptHeader,
+    mut f: impl FnMut(u32, &[u8]),
+) -> Result<(), BlockError> {
+    let per_sector = SECTOR_SIZE / header.entry_size;
+    let sectors = (header.entry_count as usize).div_ceil(per_sector);
+    let mut sector = [0u8; SECTOR_SIZE];
+
+    for index in 0..sectors {
+        let lba = header.entries_lba.checked_add(index as u64).ok_or(BlockError::OutOfRange)?;
+        read_sector(device, lba, &mut sector)?;
+        for (slot, entry) in sector.chunks_exact(header.entry_size).enumerate() {
+            let number = (index * per_sector + slot) as u32;
+            if number < header.entry_count {
+                f(number + 1, entry);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn gpt_entries_valid(device: &dyn BlockDevice, header: &GptHeader) -> Result<bool, BlockError> {
+    let mut crc = !0;
+    for_each_gpt_entry(device, header, |_, entry| crc = crc32_update(crc, entry))?;
+    Ok(!crc == header.entries_crc)
+}
+
+fn scan_gpt(device: &'static dyn BlockDevice) -> Result<(), BlockError> {
+    // Primary header at LBA 1, backup at the last block
+    let mut header = read_gpt_header(device, 1)?.filter(|header| {
+        gpt_entries_valid(device, header).unwrap_or(false)
+    });
+    if header.is_none() {
+        println!("Partitions on {}: primary GPT damaged, trying the backup", device.name());
+        let last = device.block_count() - 1;
+        header = read_gpt_header(device, last)?.filter(|header| {
+            header.backup_lba == 1 && gpt_entries_valid(device, header).unwrap_or(false)
+        });
+    }
+    let Some(header) = header else {
+        println!("Partitions on {}: no valid GPT", device.name());
+        return Ok(());
+    };
+
+    println!("Partitions on {}: GPT, {} entries", device.name(), header.entry_count);
+    for_each_gpt_entry(device, &header, |number, entry| {
+        let mut type_guid = [0u8; 16];
+        type_guid.copy_from_slice(&entry[0..16]);
+        if type_guid == [0; 16] {
+            return;
+        }
+
+        // First and last LBA, inclusive
+        let first = le_u64(entry, 32);
+        let last = le_u64(entry, 40);
+        let Some(end) = last.checked_add(1) else {
+            println!("Partition {} of {}: last block {} out of range, ignored", number, device.name(), last);
+            return;
+        };
+        add(device, number, first, end.saturating_sub(first), PartitionKind::Gpt(type_guid));
+    })
+}
+
+// Look for a partition table on a whole-disk device
+pub fn scan(device: &'static dyn BlockDevice) -> Result<(), BlockError> {
+    if device.block_size() != SECTOR_SIZE || device.block_count() < 2 {
+        return Ok(());
+    }
+
+    let mut sector = [0u8; SECTOR_SIZE];
+    read_sector(device, 0, &mut sector)?;
+    let Some(entries) = mbr_entries(&sector) else {
+        println!("Partitions on {}: no partition table", device.name());
+        return Ok(());
+    };
+
+    if entries.iter().any(|entry| entry.kind == MBR_TYPE_GPT_PROTECTIVE) {
+        scan_gpt(device)
+    } else {
+        scan_mbr(device, &entries)
+    }
+}
+
+// Scan every block device registered so far (the partitions added during
+// the scan are not scanned themselves)
+pub fn init() {
+    block::for_each(|device| {
+        if let Err(error) = scan(device) {
+            println!("Partitions on {}: read failed ({:?})", device.name(), error);
+        }
+    });
+}
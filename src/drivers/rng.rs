@@ -0,0 +1,88 @@
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::println;
+use crate::time::{self, Duration};
+
+// BCM2835 hardware random number generator (also emulated by QEMU). Its
+// output is raw entropy for the kernel pool in crate::random, not meant to
+// be handed out directly.
+const RNG_BASE: usize = 0x3F10_4000;
+
+const RNG_CTRL: usize = RNG_BASE;
+const RNG_STATUS: usize = RNG_BASE + 0x04;
+const RNG_DATA: usize = RNG_BASE + 0x08;
+const RNG_INT_MASK: usize = RNG_BASE + 0x10;
+
+const CTRL_RBGEN: u32 = 1 << 0;
+const INT_MASK_OFF: u32 = 1 << 0;
+
+// Numbers generated and thrown away after enabling, before the first one
+// is trusted (written to the low bits of STATUS)
+const WARMUP_COUNT: u32 = 0x40000;
+
+// STATUS bits 31:24: words waiting in the FIFO
+const STATUS_WORDS_SHIFT: u32 = 24;
+
+// Warm-up takes well under a second; later words come every few µs
+const WARMUP_TIMEOUT: Duration = Duration::from_secs(1);
+const WORD_TIMEOUT: Duration = Duration::from_millis(10);
+
+static READY: AtomicBool = AtomicBool::new(false);
+
+fn read(register: usize) -> u32 {
+    unsafe { read_volatile(register as *const u32) }
+}
+
+fn write(register: usize, value: u32) {
+    unsafe { write_volatile(register as *mut u32, value) }
+}
+
+fn words_available() -> u32 {
+    read(RNG_STATUS) >> STATUS_WORDS_SHIFT
+}
+
+pub fn available() -> bool {
+    READY.load(Ordering::Acquire)
+}
+
+// A word from the FIFO if one is waiting (never blocks, IRQ safe)
+pub fn try_read_word() -> Option<u32> {
+    if available() && words_available() > 0 {
+        Some(read(RNG_DATA))
+    } else {
+        None
+    }
+}
+
+// Wait for the next word (up to a few ms)
+pub fn read_word() -> Option<u32> {
+    if !available() || !time::wait_for(WORD_TIMEOUT, || words_available() > 0) {
+        return None;
+    }
+    Some(read(RNG_DATA))
+}
+
+// Fill `words`, returning how many were read before the generator stalled
+pub fn fill(words: &mut [u32]) -> usize {
+    for (count, word) in words.iter_mut().enumerate() {
+        match read_word() {
+            Some(value) => *word = value,
+            None => return count,
+        }
+    }
+    words.len()
+}
+
+pub fn init() {
+    // Polled only: keep the interrupt off
+    write(RNG_INT_MASK, read(RNG_INT_MASK) | INT_MASK_OFF);
+    write(RNG_STATUS, WARMUP_COUNT);
+    write(RNG_CTRL, read(RNG_CTRL) | CTRL_RBGEN);
+
+    if !time::wait_for(WARMUP_TIMEOUT, || words_available() > 0) {
+        println!("RNG: no output after warm-up, disabled");
+        return;
+    }
+    READY.store(true, Ordering::Release);
+    println!("RNG: hardware generator ready ({} words queued)", words_available());
+}
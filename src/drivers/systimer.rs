@@ -0,0 +1,169 @@
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::drivers::gic;
+use crate::println;
+use crate::time::{self, Duration};
+
+// BCM2835 system timer: free-running 64-bit counter at 1MHz with four
+// compare channels. Channels 0 and 2 are used by the VideoCore, so only
+// channels 1 and 3 are available to the ARM.
+const SYSTIMER_BASE: usize = 0x3F003000;
+
+const SYSTIMER_CS: usize = SYSTIMER_BASE;
+const SYSTIMER_CLO: usize = SYSTIMER_BASE + 0x04;
+const SYSTIMER_CHI: usize = SYSTIMER_BASE + 0x08;
+const SYSTIMER_C0: usize = SYSTIMER_BASE + 0x0C;
+
+pub const FREQUENCY: u64 = 1_000_000;
+
+// Compare channels and their IRQs
+pub const CHANNEL_EVENT: usize = 1;  // Event device for the timer queue
+pub const CHANNEL_USER: usize = 3;   // Free for one-shot callbacks
+pub const IRQ_SYSTIMER_1: u32 = 1;
+pub const IRQ_SYSTIMER_3: u32 = 3;
+
+// Event device state (channel 1 has no mask bit, so keep a software flag)
+static EVENT_ARMED: AtomicBool = AtomicBool::new(false);
+static mut USER_CALLBACK: Option<fn()> = None;
+
+// Consistent 64-bit read: retry if CHI changed while reading CLO
+pub fn read_counter() -> u64 {
+    unsafe {
+        loop {
+            let hi = read_volatile(SYSTIMER_CHI as *const u32);
+            let lo = read_volatile(SYSTIMER_CLO as *const u32);
+            if read_volatile(SYSTIMER_CHI as *const u32) == hi {
+                return ((hi as u64) << 32) | lo as u64;
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn uptime() -> Duration {
+    Duration::from_micros(read_counter())
+}
+
+fn compare_register(channel: usize) -> usize {
+    SYSTIMER_C0 + channel * 4
+}
+
+// Set the 32-bit compare value of a channel (matches against CLO)
+pub fn set_compare(channel: usize, value: u32) {
+    if channel != CHANNEL_EVENT && channel != CHANNEL_USER {
+        println!("System timer channel {} is reserved for the GPU", channel);
+        return;
+    }
+
+    unsafe { write_volatile(compare_register(channel) as *mut u32, value) };
+}
+
+// Clear the match flag of a channel (write 1 to clear)
+pub fn clear_match(channel: usize) {
+    unsafe { write_volatile(SYSTIMER_CS as *mut u32, 1 << channel) };
+}
+
+#[allow(dead_code)]
+pub fn matched(channel: usize) -> bool {
+    unsafe { read_volatile(SYSTIMER_CS as *const u32) & (1 << channel) != 0 }
+}
+
+// Program the event device to fire at an absolute counter value (core 0
+// only: one compare channel, whose IRQ only reaches core 0)
+pub fn program_event(deadline: u64) {
+    EVENT_ARMED.store(true, Ordering::Release);
+
+    let mut now = read_counter();
+    loop {
+        // Compare only matches the low 32 bits: never program a past deadline
+        let target = deadline.max(now + 1).min(now + u32::MAX as u64 / 2);
+        set_compare(CHANNEL_EVENT, target as u32);
+
+        // If CLO got there before the write landed, the match would only
+        // come after the 32-bit counter wraps: try again a bit later
+        now = read_counter();
+        if now < target {
+            return;
+        }
+    }
+}
+
+pub fn stop_event() {
+    EVENT_ARMED.store(false, Ordering::Release);
+}
+
+fn event_interrupt_handler() {
+    clear_match(CHANNEL_EVENT);
+
+    if EVENT_ARMED.swap(false, Ordering::AcqRel) {
+        time::queue::handle_timer_interrupt();
+    }
+}
+
+// Use the system timer as event device for the timer queue (core 0 only,
+// peripheral IRQs are not routed to the other cores)
+pub fn init_event_device() {
+    clear_match(CHANNEL_EVENT);
+    gic::register_irq_handler(IRQ_SYSTIMER_1, event_interrupt_handler);
+    gic::enable_irq(IRQ_SYSTIMER_1);
+    println!("System timer channel {} used as event device", CHANNEL_EVENT);
+}
+
+#[allow(static_mut_refs)]
+fn user_interrupt_handler() {
+    clear_match(CHANNEL_USER);
+
+    if let Some(callback) = unsafe { USER_CALLBACK.take() } {
+        callback();
+    }
+}
+
+// One-shot callback on channel 3 after the given delay (IRQ context)
+#[allow(dead_code)]
+pub fn arm_oneshot(delay: Duration, callback: fn()) {
+    let delay_us = (delay.as_micros() as u64).clamp(1, u32::MAX as u64 / 2);
+
+    unsafe { USER_CALLBACK = Some(callback) };
+    clear_match(CHANNEL_USER);
+    set_compare(CHANNEL_USER, (read_counter() + delay_us) as u32);
+}
+
+// Cross-check of the generic timer against the system timer over a
+// measurement window. Returns the drift of the generic timer in ppm
+// (positive means the generic timer runs fast).
+pub fn measure_drift(window: Duration) -> Option<i64> {
+    if !time::generic_timer_available() || time::clock_source() != time::ClockSource::GenericTimer {
+        return None;
+    }
+
+    let sys_start = read_counter();
+    let gen_start = time::read_generic_counter();
+
+    let window_us = window.as_micros() as u64;
+    while read_counter() - sys_start < window_us {
+        core::hint::spin_loop();
+    }
+
+    let gen_elapsed = time::ticks_to_nanos(time::read_generic_counter() - gen_start) as i64;
+    let sys_elapsed = ((read_counter() - sys_start) * 1000) as i64;
+    if sys_elapsed == 0 {
+        return None;
+    }
+
+    Some((gen_elapsed - sys_elapsed) * 1_000_000 / sys_elapsed)
+}
+
+pub fn init() {
+    println!("Initializing BCM2835 system timer...");
+
+    clear_match(CHANNEL_EVENT);
+    clear_match(CHANNEL_USER);
+    gic::register_irq_handler(IRQ_SYSTIMER_3, user_interrupt_handler);
+    gic::enable_irq(IRQ_SYSTIMER_3);
+
+    println!("System timer at {} us", read_counter());
+
+    if let Some(ppm) = measure_drift(Duration::from_millis(10)) {
+        println!("Generic timer drift vs system timer: {} ppm", ppm);
+    }
+}
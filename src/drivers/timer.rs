@@ -1,53 +1,217 @@
-use crate::println;
-use crate::drivers::gic;
+use crate::{ktrace, println};
+use crate::arch::exceptions;
+use crate::arch::multicore::get_core_id;
+use crate::arch::multicore::sync::IrqMutex;
+use crate::drivers::{gic, systimer};
+use crate::time::{self, queue, ClockSource, Duration, Instant};
+use core::arch::asm;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
-static mut TICK_COUNT: u64 = 0;
+// System tick period (10ms)
+pub const TICK_PERIOD: Duration = Duration::from_millis(10);
 
-// Timer interrupt handler
-#[allow(static_mut_refs)]
+// CNTP_CTL_EL0 bits
+const CNTP_CTL_ENABLE: u64 = 1 << 0;
+const CNTP_CTL_IMASK: u64 = 1 << 1;
+
+static TICK_COUNT: AtomicU64 = AtomicU64::new(0);
+
+// Tick timer state: the timer id while the tick runs, and the time (ns since
+// boot) of the last tick accounted in TICK_COUNT
+static TICK_TIMER: IrqMutex<Option<queue::TimerId>> = IrqMutex::new(None);
+static LAST_TICK_NS: AtomicU64 = AtomicU64::new(0);
+
+// Generic timer interrupt: dispatch expired timers of this core
 fn timer_interrupt_handler() {
+    queue::handle_timer_interrupt();
+}
+
+// Add the tick periods elapsed since LAST_TICK_NS to TICK_COUNT. Returns
+// the tick count before and after.
+fn account_ticks() -> (u64, u64) {
+    let period_ns = TICK_PERIOD.as_nanos() as u64;
+    let last = LAST_TICK_NS.load(Ordering::Relaxed);
+    let elapsed = queue::now_ns().saturating_sub(last) / period_ns;
+
+    let before = TICK_COUNT.fetch_add(elapsed, Ordering::Relaxed);
+    LAST_TICK_NS.store(last + elapsed * period_ns, Ordering::Relaxed);
+    (before, before + elapsed)
+}
+
+// Periodic system tick, armed in core 0's timer queue. A late interrupt
+// runs it once for several periods (the queue skips the missed ones), so
+// it counts every period elapsed, not one per call.
+fn tick_callback(_id: queue::TimerId, _data: usize) {
+    let (before, ticks) = account_ticks();
+
+    // Print tick every 100 ticks (approximately every second if 10ms ticks)
+    if ticks / 100 > before / 100 {
+        ktrace!("Timer tick: {}", ticks);
+    }
+}
+
+// Whether the calling core can program timer events. The system timer
+// fallback has a single event channel, owned by core 0.
+pub fn has_event_device() -> bool {
+    time::clock_source() != ClockSource::SystemTimer || get_core_id() == 0
+}
+
+// Program the comparator of the calling core to fire at the deadline
+pub fn program_event(deadline: Instant) {
+    if time::clock_source() == ClockSource::SystemTimer {
+        systimer::program_event(deadline.ticks());
+        return;
+    }
+
     unsafe {
-        TICK_COUNT += 1;
-        
-        // Print tick every 100 ticks (approximately every second if 10ms ticks)
-        if TICK_COUNT % 100 == 0 {
-            println!("Timer tick: {}", TICK_COUNT);
-        }
+        asm!("msr cntp_cval_el0, {}", in(reg) deadline.ticks(), options(nomem, nostack));
+        asm!("msr cntp_ctl_el0, {}", in(reg) CNTP_CTL_ENABLE, options(nomem, nostack));
+        asm!("isb", options(nomem, nostack));
     }
 }
 
+// Mask the comparator of the calling core
+pub fn stop_event() {
+    if time::clock_source() == ClockSource::SystemTimer {
+        systimer::stop_event();
+        return;
+    }
+
+    unsafe {
+        asm!("msr cntp_ctl_el0, {}", in(reg) CNTP_CTL_IMASK, options(nomem, nostack));
+        asm!("isb", options(nomem, nostack));
+    }
+}
+
+// Route the generic timer interrupts to the calling core
+pub fn init_core() {
+    if time::clock_source() == ClockSource::SystemTimer {
+        return;
+    }
+
+    stop_event();
+    gic::enable_local_irq(gic::IRQ_TIMER);
+    gic::enable_local_irq(gic::IRQ_TIMER_NS);
+}
+
 pub fn init() {
     println!("Initializing timer system...");
-    
+
     // Register our timer handler
     gic::register_timer_handler(timer_interrupt_handler);
-    
-    // Setup timer for 10ms intervals (10000 microseconds)
-    gic::setup_timer(10000);
-    
+
+    if time::clock_source() == ClockSource::SystemTimer {
+        // Generic timer unavailable: fall back to the BCM2835 system timer
+        systimer::init_event_device();
+    } else {
+        init_core();
+    }
+
+    // Setup the periodic tick for 10ms intervals
+    LAST_TICK_NS.store(queue::now_ns(), Ordering::Relaxed);
+    if !arm_tick() {
+        println!("Failed to arm system tick");
+    }
+
     println!("Timer system initialized");
 }
 
 pub fn get_tick_count() -> u64 {
-    unsafe { TICK_COUNT }
+    TICK_COUNT.load(Ordering::Relaxed)
+}
+
+fn arm_tick() -> bool {
+    let period_ns = TICK_PERIOD.as_nanos() as u64;
+    let first_ns = LAST_TICK_NS.load(Ordering::Relaxed) + period_ns;
+
+    let mut tick = TICK_TIMER.lock();
+    *tick = queue::arm_periodic(first_ns, period_ns, tick_callback, 0);
+    tick.is_some()
+}
+
+// Stop the periodic tick if it runs on the calling core (tickless idle).
+// Returns true if the tick was stopped and must be restarted on wakeup.
+pub fn tick_stop() -> bool {
+    let mut tick = TICK_TIMER.lock();
+
+    match *tick {
+        Some(id) if id.core() == get_core_id() => {
+            queue::cancel(id);
+            *tick = None;
+            true
+        }
+        _ => false,
+    }
+}
+
+// Restart the periodic tick, first accounting the ticks missed while stopped
+pub fn tick_restart() {
+    account_ticks();
+
+    if !arm_tick() {
+        println!("Failed to restart system tick");
+    }
+}
+
+#[allow(dead_code)]
+pub fn tick_running() -> bool {
+    TICK_TIMER.lock().is_some()
+}
+
+fn wake_callback(_id: queue::TimerId, data: usize) {
+    let flag = unsafe { &*(data as *const AtomicBool) };
+    flag.store(true, Ordering::Release);
+}
+
+// Sleep until the deadline, waiting for interrupts instead of spinning
+#[allow(dead_code)]
+pub fn sleep_until(deadline: Instant) {
+    let expired = AtomicBool::new(false);
+
+    if !exceptions::interrupts_enabled() {
+        time::wait_until(deadline);
+        return;
+    }
+
+    let id = queue::arm_oneshot(
+        deadline.as_nanos(),
+        wake_callback,
+        &expired as *const AtomicBool as usize,
+    );
+    let Some(id) = id else {
+        time::wait_until(deadline);
+        return;
+    };
+
+    loop {
+        // Check the flag with IRQs masked so the wakeup cannot be lost;
+        // WFI still returns on a pending (masked) interrupt
+        let daif = exceptions::save_and_disable_interrupts();
+        if expired.load(Ordering::Acquire) {
+            exceptions::restore_interrupts(daif);
+            break;
+        }
+        unsafe { asm!("wfi", options(nomem, nostack)) };
+        exceptions::restore_interrupts(daif);
+    }
+
+    // The flag lives on this stack frame: never leave the timer armed
+    queue::cancel(id);
+}
+
+#[allow(dead_code)]
+pub fn sleep(duration: Duration) {
+    sleep_until(Instant::now() + duration);
 }
 
 // Sleep for approximately the given number of ticks
 #[allow(dead_code)]
 pub fn sleep_ticks(ticks: u64) {
-    let start_tick = get_tick_count();
-    while get_tick_count() - start_tick < ticks {
-        // Wait for timer interrupts
-        core::hint::spin_loop();
-    }
+    sleep(TICK_PERIOD.saturating_mul(ticks.min(u32::MAX as u64) as u32));
 }
 
 // Sleep for approximately the given number of milliseconds
 #[allow(dead_code)]
 pub fn sleep_ms(ms: u64) {
-    // Each tick is 10ms, so divide by 10
-    let ticks = ms / 10;
-    if ticks > 0 {
-        sleep_ticks(ticks);
-    }
+    sleep(Duration::from_millis(ms));
 }
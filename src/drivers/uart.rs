@@ -1,8 +1,190 @@
+use core::arch::asm;
 use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use spin::Mutex;
+use crate::arch::exceptions;
+use crate::arch::multicore::sync::{IrqMutex, RingBuffer};
+use crate::drivers::{console, gic};
+use crate::time::{self, Duration};
 
 const UART_BASE: usize = 0x3F20_1000;
 
+// Reference clock of the PL011 (firmware default, init_uart_clock)
+const DEFAULT_CLOCK_HZ: u32 = 48_000_000;
+
+// Register offsets (in words)
+const DR: usize = 0;
+const FR: usize = 6;
+const IBRD: usize = 9;
+const FBRD: usize = 10;
+const LCR_H: usize = 11;
+const CR: usize = 12;
+const IFLS: usize = 13;
+const IMSC: usize = 14;
+const MIS: usize = 16;
+const ICR: usize = 17;
+
+// FR bits
+const FR_BUSY: u32 = 1 << 3;
+const FR_RXFE: u32 = 1 << 4;
+const FR_TXFF: u32 = 1 << 5;
+
+// LCR_H bits
+const LCR_H_PEN: u32 = 1 << 1;
+const LCR_H_EPS: u32 = 1 << 2;
+const LCR_H_STP2: u32 = 1 << 3;
+const LCR_H_FEN: u32 = 1 << 4;
+const LCR_H_WLEN_SHIFT: u32 = 5;
+
+// CR bits
+const CR_UARTEN: u32 = 1 << 0;
+const CR_TXE: u32 = 1 << 8;
+const CR_RXE: u32 = 1 << 9;
+const CR_RTSEN: u32 = 1 << 14;
+const CR_CTSEN: u32 = 1 << 15;
+
+// Interrupt bits (IMSC/RIS/MIS/ICR)
+const INT_RX: u32 = 1 << 4;
+const INT_TX: u32 = 1 << 5;
+const INT_RT: u32 = 1 << 6;
+const INT_ERRORS: u32 = 0b1111 << 7;  // FE, PE, BE, OE
+const INT_ALL: u32 = 0x7FF;
+
+// DR error bits
+const DR_ERRORS: u32 = 0b1111 << 8;
+
+// FIFO interrupt trigger levels (IFLS)
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
+pub enum FifoLevel {
+    OneEighth = 0b000,
+    OneQuarter = 0b001,
+    Half = 0b010,
+    ThreeQuarters = 0b011,
+    SevenEighths = 0b100,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum FlowControl {
+    None,
+    RtsCts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartError {
+    InvalidBaudRate,
+    InvalidDataBits,
+}
+
+// Line settings of the PL011
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartConfig {
+    pub baud_rate: u32,
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+}
+
+impl UartConfig {
+    // 115200 8N1, no flow control
+    pub const DEFAULT: UartConfig = UartConfig {
+        baud_rate: 115_200,
+        data_bits: 8,
+        parity: Parity::None,
+        stop_bits: StopBits::One,
+        flow_control: FlowControl::None,
+    };
+
+    // Integer and fractional (1/64) baud divisor for the given clock:
+    // divisor = clock / (16 * baud), rounded to the nearest 1/64
+    pub fn divisor(&self, clock_hz: u32) -> Result<(u32, u32), UartError> {
+        if self.baud_rate == 0 {
+            return Err(UartError::InvalidBaudRate);
+        }
+
+        let baud = self.baud_rate as u64;
+        let div64 = (clock_hz as u64 * 4 + baud / 2) / baud;
+        let ibrd = (div64 >> 6) as u32;
+        let fbrd = (div64 & 0x3F) as u32;
+
+        // IBRD is 16 bits wide and the divisor must be at least 1
+        if ibrd == 0 || ibrd > 0xFFFF || (ibrd == 0xFFFF && fbrd != 0) {
+            return Err(UartError::InvalidBaudRate);
+        }
+        Ok((ibrd, fbrd))
+    }
+
+    // Baud rate actually produced by the divisor
+    #[allow(dead_code)]
+    pub fn actual_baud_rate(&self, clock_hz: u32) -> Result<u32, UartError> {
+        let (ibrd, fbrd) = self.divisor(clock_hz)?;
+        let div64 = (ibrd as u64) * 64 + fbrd as u64;
+        Ok((clock_hz as u64 * 4 / div64) as u32)
+    }
+
+    fn line_control(&self) -> Result<u32, UartError> {
+        if !(5..=8).contains(&self.data_bits) {
+            return Err(UartError::InvalidDataBits);
+        }
+
+        let mut lcr_h = LCR_H_FEN | ((self.data_bits as u32 - 5) << LCR_H_WLEN_SHIFT);
+        match self.parity {
+            Parity::None => {}
+            Parity::Odd => lcr_h |= LCR_H_PEN,
+            Parity::Even => lcr_h |= LCR_H_PEN | LCR_H_EPS,
+        }
+        if self.stop_bits == StopBits::Two {
+            lcr_h |= LCR_H_STP2;
+        }
+        Ok(lcr_h)
+    }
+}
+
+impl Default for UartConfig {
+    fn default() -> Self {
+        UartConfig::DEFAULT
+    }
+}
+
+static CLOCK_HZ: AtomicU32 = AtomicU32::new(DEFAULT_CLOCK_HZ);
+static CONFIG: Mutex<UartConfig> = Mutex::new(UartConfig::DEFAULT);
+
+const RX_BUFFER_SIZE: usize = 1024;
+const TX_BUFFER_SIZE: usize = 4096;
+
+// Single-producer/single-consumer ring buffers, each side serialized:
+// RX is filled by whoever holds RX_FILLING (the IRQ handler, or a reader
+// polling with IRQs masked, on any core) and drained under RX_READER (the
+// monitor, the TTY pump and the gdb stub all read); TX is filled under
+// TX_WRITER and drained by whoever holds TX_DRAINING
+static RX_BUFFER: RingBuffer<RX_BUFFER_SIZE> = RingBuffer::new();
+static RX_FILLING: AtomicBool = AtomicBool::new(false);
+static RX_READER: IrqMutex<()> = IrqMutex::new(());
+static TX_BUFFER: RingBuffer<TX_BUFFER_SIZE> = RingBuffer::new();
+static TX_DRAINING: AtomicBool = AtomicBool::new(false);
+static TX_WRITER: IrqMutex<()> = IrqMutex::new(());
+
+// Error counters
+static RX_OVERRUNS: AtomicU32 = AtomicU32::new(0);
+static RX_ERRORS: AtomicU32 = AtomicU32::new(0);
+
 #[repr(C, align(4))]
 struct UartRegisters {
     dr: u32,
@@ -26,23 +208,40 @@ pub struct Uart;
 
 impl Uart {
     pub fn init(&self) {
+        // 115200 8N1 é sempre válido para o clock de referência
+        let _ = self.configure(&UartConfig::DEFAULT);
+    }
+    
+    // Apply line settings. The UART is disabled while the divisor and the
+    // line control are rewritten: only the byte being shifted out is waited
+    // for, so nothing else may write meanwhile (boot, or see configure()).
+    pub fn configure(&self, config: &UartConfig) -> Result<(), UartError> {
+        let (ibrd, fbrd) = config.divisor(clock_hz())?;
+        let lcr_h = config.line_control()?;
         let regs = self.registers() as *mut u32;
         
-        // Desabilita UART
-        unsafe { regs.add(12).write_volatile(0) }; // CR offset
-        
-        // Configura baud rate (115200)
-        let baud_rate_div = (48_000_000 / (16 * 115200)) as u32;
         unsafe {
-            regs.add(9).write_volatile(baud_rate_div);  // IBRD
-            regs.add(10).write_volatile(0);             // FBRD
+            // Espera o fim da transmissão em curso
+            while regs.add(FR).read_volatile() & FR_BUSY != 0 {}
+            
+            // Desabilita UART e esvazia os FIFOs
+            regs.add(CR).write_volatile(0);
+            regs.add(LCR_H).write_volatile(0);
+            
+            // IBRD/FBRD só são carregados na escrita seguinte de LCR_H
+            regs.add(IBRD).write_volatile(ibrd);
+            regs.add(FBRD).write_volatile(fbrd);
+            regs.add(LCR_H).write_volatile(lcr_h);
+            
+            // Habilita UART, TX/RX e controle de fluxo se pedido
+            let mut cr = CR_UARTEN | CR_TXE | CR_RXE;
+            if config.flow_control == FlowControl::RtsCts {
+                cr |= CR_RTSEN | CR_CTSEN;
+            }
+            regs.add(CR).write_volatile(cr);
         }
         
-        // 8 bits, sem paridade, FIFO habilitado
-        unsafe { regs.add(11).write_volatile(0b11 << 5 | 1 << 4) }; // LCR_H
-        
-        // Habilita UART, TX/RX
-        unsafe { regs.add(12).write_volatile(0b1 << 0 | 0b1 << 8 | 0b1 << 9) }; // CR
+        Ok(())
     }
     
     fn registers(&self) -> *mut UartRegisters {
@@ -59,6 +258,175 @@ impl Uart {
     }
 }
 
+fn reg(offset: usize) -> *mut u32 {
+    (UART_BASE as *mut u32).wrapping_add(offset)
+}
+
+fn read_reg(offset: usize) -> u32 {
+    unsafe { reg(offset).read_volatile() }
+}
+
+fn write_reg(offset: usize, value: u32) {
+    unsafe { reg(offset).write_volatile(value) }
+}
+
+// Move bytes from the RX FIFO into the RX ring buffer. If another core is
+// already at it, leave the FIFO to it.
+fn receive_pending() {
+    if RX_FILLING
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        return;
+    }
+
+    while read_reg(FR) & FR_RXFE == 0 {
+        let data = read_reg(DR);
+        if data & DR_ERRORS != 0 {
+            RX_ERRORS.fetch_add(1, Ordering::Relaxed);
+        }
+        if !RX_BUFFER.push(data as u8) {
+            RX_OVERRUNS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    RX_FILLING.store(false, Ordering::Release);
+}
+
+// Move bytes from the TX ring buffer into the TX FIFO. Only one drainer
+// runs at a time (the ring buffer has a single consumer).
+fn transmit_pending() {
+    loop {
+        if TX_DRAINING
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+
+        while read_reg(FR) & FR_TXFF == 0 {
+            match TX_BUFFER.pop() {
+                Some(byte) => write_reg(DR, byte as u32),
+                None => break,
+            }
+        }
+
+        // TX interrupt only while there is something left to send
+        let imsc = read_reg(IMSC);
+        if TX_BUFFER.is_empty() {
+            write_reg(IMSC, imsc & !INT_TX);
+        } else {
+            write_reg(IMSC, imsc | INT_TX);
+        }
+
+        TX_DRAINING.store(false, Ordering::Release);
+
+        // A writer may have queued data after our last check
+        if TX_BUFFER.is_empty() || read_reg(FR) & FR_TXFF != 0 {
+            return;
+        }
+    }
+}
+
+fn uart_interrupt_handler() {
+    let status = read_reg(MIS);
+
+    if status & INT_ERRORS != 0 {
+        RX_ERRORS.fetch_add(1, Ordering::Relaxed);
+    }
+    if status & (INT_RX | INT_RT) != 0 {
+        receive_pending();
+    }
+    if status & INT_TX != 0 {
+        transmit_pending();
+    }
+
+    write_reg(ICR, status);
+}
+
+// Non-blocking read of one received byte
+#[allow(dead_code)]
+pub fn read_byte() -> Option<u8> {
+    // Checked before RX_READER masks them
+    let interrupts = exceptions::interrupts_enabled();
+
+    let _reader = RX_READER.lock();
+    if let Some(byte) = RX_BUFFER.pop() {
+        return Some(byte);
+    }
+
+    // Interrupts masked: poll the FIFO directly
+    if !interrupts {
+        receive_pending();
+        return RX_BUFFER.pop();
+    }
+
+    None
+}
+
+// How long a panic waits for another core to let go of the RX side
+const PANIC_LOCK_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Called from console::panic_takeover(): the gdb stub reads after a panic,
+// and a core stopped inside read_byte would keep the RX side locked
+pub fn panic_takeover() {
+    if !time::wait_for(PANIC_LOCK_TIMEOUT, || !RX_READER.is_locked()) {
+        unsafe { RX_READER.force_unlock() };
+    }
+    if !time::wait_for(PANIC_LOCK_TIMEOUT, || !RX_FILLING.load(Ordering::Acquire)) {
+        RX_FILLING.store(false, Ordering::Release);
+    }
+}
+
+// Blocking read: sleep until the RX interrupt delivers a byte
+#[allow(dead_code)]
+pub fn read_byte_blocking() -> u8 {
+    loop {
+        if let Some(byte) = read_byte() {
+            return byte;
+        }
+
+        // Check with IRQs masked so the wakeup cannot be lost
+        let daif = exceptions::save_and_disable_interrupts();
+        if RX_BUFFER.is_empty() {
+            unsafe { asm!("wfi", options(nomem, nostack)) };
+        }
+        exceptions::restore_interrupts(daif);
+    }
+}
+
+// Non-blocking write through the TX ring buffer.
+// Returns how many bytes were queued.
+#[allow(dead_code)]
+pub fn write(data: &[u8]) -> usize {
+    let queued = {
+        let _writer = TX_WRITER.lock();
+        data.iter().take_while(|&&byte| TX_BUFFER.push(byte)).count()
+    };
+
+    transmit_pending();
+    queued
+}
+
+// Wait until everything queued by write() left the TX ring buffer
+#[allow(dead_code)]
+pub fn flush() {
+    while !TX_BUFFER.is_empty() {
+        transmit_pending();
+        core::hint::spin_loop();
+    }
+}
+
+pub fn set_fifo_thresholds(rx: FifoLevel, tx: FifoLevel) {
+    write_reg(IFLS, ((rx as u32) << 3) | tx as u32);
+}
+
+// (overruns, line errors) seen by the receiver
+#[allow(dead_code)]
+pub fn error_counts() -> (u32, u32) {
+    (RX_OVERRUNS.load(Ordering::Relaxed), RX_ERRORS.load(Ordering::Relaxed))
+}
+
 impl fmt::Write for Uart {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         for byte in s.bytes() {
@@ -73,4 +441,47 @@ pub static UART: Mutex<Uart> = Mutex::new(Uart);
 
 pub fn init() {
     UART.lock().init();
+    *CONFIG.lock() = UartConfig::DEFAULT;
+}
+
+pub fn clock_hz() -> u32 {
+    CLOCK_HZ.load(Ordering::Relaxed)
+}
+
+// Update the reference clock (e.g. as reported by the firmware) and
+// recompute the divisor for the current settings
+pub fn set_clock_hz(hz: u32) -> Result<(), UartError> {
+    let previous = CLOCK_HZ.swap(hz, Ordering::Relaxed);
+    let result = configure(config());
+    if result.is_err() {
+        CLOCK_HZ.store(previous, Ordering::Relaxed);
+    }
+    result
+}
+
+pub fn config() -> UartConfig {
+    *CONFIG.lock()
+}
+
+// Change the line settings at runtime. Console output and queued TX data
+// go out with the old settings first; the console lock keeps other cores
+// from writing to the PL011 while it is disabled.
+pub fn configure(config: UartConfig) -> Result<(), UartError> {
+    let mut current = CONFIG.lock();
+    let _console = console::CONSOLE.lock();
+    let _writer = TX_WRITER.lock();
+    flush();
+    UART.lock().configure(&config)?;
+    *current = config;
+    Ok(())
+}
+
+// Enable RX/TX interrupts (needs the interrupt controller initialized)
+pub fn init_interrupts() {
+    set_fifo_thresholds(FifoLevel::Half, FifoLevel::OneQuarter);
+    write_reg(ICR, INT_ALL);
+    write_reg(IMSC, INT_RX | INT_RT | INT_ERRORS);
+
+    gic::register_uart_handler(uart_interrupt_handler);
+    gic::enable_irq(gic::IRQ_UART);
 }
\ No newline at end of file
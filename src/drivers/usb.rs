@@ -0,0 +1,776 @@
+use core::ptr::{read_volatile, write_volatile};
+use spin::Mutex;
+use crate::drivers::mailbox::{self, PowerDevice};
+use crate::drivers::{dma, usb_keyboard};
+use crate::println;
+use crate::time::{self, Duration, Instant};
+
+// Synopsys DesignWare OTG controller in host mode. Transfers run on host
+// channel 0 in buffer DMA mode (the only mode QEMU emulates), through a
+// bounce buffer the controller reaches over the bus alias. Devices are
+// enumerated once at boot from the root port down through external hubs
+// (the Pi 3B+ has a LAN7515 hub on it, QEMU adds one for several devices);
+// low/full-speed devices behind a high-speed hub are reached with split
+// transactions. No hot-plug: devices attached later are not seen.
+const USB_BASE: usize = 0x3F98_0000;
+
+// Core global registers
+const GAHBCFG: usize = USB_BASE + 0x008;
+const GUSBCFG: usize = USB_BASE + 0x00C;
+const GRSTCTL: usize = USB_BASE + 0x010;
+const GINTSTS: usize = USB_BASE + 0x014;
+const GINTMSK: usize = USB_BASE + 0x018;
+const GRXFSIZ: usize = USB_BASE + 0x024;
+const GNPTXFSIZ: usize = USB_BASE + 0x028;
+const GSNPSID: usize = USB_BASE + 0x040;
+const GHWCFG2: usize = USB_BASE + 0x048;
+const HPTXFSIZ: usize = USB_BASE + 0x100;
+
+// Host registers
+const HFNUM: usize = USB_BASE + 0x408;
+const HPRT: usize = USB_BASE + 0x440;
+
+// Host channel 0 registers
+const HCCHAR: usize = USB_BASE + 0x500;
+const HCSPLT: usize = USB_BASE + 0x504;
+const HCINT: usize = USB_BASE + 0x508;
+const HCINTMSK: usize = USB_BASE + 0x50C;
+const HCTSIZ: usize = USB_BASE + 0x510;
+const HCDMA: usize = USB_BASE + 0x514;
+
+// "OT" in the upper half of GSNPSID
+const SNPSID_OTG: u32 = 0x4F54;
+
+const GAHBCFG_DMA_ENABLE: u32 = 1 << 5;
+const GUSBCFG_FORCE_HOST: u32 = 1 << 29;
+const GUSBCFG_FORCE_DEVICE: u32 = 1 << 30;
+const GRSTCTL_SOFT_RESET: u32 = 1 << 0;
+const GRSTCTL_RX_FLUSH: u32 = 1 << 4;
+const GRSTCTL_TX_FLUSH: u32 = 1 << 5;
+const GRSTCTL_TX_ALL: u32 = 0x10 << 6;
+const GRSTCTL_AHB_IDLE: u32 = 1 << 31;
+const GINTSTS_HOST_MODE: u32 = 1 << 0;
+
+// FIFO sizes in words
+const RX_FIFO_WORDS: u32 = 0x200;
+const NP_TX_FIFO_WORDS: u32 = 0x100;
+const P_TX_FIFO_WORDS: u32 = 0x100;
+
+const HPRT_CONNECT: u32 = 1 << 0;
+const HPRT_CONNECT_CHANGE: u32 = 1 << 1;
+const HPRT_ENABLE: u32 = 1 << 2;
+const HPRT_ENABLE_CHANGE: u32 = 1 << 3;
+const HPRT_OVERCURRENT_CHANGE: u32 = 1 << 5;
+const HPRT_RESET: u32 = 1 << 8;
+const HPRT_POWER: u32 = 1 << 12;
+const HPRT_SPEED_SHIFT: u32 = 17;
+// Write-1-to-clear bits (ENABLE included: writing 1 disables the port)
+const HPRT_W1C: u32 = HPRT_CONNECT_CHANGE | HPRT_ENABLE | HPRT_ENABLE_CHANGE | HPRT_OVERCURRENT_CHANGE;
+
+const HCCHAR_DIRECTION_SHIFT: u32 = 15;
+const HCCHAR_LOW_SPEED: u32 = 1 << 17;
+const HCCHAR_TYPE_SHIFT: u32 = 18;
+const HCCHAR_ONE_PACKET_PER_FRAME: u32 = 1 << 20;
+const HCCHAR_ADDRESS_SHIFT: u32 = 22;
+const HCCHAR_ODD_FRAME: u32 = 1 << 29;
+const HCCHAR_DISABLE: u32 = 1 << 30;
+const HCCHAR_ENABLE: u32 = 1 << 31;
+
+const HCSPLT_HUB_SHIFT: u32 = 7;
+const HCSPLT_ALL: u32 = 3 << 14;
+const HCSPLT_COMPLETE: u32 = 1 << 16;
+const HCSPLT_ENABLE: u32 = 1 << 31;
+
+const HCINT_COMPLETE: u32 = 1 << 0;
+const HCINT_HALTED: u32 = 1 << 1;
+const HCINT_AHB_ERROR: u32 = 1 << 2;
+const HCINT_STALL: u32 = 1 << 3;
+const HCINT_NAK: u32 = 1 << 4;
+const HCINT_ACK: u32 = 1 << 5;
+const HCINT_NYET: u32 = 1 << 6;
+const HCINT_TRANSACTION_ERROR: u32 = 1 << 7;
+const HCINT_BABBLE: u32 = 1 << 8;
+const HCINT_FRAME_OVERRUN: u32 = 1 << 9;
+const HCINT_TOGGLE_ERROR: u32 = 1 << 10;
+const HCINT_ALL: u32 = 0x7FF;
+const HCINT_ERRORS: u32 =
+    HCINT_AHB_ERROR | HCINT_TRANSACTION_ERROR | HCINT_BABBLE | HCINT_FRAME_OVERRUN | HCINT_TOGGLE_ERROR;
+
+const HCTSIZ_SIZE_MASK: u32 = 0x7FFFF;
+const HCTSIZ_PACKETS_SHIFT: u32 = 19;
+const HCTSIZ_PACKETS_MASK: u32 = 0x3FF;
+const HCTSIZ_PID_SHIFT: u32 = 29;
+
+// Data PIDs as encoded in HCTSIZ
+pub const PID_DATA0: u32 = 0;
+const PID_DATA1: u32 = 2;
+const PID_SETUP: u32 = 3;
+
+// Standard requests and descriptor types
+const REQUEST_GET_STATUS: u8 = 0;
+const REQUEST_CLEAR_FEATURE: u8 = 1;
+const REQUEST_SET_FEATURE: u8 = 3;
+const REQUEST_SET_ADDRESS: u8 = 5;
+const REQUEST_GET_DESCRIPTOR: u8 = 6;
+const REQUEST_SET_CONFIGURATION: u8 = 9;
+
+const DESCRIPTOR_DEVICE: u8 = 1;
+const DESCRIPTOR_CONFIGURATION: u8 = 2;
+pub const DESCRIPTOR_INTERFACE: u8 = 4;
+pub const DESCRIPTOR_ENDPOINT: u8 = 5;
+const DESCRIPTOR_HUB: u8 = 0x29;
+
+// bmRequestType values
+const TYPE_STANDARD_IN: u8 = 0x80;
+const TYPE_STANDARD_OUT: u8 = 0x00;
+const TYPE_HUB_IN: u8 = 0xA0;
+const TYPE_PORT_IN: u8 = 0xA3;
+const TYPE_PORT_OUT: u8 = 0x23;
+pub const TYPE_INTERFACE_OUT: u8 = 0x21;
+
+const CLASS_HUB: u8 = 9;
+
+// Hub port features and status bits
+const PORT_RESET_FEATURE: u16 = 4;
+const PORT_POWER_FEATURE: u16 = 8;
+const PORT_CONNECTION_CHANGE_FEATURE: u16 = 16;
+const PORT_RESET_CHANGE_FEATURE: u16 = 20;
+const PORT_STATUS_CONNECTION: u16 = 1 << 0;
+const PORT_STATUS_ENABLE: u16 = 1 << 1;
+const PORT_STATUS_RESET: u16 = 1 << 4;
+const PORT_STATUS_LOW_SPEED: u16 = 1 << 9;
+const PORT_STATUS_HIGH_SPEED: u16 = 1 << 10;
+
+const MAX_DEVICES: usize = 8;
+const MAX_HUB_DEPTH: u8 = 5;
+const BUFFER_SIZE: usize = 512;
+const CONFIG_SIZE: usize = 256;
+
+const RESET_TIMEOUT: Duration = Duration::from_millis(100);
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+const CHANNEL_TIMEOUT: Duration = Duration::from_millis(100);
+const CONTROL_TIMEOUT: Duration = Duration::from_millis(500);
+const PORT_RESET_TIMEOUT: Duration = Duration::from_millis(500);
+
+// Delays from the USB 2.0 specification (ms)
+const HOST_MODE_DELAY_MS: u64 = 50;
+const DEBOUNCE_MS: u64 = 100;
+const ROOT_RESET_MS: u64 = 60;
+const RESET_RECOVERY_MS: u64 = 20;
+const SET_ADDRESS_MS: u64 = 2;
+const PORT_POLL_MS: u64 = 10;
+
+// Transaction errors retried before giving up, complete splits retried
+// while the hub answers NYET
+const MAX_ERRORS: u32 = 3;
+const MAX_NYET: u32 = 200;
+const MICROFRAME_US: u64 = 125;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsbError {
+    NoController,
+    NotConnected,
+    Timeout,
+    Stall,
+    // No data (interrupt endpoints)
+    Nak,
+    // Channel error bits (HCINT)
+    Transaction(u32),
+    BadDescriptor,
+    TooManyDevices,
+    // Transfer larger than the bounce buffer
+    TooLarge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    High,
+    Full,
+    Low,
+}
+
+impl Speed {
+    pub fn name(self) -> &'static str {
+        match self {
+            Speed::High => "high",
+            Speed::Full => "full",
+            Speed::Low => "low",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointType {
+    Control = 0,
+    Interrupt = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Out = 0,
+    In = 1,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Endpoint {
+    pub number: u8,
+    pub kind: EndpointType,
+    pub max_packet: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Device {
+    pub address: u8,
+    pub speed: Speed,
+    // Hub the device is plugged into (0 for the root port) and its port
+    pub parent: u8,
+    pub port: u8,
+    // Hub address and port doing the transaction translation, for
+    // low/full-speed devices below a high-speed hub
+    translator: Option<(u8, u8)>,
+    max_packet0: u16,
+    pub vendor: u16,
+    pub product: u16,
+    // Device class, or the first interface's class when that is 0
+    pub class: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Setup {
+    pub request_type: u8,
+    pub request: u8,
+    pub value: u16,
+    pub index: u16,
+    pub length: u16,
+}
+
+impl Setup {
+    pub const fn new(request_type: u8, request: u8, value: u16, index: u16, length: u16) -> Self {
+        Setup { request_type, request, value, index, length }
+    }
+
+    fn bytes(&self) -> [u8; 8] {
+        let [value_low, value_high] = self.value.to_le_bytes();
+        let [index_low, index_high] = self.index.to_le_bytes();
+        let [length_low, length_high] = self.length.to_le_bytes();
+        [self.request_type, self.request, value_low, value_high, index_low, index_high, length_low, length_high]
+    }
+}
+
+// Walks the descriptors of a configuration, by bLength
+pub struct Descriptors<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Descriptors<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let length = *self.data.first()? as usize;
+        if length < 2 || length > self.data.len() {
+            return None;
+        }
+        let (descriptor, rest) = self.data.split_at(length);
+        self.data = rest;
+        Some(descriptor)
+    }
+}
+
+pub fn descriptors(config: &[u8]) -> Descriptors<'_> {
+    Descriptors { data: config }
+}
+
+fn le16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read(register: usize) -> u32 {
+    unsafe { read_volatile(register as *const u32) }
+}
+
+fn write(register: usize, value: u32) {
+    unsafe { write_volatile(register as *mut u32, value) }
+}
+
+fn barrier() {
+    unsafe { core::arch::asm!("dsb sy", options(nostack)) };
+}
+
+// DMA target for every transfer; the controller needs word alignment
+#[repr(C, align(64))]
+struct BounceBuffer([u8; BUFFER_SIZE]);
+
+pub struct Host {
+    buffer: BounceBuffer,
+    devices: [Option<Device>; MAX_DEVICES],
+}
+
+static HOST: Mutex<Host> = Mutex::new(Host {
+    buffer: BounceBuffer([0; BUFFER_SIZE]),
+    devices: [None; MAX_DEVICES],
+});
+
+fn characteristics(device: &Device, endpoint: &Endpoint, direction: Direction) -> u32 {
+    let mut value = endpoint.max_packet as u32 & 0x7FF
+        | (endpoint.number as u32 & 0xF) << 11
+        | (direction as u32) << HCCHAR_DIRECTION_SHIFT
+        | (endpoint.kind as u32) << HCCHAR_TYPE_SHIFT
+        | HCCHAR_ONE_PACKET_PER_FRAME
+        | (device.address as u32) << HCCHAR_ADDRESS_SHIFT;
+    if device.speed == Speed::Low {
+        value |= HCCHAR_LOW_SPEED;
+    }
+    value
+}
+
+fn next_pid(pid: u32) -> u32 {
+    if pid == PID_DATA0 { PID_DATA1 } else { PID_DATA0 }
+}
+
+impl Host {
+    // Program channel 0, enable it and wait for the halt. Returns HCINT and
+    // what is left in HCTSIZ.
+    fn run_channel(&mut self, characteristics: u32, split: u32, size: usize, packets: usize, pid: u32, offset: usize) -> Result<(u32, u32), UsbError> {
+        let buffer = self.buffer.0[offset..].as_ptr() as usize;
+        let address = dma::ram_bus_address(buffer).map_err(|_| UsbError::TooLarge)?;
+
+        write(HCINT, u32::MAX);
+        write(HCINTMSK, HCINT_ALL);
+        write(HCSPLT, split);
+        write(HCTSIZ, size as u32 | (packets as u32) << HCTSIZ_PACKETS_SHIFT | pid << HCTSIZ_PID_SHIFT);
+        write(HCDMA, address);
+        barrier();
+
+        // Periodic transfers go out in the next (micro)frame
+        let mut value = characteristics | HCCHAR_ENABLE;
+        let periodic = characteristics >> HCCHAR_TYPE_SHIFT & 3 == EndpointType::Interrupt as u32;
+        if periodic && read(HFNUM) & 1 == 0 {
+            value |= HCCHAR_ODD_FRAME;
+        }
+        write(HCCHAR, value);
+
+        if !time::wait_for(CHANNEL_TIMEOUT, || read(HCINT) & HCINT_HALTED != 0) {
+            write(HCCHAR, read(HCCHAR) | HCCHAR_DISABLE | HCCHAR_ENABLE);
+            time::wait_for(CHANNEL_TIMEOUT, || read(HCINT) & HCINT_HALTED != 0);
+            return Err(UsbError::Timeout);
+        }
+        barrier();
+        Ok((read(HCINT), read(HCTSIZ)))
+    }
+
+    // One packet through a hub's transaction translator: start split, then
+    // complete split until the hub has the answer
+    fn run_split(&mut self, characteristics: u32, translator: (u8, u8), size: usize, pid: u32, offset: usize) -> Result<(u32, u32), UsbError> {
+        let (hub, port) = translator;
+        let split = HCSPLT_ENABLE | HCSPLT_ALL | (hub as u32) << HCSPLT_HUB_SHIFT | port as u32;
+
+        let (status, remaining) = self.run_channel(characteristics, split, size, 1, pid, offset)?;
+        if status & HCINT_ACK == 0 {
+            return Ok((status, remaining));
+        }
+
+        for _ in 0..MAX_NYET {
+            let (status, remaining) = self.run_channel(characteristics, split | HCSPLT_COMPLETE, size, 1, pid, offset)?;
+            if status & HCINT_NYET == 0 {
+                return Ok((status, remaining));
+            }
+            time::udelay(MICROFRAME_US);
+        }
+        Err(UsbError::Timeout)
+    }
+
+    // Move `length` bytes between an endpoint and the bounce buffer. NAKs
+    // are retried until `deadline`; without one a NAK ends the transfer.
+    fn transfer(&mut self, device: &Device, endpoint: &Endpoint, direction: Direction, pid: &mut u32, length: usize, deadline: Option<Instant>) -> Result<usize, UsbError> {
+        if length > BUFFER_SIZE {
+            return Err(UsbError::TooLarge);
+        }
+
+        let characteristics = characteristics(device, endpoint, direction);
+        let max_packet = endpoint.max_packet.max(1) as usize;
+        let mut done = 0;
+        let mut errors = 0;
+
+        loop {
+            // Split transactions carry one packet at a time
+            let remaining = length - done;
+            let size = match device.translator {
+                Some(_) => remaining.min(max_packet),
+                None => remaining,
+            };
+            let packets = size.div_ceil(max_packet).max(1);
+
+            let (status, left) = match device.translator {
+                Some(translator) => self.run_split(characteristics, translator, size, *pid, done)?,
+                None => self.run_channel(characteristics, 0, size, packets, *pid, done)?,
+            };
+
+            let moved = match direction {
+                Direction::In => size - (left & HCTSIZ_SIZE_MASK) as usize,
+                Direction::Out => {
+                    let sent = packets - (left >> HCTSIZ_PACKETS_SHIFT & HCTSIZ_PACKETS_MASK) as usize;
+                    (sent * max_packet).min(size)
+                }
+            };
+            done += moved;
+            // The controller tracks the toggle itself, except across split packets
+            if device.translator.is_none() {
+                *pid = left >> HCTSIZ_PID_SHIFT & 3;
+            }
+
+            if status & HCINT_STALL != 0 {
+                return Err(UsbError::Stall);
+            }
+            if status & HCINT_ERRORS != 0 {
+                errors += 1;
+                if status & HCINT_TRANSACTION_ERROR != 0 && errors < MAX_ERRORS {
+                    continue;
+                }
+                return Err(UsbError::Transaction(status & HCINT_ERRORS));
+            }
+
+            if status & HCINT_COMPLETE != 0 {
+                if device.translator.is_some() {
+                    *pid = next_pid(*pid);
+                }
+                let short = direction == Direction::In && moved < size;
+                if device.translator.is_none() || short || done >= length {
+                    return Ok(done);
+                }
+                continue;
+            }
+
+            if status & HCINT_NAK != 0 {
+                match deadline {
+                    Some(deadline) if !deadline.has_passed() => continue,
+                    _ => return Err(UsbError::Nak),
+                }
+            }
+            return Err(UsbError::Transaction(status));
+        }
+    }
+
+    // Control transfer on endpoint 0. `data` is sent or filled depending on
+    // the direction bit of the request; returns the bytes received.
+    pub fn control(&mut self, device: &Device, setup: Setup, data: &mut [u8]) -> Result<usize, UsbError> {
+        let endpoint = Endpoint { number: 0, kind: EndpointType::Control, max_packet: device.max_packet0 };
+        let deadline = Some(Instant::now() + CONTROL_TIMEOUT);
+        let input = setup.request_type & 0x80 != 0;
+        let length = (setup.length as usize).min(data.len());
+
+        self.buffer.0[..8].copy_from_slice(&setup.bytes());
+        let mut pid = PID_SETUP;
+        self.transfer(device, &endpoint, Direction::Out, &mut pid, 8, deadline)?;
+
+        let mut received = 0;
+        if length > 0 {
+            pid = PID_DATA1;
+            if input {
+                received = self.transfer(device, &endpoint, Direction::In, &mut pid, length, deadline)?;
+                data[..received].copy_from_slice(&self.buffer.0[..received]);
+            } else {
+                self.buffer.0[..length].copy_from_slice(&data[..length]);
+                self.transfer(device, &endpoint, Direction::Out, &mut pid, length, deadline)?;
+            }
+        }
+
+        // Status stage: zero-length DATA1 in the other direction
+        let mut pid = PID_DATA1;
+        let status = if input && length > 0 { Direction::Out } else { Direction::In };
+        self.transfer(device, &endpoint, status, &mut pid, 0, deadline)?;
+        Ok(received)
+    }
+
+    // Poll an interrupt IN endpoint once: Err(Nak) when it has nothing new
+    pub fn interrupt_in(&mut self, device: &Device, endpoint: &Endpoint, pid: &mut u32, data: &mut [u8]) -> Result<usize, UsbError> {
+        let length = data.len().min(BUFFER_SIZE);
+        let received = self.transfer(device, endpoint, Direction::In, pid, length, None)?;
+        data[..received].copy_from_slice(&self.buffer.0[..received]);
+        Ok(received)
+    }
+
+    fn get_descriptor(&mut self, device: &Device, kind: u8, data: &mut [u8]) -> Result<usize, UsbError> {
+        let setup = Setup::new(TYPE_STANDARD_IN, REQUEST_GET_DESCRIPTOR, (kind as u16) << 8, 0, data.len() as u16);
+        self.control(device, setup, data)
+    }
+
+    // Address, describe and configure a new device, then hand it to the
+    // hub or keyboard driver
+    fn enumerate(&mut self, parent: u8, port: u8, speed: Speed, translator: Option<(u8, u8)>, depth: u8) -> Result<(), UsbError> {
+        let mut device = Device {
+            address: 0,
+            speed,
+            parent,
+            port,
+            translator,
+            max_packet0: if speed == Speed::High { 64 } else { 8 },
+            vendor: 0,
+            product: 0,
+            class: 0,
+        };
+
+        // The first 8 bytes carry bMaxPacketSize0
+        let mut descriptor = [0u8; 18];
+        self.get_descriptor(&device, DESCRIPTOR_DEVICE, &mut descriptor[..8])?;
+        device.max_packet0 = (descriptor[7] as u16).max(8);
+
+        let slot = self.devices.iter().position(Option::is_none).ok_or(UsbError::TooManyDevices)?;
+        let address = slot as u8 + 1;
+        // Reserved from here on, also if a later step fails: the device may
+        // answer on this address already, so it must not be handed out again
+        self.devices[slot] = Some(Device { address, ..device });
+        let setup = Setup::new(TYPE_STANDARD_OUT, REQUEST_SET_ADDRESS, address as u16, 0, 0);
+        self.control(&device, setup, &mut [])?;
+        time::mdelay(SET_ADDRESS_MS);
+        device.address = address;
+
+        if self.get_descriptor(&device, DESCRIPTOR_DEVICE, &mut descriptor)? < descriptor.len() {
+            return Err(UsbError::BadDescriptor);
+        }
+        device.class = descriptor[4];
+        device.vendor = le16(&descriptor, 8);
+        device.product = le16(&descriptor, 10);
+
+        let mut config = [0u8; CONFIG_SIZE];
+        if self.get_descriptor(&device, DESCRIPTOR_CONFIGURATION, &mut config[..9])? < 9 {
+            return Err(UsbError::BadDescriptor);
+        }
+        let total = (le16(&config, 2) as usize).clamp(9, CONFIG_SIZE);
+        let length = self.get_descriptor(&device, DESCRIPTOR_CONFIGURATION, &mut config[..total])?;
+        let config = &config[..length];
+
+        let setup = Setup::new(TYPE_STANDARD_OUT, REQUEST_SET_CONFIGURATION, config[5] as u16, 0, 0);
+        self.control(&device, setup, &mut [])?;
+
+        if device.class == 0 {
+            let interface = descriptors(config).find(|d| d[1] == DESCRIPTOR_INTERFACE && d.len() >= 9);
+            device.class = interface.map_or(0, |interface| interface[5]);
+        }
+        self.devices[slot] = Some(device);
+
+        println!(
+            "USB: device {} on {}:{}, {} speed, {:04x}:{:04x}, class 0x{:02x}",
+            device.address,
+            parent,
+            port,
+            speed.name(),
+            device.vendor,
+            device.product,
+            device.class
+        );
+
+        if device.class == CLASS_HUB {
+            self.probe_hub(&device, depth);
+        } else {
+            usb_keyboard::probe(self, &device, config);
+        }
+        Ok(())
+    }
+
+    fn port_request(&mut self, hub: &Device, request: u8, feature: u16, port: u8) -> Result<(), UsbError> {
+        let setup = Setup::new(TYPE_PORT_OUT, request, feature, port as u16, 0);
+        self.control(hub, setup, &mut []).map(|_| ())
+    }
+
+    // (wPortStatus, wPortChange)
+    fn port_status(&mut self, hub: &Device, port: u8) -> Result<(u16, u16), UsbError> {
+        let mut status = [0u8; 4];
+        let setup = Setup::new(TYPE_PORT_IN, REQUEST_GET_STATUS, 0, port as u16, 4);
+        if self.control(hub, setup, &mut status)? < 4 {
+            return Err(UsbError::BadDescriptor);
+        }
+        Ok((le16(&status, 0), le16(&status, 2)))
+    }
+
+    fn probe_hub(&mut self, hub: &Device, depth: u8) {
+        if depth >= MAX_HUB_DEPTH {
+            println!("USB: hub {} nested too deep, ignored", hub.address);
+            return;
+        }
+
+        let mut descriptor = [0u8; 16];
+        let setup = Setup::new(TYPE_HUB_IN, REQUEST_GET_DESCRIPTOR, (DESCRIPTOR_HUB as u16) << 8, 0, 16);
+        let ports = match self.control(hub, setup, &mut descriptor) {
+            Ok(length) if length >= 7 => descriptor[2],
+            Ok(_) => return println!("USB: hub {}: short hub descriptor", hub.address),
+            Err(error) => return println!("USB: hub {}: no hub descriptor ({:?})", hub.address, error),
+        };
+        // bPwrOn2PwrGood is in 2 ms units
+        let power_delay = descriptor[5] as u64 * 2;
+        println!("USB: hub {} with {} ports", hub.address, ports);
+
+        for port in 1..=ports {
+            let _ = self.port_request(hub, REQUEST_SET_FEATURE, PORT_POWER_FEATURE, port);
+        }
+        time::mdelay(power_delay.max(DEBOUNCE_MS));
+
+        for port in 1..=ports {
+            if let Err(error) = self.probe_hub_port(hub, port, depth) {
+                println!("USB: hub {} port {}: {:?}", hub.address, port, error);
+            }
+        }
+    }
+
+    fn probe_hub_port(&mut self, hub: &Device, port: u8, depth: u8) -> Result<(), UsbError> {
+        let (status, _) = self.port_status(hub, port)?;
+        if status & PORT_STATUS_CONNECTION == 0 {
+            return Ok(());
+        }
+        self.port_request(hub, REQUEST_CLEAR_FEATURE, PORT_CONNECTION_CHANGE_FEATURE, port)?;
+        self.port_request(hub, REQUEST_SET_FEATURE, PORT_RESET_FEATURE, port)?;
+
+        let deadline = Instant::now() + PORT_RESET_TIMEOUT;
+        let status = loop {
+            let (status, _) = self.port_status(hub, port)?;
+            if status & PORT_STATUS_RESET == 0 && status & PORT_STATUS_ENABLE != 0 {
+                break status;
+            }
+            if deadline.has_passed() {
+                return Err(UsbError::Timeout);
+            }
+            time::mdelay(PORT_POLL_MS);
+        };
+        self.port_request(hub, REQUEST_CLEAR_FEATURE, PORT_RESET_CHANGE_FEATURE, port)?;
+        time::mdelay(RESET_RECOVERY_MS);
+
+        let speed = if status & PORT_STATUS_LOW_SPEED != 0 {
+            Speed::Low
+        } else if status & PORT_STATUS_HIGH_SPEED != 0 {
+            Speed::High
+        } else {
+            Speed::Full
+        };
+
+        // The nearest high-speed hub above a slower device translates for it
+        let translator = match (speed, hub.speed) {
+            (Speed::High, _) => None,
+            (_, Speed::High) => Some((hub.address, port)),
+            _ => hub.translator,
+        };
+        self.enumerate(hub.address, port, speed, translator, depth + 1)
+    }
+}
+
+fn write_port(bits: u32) {
+    write(HPRT, bits & !HPRT_W1C);
+}
+
+fn core_reset() -> Result<(), UsbError> {
+    if !time::wait_for(RESET_TIMEOUT, || read(GRSTCTL) & GRSTCTL_AHB_IDLE != 0) {
+        return Err(UsbError::Timeout);
+    }
+    write(GRSTCTL, GRSTCTL_SOFT_RESET);
+    if !time::wait_for(RESET_TIMEOUT, || read(GRSTCTL) & GRSTCTL_SOFT_RESET == 0) {
+        return Err(UsbError::Timeout);
+    }
+    time::mdelay(HOST_MODE_DELAY_MS);
+    Ok(())
+}
+
+fn flush_fifos() {
+    write(GRSTCTL, GRSTCTL_TX_FLUSH | GRSTCTL_TX_ALL);
+    time::wait_for(RESET_TIMEOUT, || read(GRSTCTL) & GRSTCTL_TX_FLUSH == 0);
+    write(GRSTCTL, GRSTCTL_RX_FLUSH);
+    time::wait_for(RESET_TIMEOUT, || read(GRSTCTL) & GRSTCTL_RX_FLUSH == 0);
+}
+
+// Reset the core into host mode with DMA, interrupts masked (polled)
+fn start_host() -> Result<(), UsbError> {
+    core_reset()?;
+
+    let config = read(GUSBCFG) & !GUSBCFG_FORCE_DEVICE;
+    write(GUSBCFG, config | GUSBCFG_FORCE_HOST);
+    time::mdelay(HOST_MODE_DELAY_MS);
+    if read(GINTSTS) & GINTSTS_HOST_MODE == 0 {
+        return Err(UsbError::NoController);
+    }
+
+    write(GINTMSK, 0);
+    write(GAHBCFG, GAHBCFG_DMA_ENABLE);
+
+    write(GRXFSIZ, RX_FIFO_WORDS);
+    write(GNPTXFSIZ, NP_TX_FIFO_WORDS << 16 | RX_FIFO_WORDS);
+    write(HPTXFSIZ, P_TX_FIFO_WORDS << 16 | (RX_FIFO_WORDS + NP_TX_FIFO_WORDS));
+    flush_fifos();
+    Ok(())
+}
+
+// Power and reset the root port; returns the speed of what is attached
+fn reset_root_port() -> Result<Speed, UsbError> {
+    write_port(read(HPRT) | HPRT_POWER);
+    if !time::wait_for(CONNECT_TIMEOUT, || read(HPRT) & HPRT_CONNECT != 0) {
+        return Err(UsbError::NotConnected);
+    }
+    time::mdelay(DEBOUNCE_MS);
+
+    write_port(read(HPRT) | HPRT_RESET);
+    time::mdelay(ROOT_RESET_MS);
+    write_port(read(HPRT) & !HPRT_RESET);
+    time::mdelay(RESET_RECOVERY_MS);
+
+    if !time::wait_for(CONNECT_TIMEOUT, || read(HPRT) & HPRT_ENABLE != 0) {
+        return Err(UsbError::Timeout);
+    }
+
+    // Acknowledge the change bits, leaving the port enabled
+    let status = read(HPRT);
+    write(HPRT, status & !HPRT_ENABLE);
+
+    Ok(match status >> HPRT_SPEED_SHIFT & 3 {
+        0 => Speed::High,
+        1 => Speed::Full,
+        _ => Speed::Low,
+    })
+}
+
+// Controller access for class drivers polling from drivers::poll: None
+// if the controller is busy (enumeration, or another core)
+pub fn with_host<R>(f: impl FnOnce(&mut Host) -> R) -> Option<R> {
+    HOST.try_lock().map(|mut host| f(&mut host))
+}
+
+pub fn for_each_device(mut f: impl FnMut(&Device)) {
+    let devices = HOST.lock().devices;
+    for device in devices.iter().flatten() {
+        f(device);
+    }
+}
+
+pub fn init() {
+    if let Err(error) = mailbox::set_power_state(PowerDevice::UsbHcd, true, true) {
+        println!("USB: cannot power the controller ({:?})", error);
+        return;
+    }
+
+    let id = read(GSNPSID);
+    if id >> 16 != SNPSID_OTG {
+        println!("USB: no DWC OTG controller (id 0x{:08x})", id);
+        return;
+    }
+
+    let speed = match start_host().and_then(|()| reset_root_port()) {
+        Ok(speed) => speed,
+        Err(UsbError::NotConnected) => {
+            println!("USB: DWC OTG {:x}.{:03x}, nothing on the root port", id >> 12 & 0xF, id & 0xFFF);
+            return;
+        }
+        Err(error) => {
+            println!("USB: controller start failed ({:?})", error);
+            return;
+        }
+    };
+
+    let channels = (read(GHWCFG2) >> 14 & 0xF) + 1;
+    println!(
+        "USB: DWC OTG {:x}.{:03x}, {} channels, root port {} speed",
+        id >> 12 & 0xF,
+        id & 0xFFF,
+        channels,
+        speed.name()
+    );
+
+    let mut host = HOST.lock();
+    if let Err(error) = host.enumerate(0, 1, speed, None, 0) {
+        println!("USB: enumeration failed ({:?})", error);
+    }
+}
@@ -0,0 +1,288 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use crate::drivers::console;
+use crate::drivers::usb::{self, Device, Endpoint, EndpointType, Host, Setup, UsbError};
+use crate::println;
+use crate::time::{queue, Duration, Instant};
+
+// HID keyboard in boot protocol: 8-byte reports (modifiers, reserved, six
+// key codes) read from its interrupt IN endpoint and turned into console
+// input with a US layout. Navigation keys become the ANSI sequences a
+// serial terminal would send. One keyboard is supported. A periodic timer
+// only flags the poll: transfers busy-wait on the controller, so they run
+// in task context (drivers::poll).
+
+const CLASS_HID: u8 = 3;
+const SUBCLASS_BOOT: u8 = 1;
+const PROTOCOL_KEYBOARD: u8 = 1;
+
+const HID_SET_IDLE: u8 = 0x0A;
+const HID_SET_PROTOCOL: u8 = 0x0B;
+const BOOT_PROTOCOL: u16 = 0;
+
+const REPORT_SIZE: usize = 8;
+
+const POLL_PERIOD: Duration = Duration::from_millis(10);
+
+// At most one transfer error message per interval; the rest are counted
+const ERROR_INTERVAL: Duration = Duration::from_secs(5);
+
+// Typematic repeat of the last key pressed
+const REPEAT_DELAY: Duration = Duration::from_millis(500);
+const REPEAT_INTERVAL: Duration = Duration::from_millis(33);
+
+// Modifier bits (left and right)
+const MOD_CTRL: u8 = 0x11;
+const MOD_SHIFT: u8 = 0x22;
+const MOD_ALT: u8 = 0x44;
+
+// Key codes (HID usage page 7)
+const KEY_ERROR_ROLLOVER: u8 = 0x01;
+const KEY_FIRST: u8 = 0x04;
+const KEY_CAPS_LOCK: u8 = 0x39;
+const KEY_KEYPAD_FIRST: u8 = 0x54;
+
+const ESC: u8 = 0x1B;
+
+// Usages 0x04-0x38: letters, digits, Enter, Esc, Backspace (DEL, as
+// terminals send it), Tab, space and punctuation
+const NORMAL: &[u8; 53] = b"abcdefghijklmnopqrstuvwxyz1234567890\r\x1b\x7f\t -=[]\\#;'`,./";
+const SHIFTED: &[u8; 53] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ!@#$%^&*()\r\x1b\x7f\t _+{}|~:\"~<>?";
+
+// Usages 0x54-0x63 (keypad, NumLock assumed on)
+const KEYPAD: &[u8; 16] = b"/*-+\r1234567890.";
+
+fn sequence(key: u8) -> Option<&'static [u8]> {
+    let sequence: &[u8] = match key {
+        0x49 => b"\x1b[2~",
+        0x4A => b"\x1b[H",
+        0x4B => b"\x1b[5~",
+        0x4C => b"\x1b[3~",
+        0x4D => b"\x1b[F",
+        0x4E => b"\x1b[6~",
+        0x4F => b"\x1b[C",
+        0x50 => b"\x1b[D",
+        0x51 => b"\x1b[B",
+        0x52 => b"\x1b[A",
+        _ => return None,
+    };
+    Some(sequence)
+}
+
+// Bytes produced by one poll
+struct Output {
+    data: [u8; 32],
+    len: usize,
+}
+
+impl Output {
+    fn push(&mut self, bytes: &[u8]) {
+        let count = bytes.len().min(self.data.len() - self.len);
+        self.data[self.len..self.len + count].copy_from_slice(&bytes[..count]);
+        self.len += count;
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Repeat {
+    key: u8,
+    next: Instant,
+}
+
+struct Keyboard {
+    device: Device,
+    endpoint: Endpoint,
+    pid: u32,
+    previous: [u8; REPORT_SIZE],
+    caps_lock: bool,
+    repeat: Option<Repeat>,
+    // Transfer errors not printed yet, and when the next may be
+    errors: u32,
+    next_error: Instant,
+}
+
+impl Keyboard {
+    fn translate(&self, key: u8, modifiers: u8, output: &mut Output) {
+        if let Some(sequence) = sequence(key) {
+            output.push(sequence);
+            return;
+        }
+
+        let byte = match key {
+            KEY_FIRST..=0x38 => {
+                let index = (key - KEY_FIRST) as usize;
+                let letter = key <= 0x1D;
+                let shift = modifiers & MOD_SHIFT != 0;
+                if shift != (letter && self.caps_lock) { SHIFTED[index] } else { NORMAL[index] }
+            }
+            KEY_KEYPAD_FIRST..=0x63 => KEYPAD[(key - KEY_KEYPAD_FIRST) as usize],
+            _ => return,
+        };
+
+        // Ctrl+letter (and @ [ \ ] ^ _) gives the control code
+        let byte = if modifiers & MOD_CTRL != 0 && (b'@'..=b'~').contains(&byte) && byte != b'`' {
+            byte & 0x1F
+        } else {
+            byte
+        };
+        if modifiers & MOD_ALT != 0 {
+            output.push(&[ESC]);
+        }
+        output.push(&[byte]);
+    }
+
+    fn report(&mut self, report: &[u8; REPORT_SIZE], output: &mut Output) {
+        let keys = &report[2..];
+        // Too many keys down: the report carries no key state
+        if keys.contains(&KEY_ERROR_ROLLOVER) {
+            return;
+        }
+
+        for &key in keys.iter().filter(|&&key| key >= KEY_FIRST) {
+            if self.previous[2..].contains(&key) {
+                continue;
+            }
+            if key == KEY_CAPS_LOCK {
+                self.caps_lock = !self.caps_lock;
+                continue;
+            }
+            self.translate(key, report[0], output);
+            self.repeat = Some(Repeat { key, next: Instant::now() + REPEAT_DELAY });
+        }
+
+        if self.repeat.is_some_and(|repeat| !keys.contains(&repeat.key)) {
+            self.repeat = None;
+        }
+        self.previous = *report;
+    }
+
+    fn repeat(&mut self, output: &mut Output) {
+        let Some(repeat) = self.repeat else {
+            return;
+        };
+        if repeat.next.has_passed() {
+            self.translate(repeat.key, self.previous[0], output);
+            self.repeat = Some(Repeat { next: Instant::now() + REPEAT_INTERVAL, ..repeat });
+        }
+    }
+
+    fn error(&mut self, error: UsbError) {
+        self.errors += 1;
+        if self.next_error.has_passed() {
+            println!("USB keyboard: {:?} ({} errors)", error, self.errors);
+            self.errors = 0;
+            self.next_error = Instant::now() + ERROR_INTERVAL;
+        }
+    }
+}
+
+// Never taken from IRQ context: a plain lock keeps IRQs enabled while a
+// transfer runs
+static KEYBOARD: Mutex<Option<Keyboard>> = Mutex::new(None);
+
+static POLL_DUE: AtomicBool = AtomicBool::new(false);
+
+// Periodic timer callback (IRQ context)
+fn tick(_id: queue::TimerId, _data: usize) {
+    POLL_DUE.store(true, Ordering::Release);
+}
+
+pub fn poll_pending() -> bool {
+    POLL_DUE.load(Ordering::Acquire)
+}
+
+// Read a report if the timer asked for one (see drivers::poll). Skips the
+// poll if the keyboard or the controller is busy, e.g. on another core.
+pub fn poll() {
+    if !POLL_DUE.swap(false, Ordering::AcqRel) {
+        return;
+    }
+
+    let mut output = Output { data: [0; 32], len: 0 };
+    {
+        let Some(mut guard) = KEYBOARD.try_lock() else {
+            return;
+        };
+        let Some(keyboard) = guard.as_mut() else {
+            return;
+        };
+
+        let mut report = [0u8; REPORT_SIZE];
+        let Keyboard { device, endpoint, pid, .. } = keyboard;
+        let result = usb::with_host(|host| host.interrupt_in(device, endpoint, pid, &mut report));
+        match result {
+            Some(Ok(length)) if length >= 3 => keyboard.report(&report, &mut output),
+            Some(Ok(_)) | Some(Err(UsbError::Nak)) | None => {}
+            Some(Err(error)) => keyboard.error(error),
+        }
+        keyboard.repeat(&mut output);
+    }
+    console::push_input(&output.data[..output.len]);
+}
+
+// Called for every new device: take it if it has a boot keyboard interface
+pub fn probe(host: &mut Host, device: &Device, config: &[u8]) {
+    if KEYBOARD.lock().is_some() {
+        return;
+    }
+
+    // The interrupt IN endpoint following the keyboard interface
+    let mut interface = None;
+    let mut endpoint = None;
+    for descriptor in usb::descriptors(config) {
+        match descriptor[1] {
+            usb::DESCRIPTOR_INTERFACE if descriptor.len() >= 9 => {
+                if interface.is_some() {
+                    break;
+                }
+                let boot_keyboard = descriptor[5] == CLASS_HID
+                    && descriptor[6] == SUBCLASS_BOOT
+                    && descriptor[7] == PROTOCOL_KEYBOARD;
+                if boot_keyboard {
+                    interface = Some(descriptor[2]);
+                }
+            }
+            usb::DESCRIPTOR_ENDPOINT if interface.is_some() && descriptor.len() >= 7 => {
+                let input = descriptor[2] & 0x80 != 0;
+                if input && descriptor[3] & 3 == EndpointType::Interrupt as u8 {
+                    endpoint = Some(Endpoint {
+                        number: descriptor[2] & 0xF,
+                        kind: EndpointType::Interrupt,
+                        max_packet: u16::from_le_bytes([descriptor[4], descriptor[5]]) & 0x7FF,
+                    });
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let (Some(interface), Some(endpoint)) = (interface, endpoint) else {
+        return;
+    };
+
+    let setup = Setup::new(usb::TYPE_INTERFACE_OUT, HID_SET_PROTOCOL, BOOT_PROTOCOL, interface as u16, 0);
+    if let Err(error) = host.control(device, setup, &mut []) {
+        println!("USB keyboard: cannot select the boot protocol ({:?})", error);
+        return;
+    }
+    // Report only on changes; the repeat is done here. Optional: may stall.
+    let setup = Setup::new(usb::TYPE_INTERFACE_OUT, HID_SET_IDLE, 0, interface as u16, 0);
+    let _ = host.control(device, setup, &mut []);
+
+    *KEYBOARD.lock() = Some(Keyboard {
+        device: *device,
+        endpoint,
+        pid: usb::PID_DATA0,
+        previous: [0; REPORT_SIZE],
+        caps_lock: false,
+        repeat: None,
+        errors: 0,
+        next_error: Instant::now(),
+    });
+
+    if queue::periodic_every(POLL_PERIOD, tick, 0).is_none() {
+        println!("USB keyboard: no free timer, keyboard not polled");
+        return;
+    }
+    println!("USB keyboard: device {}, endpoint {}", device.address, endpoint.number);
+}
@@ -0,0 +1,151 @@
+use core::arch::asm;
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use crate::println;
+use crate::time::{self, Duration, Instant};
+
+// BCM2835 power management watchdog
+const PM_BASE: usize = 0x3F100000;
+
+const PM_RSTC: usize = PM_BASE + 0x1C;
+const PM_RSTS: usize = PM_BASE + 0x20;
+const PM_WDOG: usize = PM_BASE + 0x24;
+
+// Every write to the PM block must carry the password
+const PM_PASSWORD: u32 = 0x5A00_0000;
+const PM_WDOG_TIME_SET: u32 = 0x000F_FFFF;
+const PM_RSTC_WRCFG_CLR: u32 = 0xFFFF_FFCF;
+const PM_RSTC_WRCFG_FULL_RESET: u32 = 0x0000_0020;
+const PM_RSTC_RESET: u32 = 0x0000_0102;
+
+// Watchdog ticks run at 65536 Hz; the 20-bit counter allows ~16s
+const WDOG_TICKS_PER_SEC: u64 = 1 << 16;
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(15);
+
+static ARMED: AtomicBool = AtomicBool::new(false);
+static TIMEOUT_TICKS: AtomicU32 = AtomicU32::new(0);
+
+// Panic policy: reset N milliseconds after a panic (0 = stay halted)
+static PANIC_REBOOT_MS: AtomicU32 = AtomicU32::new(0);
+
+fn duration_to_ticks(timeout: Duration) -> u32 {
+    let ticks = timeout.as_micros() as u64 * WDOG_TICKS_PER_SEC / 1_000_000;
+    ticks.clamp(1, PM_WDOG_TIME_SET as u64) as u32
+}
+
+fn write_timeout(ticks: u32) {
+    unsafe {
+        write_volatile(PM_WDOG as *mut u32, PM_PASSWORD | (ticks & PM_WDOG_TIME_SET));
+        let rstc = read_volatile(PM_RSTC as *const u32);
+        write_volatile(
+            PM_RSTC as *mut u32,
+            PM_PASSWORD | (rstc & PM_RSTC_WRCFG_CLR) | PM_RSTC_WRCFG_FULL_RESET,
+        );
+    }
+}
+
+// Arm the watchdog: the board resets unless pet() is called within timeout
+pub fn start(timeout: Duration) {
+    let timeout = timeout.min(MAX_TIMEOUT);
+    let ticks = duration_to_ticks(timeout);
+
+    TIMEOUT_TICKS.store(ticks, Ordering::Relaxed);
+    write_timeout(ticks);
+    ARMED.store(true, Ordering::Release);
+
+    println!("Watchdog armed: {} ms timeout", timeout.as_millis());
+}
+
+// Reload the watchdog counter with the armed timeout
+pub fn pet() {
+    if ARMED.load(Ordering::Acquire) {
+        write_timeout(TIMEOUT_TICKS.load(Ordering::Relaxed));
+    }
+}
+
+pub fn stop() {
+    ARMED.store(false, Ordering::Release);
+    unsafe { write_volatile(PM_RSTC as *mut u32, PM_PASSWORD | PM_RSTC_RESET) };
+}
+
+pub fn is_armed() -> bool {
+    ARMED.load(Ordering::Acquire)
+}
+
+// Interval at which an idle core must wake up to pet the watchdog
+pub fn pet_interval() -> Option<Duration> {
+    if !is_armed() {
+        return None;
+    }
+
+    let ticks = TIMEOUT_TICKS.load(Ordering::Relaxed) as u64;
+    Some(Duration::from_micros(ticks * 1_000_000 / WDOG_TICKS_PER_SEC / 2))
+}
+
+// Time left before the watchdog fires
+#[allow(dead_code)]
+pub fn remaining() -> Duration {
+    let ticks = unsafe { read_volatile(PM_WDOG as *const u32) } & PM_WDOG_TIME_SET;
+    Duration::from_micros(ticks as u64 * 1_000_000 / WDOG_TICKS_PER_SEC)
+}
+
+// Reset status of the last boot (RSTS), useful to detect watchdog resets
+pub fn reset_status() -> u32 {
+    unsafe { read_volatile(PM_RSTS as *const u32) }
+}
+
+// Full board reset through the watchdog
+#[allow(dead_code)]
+pub fn reboot() -> ! {
+    println!("Rebooting...");
+
+    ARMED.store(false, Ordering::Release);
+    write_timeout(10);
+
+    loop {
+        unsafe { asm!("wfi", options(nomem, nostack)) };
+    }
+}
+
+// Reset the board this long after a panic instead of halting
+#[allow(dead_code)]
+pub fn set_panic_reboot(delay: Option<Duration>) {
+    let ms = delay.map_or(0, |d| d.as_millis().clamp(1, u32::MAX as u128) as u32);
+    PANIC_REBOOT_MS.store(ms, Ordering::Relaxed);
+}
+
+// Called by the panic handler. Without a reboot policy the watchdog is
+// stopped so the panic message stays on the console.
+pub fn on_panic() {
+    let ms = PANIC_REBOOT_MS.load(Ordering::Relaxed);
+    if ms == 0 {
+        if is_armed() {
+            stop();
+        }
+        return;
+    }
+
+    let delay = Duration::from_millis(ms as u64);
+    println!("Rebooting in {} ms", ms);
+
+    // The hardware timeout is limited: wait out the excess first, then let
+    // the watchdog itself reset the board even if the CPU hangs
+    let hardware = delay.min(MAX_TIMEOUT);
+    let wait_until = Instant::now() + (delay - hardware);
+    while !wait_until.has_passed() {
+        write_timeout(duration_to_ticks(MAX_TIMEOUT));
+        time::mdelay(100);
+    }
+
+    ARMED.store(false, Ordering::Release);
+    write_timeout(duration_to_ticks(hardware));
+
+    loop {
+        unsafe { asm!("wfi", options(nomem, nostack)) };
+    }
+}
+
+pub fn init() {
+    let rsts = reset_status();
+    println!("Watchdog: PM_RSTS = 0x{:08x}", rsts);
+}
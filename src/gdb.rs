@@ -0,0 +1,642 @@
+use core::arch::asm;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use crate::arch::exceptions::ExceptionContext;
+use crate::drivers::{console, watchdog};
+use crate::println;
+
+// Stub do protocolo remoto do GDB (RSP) pelo console serial. Só o core que
+// entrou no stub para; os outros continuam rodando.
+//
+// Entrada: comando "gdb" do monitor, um BRK no código, ou um panic quando
+// "kgdb" está na linha de comando. No host:
+//   (gdb) target remote /dev/ttyUSB0
+
+const PACKET_SIZE: usize = 1024;
+const MAX_BREAKPOINTS: usize = 16;
+
+// Exception classes (ESR_EL1.EC) tratadas pelo stub
+const EC_BRK: u64 = 0x3C;
+const EC_SOFTWARE_STEP: u64 = 0x33;
+const EC_DATA_ABORT: u64 = 0x25;
+
+// brk #0
+const BRK_INSTRUCTION: u32 = 0xD420_0000;
+const BRK_MASK: u32 = 0xFFE0_001F;
+
+// MDSCR_EL1: single-step e debug exceptions no próprio EL
+const MDSCR_SS: u64 = 1 << 0;
+const MDSCR_KDE: u64 = 1 << 13;
+
+// Bits do SPSR
+const SPSR_SS: u64 = 1 << 21;
+const SPSR_D: u64 = 1 << 9;
+const SPSR_I: u64 = 1 << 7;
+
+// Registradores no formato do GDB para aarch64: x0-x30, sp, pc, cpsr
+const REG_SP: usize = 31;
+const REG_PC: usize = 32;
+const REG_CPSR: usize = 33;
+const NUM_REGS: usize = 34;
+
+// Sinal reportado ao GDB (SIGTRAP)
+const SIGTRAP: u8 = 5;
+
+#[derive(Clone, Copy)]
+struct Breakpoint {
+    address: u64,
+    original: u32,
+}
+
+static BREAKPOINTS: Mutex<[Option<Breakpoint>; MAX_BREAKPOINTS]> =
+    Mutex::new([None; MAX_BREAKPOINTS]);
+
+// Entrar no stub em caso de panic
+static ON_PANIC: AtomicBool = AtomicBool::new(false);
+
+// Acesso a memória pedido pelo GDB em andamento; um data abort nesse
+// intervalo é reportado como erro em vez de derrubar o kernel
+static PROBING: AtomicBool = AtomicBool::new(false);
+static PROBE_FAULTED: AtomicBool = AtomicBool::new(false);
+
+// GDB conectado e esperando uma notificação de parada
+static ATTACHED: AtomicBool = AtomicBool::new(false);
+
+// Passo único em andamento e o bit I original do SPSR
+static STEPPING: AtomicBool = AtomicBool::new(false);
+static STEP_IRQS_MASKED: AtomicBool = AtomicBool::new(false);
+
+pub fn init() {
+    // Libera o OS lock para que debug exceptions possam ser geradas
+    unsafe { asm!("msr oslar_el1, xzr", "isb", options(nostack)) };
+
+    if crate::cmdline::has_flag("kgdb") {
+        set_panic_entry(true);
+    }
+}
+
+pub fn set_panic_entry(enabled: bool) {
+    ON_PANIC.store(enabled, Ordering::Relaxed);
+}
+
+pub fn panic_entry() -> bool {
+    ON_PANIC.load(Ordering::Relaxed)
+}
+
+// Para no stub a partir de código normal (comando "gdb" do monitor)
+pub fn breakpoint() {
+    println!("Waiting for GDB on {}...", console::device().name());
+    unsafe { asm!("brk #0", options(nostack)) };
+}
+
+// Chamado pelo panic handler depois da mensagem
+pub fn on_panic() {
+    if panic_entry() {
+        breakpoint();
+    }
+}
+
+// Chamado por sync_exception_el1. Retorna true se a exceção era do stub.
+pub fn handle_exception(ctx: &mut ExceptionContext, exception_class: u64) -> bool {
+    match exception_class {
+        EC_DATA_ABORT if PROBING.load(Ordering::Relaxed) => {
+            // Pula a instrução de acesso que falhou
+            PROBE_FAULTED.store(true, Ordering::Relaxed);
+            ctx.elr_el1 += 4;
+            true
+        }
+        EC_BRK => {
+            session(ctx);
+            true
+        }
+        EC_SOFTWARE_STEP if STEPPING.load(Ordering::Relaxed) => {
+            finish_step(ctx);
+            session(ctx);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn session(ctx: &mut ExceptionContext) {
+    // Um BRK compilado no código (não inserido pelo GDB) é pulado ao continuar
+    let compiled_break = (is_brk(ctx.elr_el1) && breakpoint_at(ctx.elr_el1).is_none())
+        .then_some(ctx.elr_el1);
+
+    let mut packet = [0u8; PACKET_SIZE];
+    let mut reply = Reply::new();
+
+    // Um GDB já conectado espera a notificação de parada; um novo pergunta com '?'
+    if ATTACHED.load(Ordering::Relaxed) {
+        reply.push_str("S");
+        reply.push_hex_u8(SIGTRAP);
+        send_packet(reply.as_bytes());
+    }
+
+    loop {
+        let len = receive_packet(&mut packet);
+        let request = &packet[..len];
+        reply.clear();
+
+        let Some((&command, args)) = request.split_first() else {
+            send_packet(b"");
+            continue;
+        };
+
+        match command {
+            b'?' => {
+                reply.push_str("S");
+                reply.push_hex_u8(SIGTRAP);
+            }
+            b'g' => {
+                for reg in 0..NUM_REGS {
+                    push_register(&mut reply, ctx, reg);
+                }
+            }
+            b'G' => {
+                write_registers(ctx, args);
+                reply.push_str("OK");
+            }
+            b'p' => match parse_hex(args) {
+                Some(reg) if (reg as usize) < NUM_REGS => push_register(&mut reply, ctx, reg as usize),
+                _ => reply.push_str("E01"),
+            },
+            b'P' => {
+                let ok = split_at_byte(args, b'=').and_then(|(reg, value)| {
+                    let reg = parse_hex(reg)? as usize;
+                    let value = decode_le(value)?;
+                    set_register(ctx, reg, value)
+                });
+                reply.push_str(if ok.is_some() { "OK" } else { "E01" });
+            }
+            b'm' => read_memory(&mut reply, args),
+            b'M' => write_memory(&mut reply, args),
+            b'Z' | b'z' => {
+                let insert = command == b'Z';
+                match parse_breakpoint(args) {
+                    Some(address) if set_breakpoint(address, insert) => reply.push_str("OK"),
+                    Some(_) => reply.push_str("E01"),
+                    // Tipos de breakpoint/watchpoint não suportados
+                    None => {}
+                }
+            }
+            b'c' | b's' => {
+                if let Some(address) = parse_hex(args) {
+                    ctx.elr_el1 = address;
+                } else if compiled_break == Some(ctx.elr_el1) {
+                    ctx.elr_el1 += 4;
+                }
+                if command == b's' {
+                    start_step(ctx);
+                }
+                ATTACHED.store(true, Ordering::Relaxed);
+                return;
+            }
+            b'D' | b'k' => {
+                // Desconecta: remove os breakpoints e segue rodando
+                remove_all_breakpoints();
+                if command == b'D' {
+                    send_packet(b"OK");
+                }
+                if compiled_break == Some(ctx.elr_el1) {
+                    ctx.elr_el1 += 4;
+                }
+                ATTACHED.store(false, Ordering::Relaxed);
+                return;
+            }
+            b'H' => reply.push_str("OK"),
+            b'q' => query(&mut reply, args),
+            _ => {}
+        }
+
+        send_packet(reply.as_bytes());
+    }
+}
+
+fn query(reply: &mut Reply, args: &[u8]) {
+    if args.starts_with(b"Supported") {
+        reply.push_str("PacketSize=");
+        reply.push_hex_u64_be(PACKET_SIZE as u64);
+    } else if args == b"Attached" {
+        reply.push_str("1");
+    } else if args == b"C" {
+        reply.push_str("QC1");
+    } else if args == b"fThreadInfo" {
+        reply.push_str("m1");
+    } else if args == b"sThreadInfo" {
+        reply.push_str("l");
+    }
+}
+
+// Passo único: MDSCR_EL1.SS + SPSR.SS, com IRQs mascaradas durante o passo
+fn start_step(ctx: &mut ExceptionContext) {
+    STEP_IRQS_MASKED.store(ctx.spsr_el1 & SPSR_I != 0, Ordering::Relaxed);
+    STEPPING.store(true, Ordering::Relaxed);
+
+    ctx.spsr_el1 = (ctx.spsr_el1 | SPSR_SS | SPSR_I) & !SPSR_D;
+
+    unsafe {
+        let mut mdscr: u64;
+        asm!("mrs {}, mdscr_el1", out(reg) mdscr, options(nomem, nostack));
+        mdscr |= MDSCR_SS | MDSCR_KDE;
+        asm!("msr mdscr_el1, {}", "isb", in(reg) mdscr, options(nostack));
+    }
+}
+
+fn finish_step(ctx: &mut ExceptionContext) {
+    STEPPING.store(false, Ordering::Relaxed);
+
+    ctx.spsr_el1 &= !SPSR_SS;
+    if !STEP_IRQS_MASKED.load(Ordering::Relaxed) {
+        ctx.spsr_el1 &= !SPSR_I;
+    }
+
+    unsafe {
+        let mut mdscr: u64;
+        asm!("mrs {}, mdscr_el1", out(reg) mdscr, options(nomem, nostack));
+        mdscr &= !MDSCR_SS;
+        asm!("msr mdscr_el1, {}", "isb", in(reg) mdscr, options(nostack));
+    }
+}
+
+// --- Registradores ---
+
+fn register(ctx: &ExceptionContext, reg: usize) -> u64 {
+    match reg {
+        0..=30 => ctx.gpr[reg],
+        // O contexto fica na pilha do kernel logo abaixo do SP interrompido
+        REG_SP => ctx as *const ExceptionContext as u64 + size_of::<ExceptionContext>() as u64,
+        REG_PC => ctx.elr_el1,
+        _ => ctx.spsr_el1,
+    }
+}
+
+fn set_register(ctx: &mut ExceptionContext, reg: usize, value: u64) -> Option<()> {
+    match reg {
+        0..=30 => ctx.gpr[reg] = value,
+        REG_PC => ctx.elr_el1 = value,
+        REG_CPSR => ctx.spsr_el1 = value & 0xFFFF_FFFF,
+        // O SP do kernel não pode ser trocado com o contexto na pilha
+        REG_SP => {}
+        _ => return None,
+    }
+    Some(())
+}
+
+fn register_size(reg: usize) -> usize {
+    if reg == REG_CPSR { 4 } else { 8 }
+}
+
+fn push_register(reply: &mut Reply, ctx: &ExceptionContext, reg: usize) {
+    let bytes = register(ctx, reg).to_le_bytes();
+    for &byte in &bytes[..register_size(reg)] {
+        reply.push_hex_u8(byte);
+    }
+}
+
+fn write_registers(ctx: &mut ExceptionContext, mut data: &[u8]) {
+    for reg in 0..NUM_REGS {
+        let size = register_size(reg) * 2;
+        if data.len() < size {
+            break;
+        }
+        if let Some(value) = decode_le(&data[..size]) {
+            set_register(ctx, reg, value);
+        }
+        data = &data[size..];
+    }
+}
+
+// --- Memória ---
+
+fn probe_read(address: u64) -> Option<u8> {
+    PROBE_FAULTED.store(false, Ordering::Relaxed);
+    PROBING.store(true, Ordering::Relaxed);
+    let value = unsafe { core::ptr::read_volatile(address as *const u8) };
+    PROBING.store(false, Ordering::Relaxed);
+
+    (!PROBE_FAULTED.load(Ordering::Relaxed)).then_some(value)
+}
+
+fn probe_write(address: u64, value: u8) -> bool {
+    PROBE_FAULTED.store(false, Ordering::Relaxed);
+    PROBING.store(true, Ordering::Relaxed);
+    unsafe { core::ptr::write_volatile(address as *mut u8, value) };
+    PROBING.store(false, Ordering::Relaxed);
+
+    !PROBE_FAULTED.load(Ordering::Relaxed)
+}
+
+fn read_memory(reply: &mut Reply, args: &[u8]) {
+    let Some((address, len)) = parse_address_length(args) else {
+        reply.push_str("E01");
+        return;
+    };
+    let len = len.min((PACKET_SIZE as u64 - 4) / 2);
+
+    for offset in 0..len {
+        match probe_read(address + offset) {
+            Some(byte) => reply.push_hex_u8(byte),
+            None if offset == 0 => {
+                reply.push_str("E14");
+                return;
+            }
+            // Leitura parcial: devolve o que foi lido
+            None => return,
+        }
+    }
+}
+
+fn write_memory(reply: &mut Reply, args: &[u8]) {
+    let parsed = split_at_byte(args, b':').and_then(|(header, data)| {
+        let (address, len) = parse_address_length(header)?;
+        (data.len() as u64 == len * 2).then_some((address, data))
+    });
+    let Some((address, data)) = parsed else {
+        reply.push_str("E01");
+        return;
+    };
+
+    for (offset, pair) in data.chunks(2).enumerate() {
+        let written = decode_byte(pair).is_some_and(|byte| probe_write(address + offset as u64, byte));
+        if !written {
+            reply.push_str("E14");
+            return;
+        }
+    }
+
+    sync_instruction_cache(address, data.len() / 2);
+    reply.push_str("OK");
+}
+
+// Torna visível para a busca de instruções o que foi escrito como dado
+fn sync_instruction_cache(address: u64, len: usize) {
+    let mut line = address & !63;
+    while line < address + len as u64 {
+        unsafe { asm!("dc cvau, {}", in(reg) line, options(nostack)) };
+        line += 64;
+    }
+    unsafe { asm!("dsb ish", options(nostack)) };
+
+    let mut line = address & !63;
+    while line < address + len as u64 {
+        unsafe { asm!("ic ivau, {}", in(reg) line, options(nostack)) };
+        line += 64;
+    }
+    unsafe { asm!("dsb ish", "isb", options(nostack)) };
+}
+
+// --- Breakpoints ---
+
+fn is_brk(address: u64) -> bool {
+    if !address.is_multiple_of(4) {
+        return false;
+    }
+    read_instruction(address).is_some_and(|insn| insn & BRK_MASK == BRK_INSTRUCTION)
+}
+
+fn read_instruction(address: u64) -> Option<u32> {
+    let mut bytes = [0u8; 4];
+    for (offset, byte) in bytes.iter_mut().enumerate() {
+        *byte = probe_read(address + offset as u64)?;
+    }
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn write_instruction(address: u64, insn: u32) -> bool {
+    let written = insn
+        .to_le_bytes()
+        .iter()
+        .enumerate()
+        .all(|(offset, &byte)| probe_write(address + offset as u64, byte));
+    sync_instruction_cache(address, 4);
+    written
+}
+
+fn breakpoint_at(address: u64) -> Option<Breakpoint> {
+    BREAKPOINTS
+        .lock()
+        .iter()
+        .flatten()
+        .find(|bp| bp.address == address)
+        .copied()
+}
+
+// Z0/z0 (breakpoint de software); outros tipos ficam sem suporte
+fn parse_breakpoint(args: &[u8]) -> Option<u64> {
+    let rest = args.strip_prefix(b"0,")?;
+    let (address, _kind) = split_at_byte(rest, b',')?;
+    parse_hex(address)
+}
+
+fn set_breakpoint(address: u64, insert: bool) -> bool {
+    if !address.is_multiple_of(4) {
+        return false;
+    }
+
+    let mut breakpoints = BREAKPOINTS.lock();
+    let existing = breakpoints
+        .iter()
+        .position(|bp| bp.is_some_and(|bp| bp.address == address));
+
+    match (insert, existing) {
+        (true, Some(_)) => true,
+        (true, None) => {
+            let Some(slot) = breakpoints.iter_mut().find(|bp| bp.is_none()) else {
+                return false;
+            };
+            let Some(original) = read_instruction(address) else {
+                return false;
+            };
+            if !write_instruction(address, BRK_INSTRUCTION) {
+                return false;
+            }
+            *slot = Some(Breakpoint { address, original });
+            true
+        }
+        (false, Some(index)) => {
+            if let Some(bp) = breakpoints[index].take() {
+                write_instruction(bp.address, bp.original);
+            }
+            true
+        }
+        (false, None) => true,
+    }
+}
+
+fn remove_all_breakpoints() {
+    let mut breakpoints = BREAKPOINTS.lock();
+    for slot in breakpoints.iter_mut() {
+        if let Some(bp) = slot.take() {
+            write_instruction(bp.address, bp.original);
+        }
+    }
+}
+
+// --- Transporte ---
+
+// Leitura por polling: o stub roda com IRQs mascaradas
+fn read_byte() -> u8 {
+    loop {
+        if let Some(byte) = console::read_byte() {
+            return byte;
+        }
+        // Parado no debugger não é travamento
+        watchdog::pet();
+        core::hint::spin_loop();
+    }
+}
+
+fn write_bytes(bytes: &[u8]) {
+    // Direto no dispositivo: o lock do console pode estar com o código parado
+    console::device().write_bytes(bytes);
+}
+
+// Recebe "$dados#cs", confirmando com '+' (ou pedindo reenvio com '-')
+fn receive_packet(buffer: &mut [u8]) -> usize {
+    loop {
+        while read_byte() != b'$' {}
+
+        let mut len = 0;
+        let mut checksum = 0u8;
+        let complete = loop {
+            match read_byte() {
+                b'#' => break true,
+                b'$' => break false,
+                byte => {
+                    if len < buffer.len() {
+                        buffer[len] = byte;
+                        len += 1;
+                    }
+                    checksum = checksum.wrapping_add(byte);
+                }
+            }
+        };
+        if !complete {
+            continue;
+        }
+
+        let expected = decode_byte(&[read_byte(), read_byte()]);
+        if expected == Some(checksum) {
+            write_bytes(b"+");
+            return len;
+        }
+        write_bytes(b"-");
+    }
+}
+
+fn send_packet(data: &[u8]) {
+    let checksum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+
+    loop {
+        write_bytes(b"$");
+        write_bytes(data);
+        write_bytes(b"#");
+        write_bytes(&[HEX_DIGITS[(checksum >> 4) as usize], HEX_DIGITS[(checksum & 0xF) as usize]]);
+
+        // Espera o ack; um '$' aqui é o GDB reenviando um pacote (sem ack)
+        match read_byte() {
+            b'-' => continue,
+            _ => return,
+        }
+    }
+}
+
+// --- Codificação ---
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+struct Reply {
+    data: [u8; PACKET_SIZE],
+    len: usize,
+}
+
+impl Reply {
+    const fn new() -> Self {
+        Reply {
+            data: [0; PACKET_SIZE],
+            len: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.len < self.data.len() {
+            self.data[self.len] = byte;
+            self.len += 1;
+        }
+    }
+
+    fn push_str(&mut self, text: &str) {
+        for byte in text.bytes() {
+            self.push(byte);
+        }
+    }
+
+    fn push_hex_u8(&mut self, value: u8) {
+        self.push(HEX_DIGITS[(value >> 4) as usize]);
+        self.push(HEX_DIGITS[(value & 0xF) as usize]);
+    }
+
+    // Número em hexadecimal sem zeros à esquerda (campos de qSupported)
+    fn push_hex_u64_be(&mut self, value: u64) {
+        let digits = (64 - value.leading_zeros()).div_ceil(4).max(1);
+        for shift in (0..digits).rev() {
+            self.push(HEX_DIGITS[((value >> (shift * 4)) & 0xF) as usize]);
+        }
+    }
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn decode_byte(pair: &[u8]) -> Option<u8> {
+    match pair {
+        [high, low] => Some(hex_value(*high)? << 4 | hex_value(*low)?),
+        _ => None,
+    }
+}
+
+// Número em hexadecimal "big-endian" (endereços, tamanhos)
+fn parse_hex(text: &[u8]) -> Option<u64> {
+    if text.is_empty() || text.len() > 16 {
+        return None;
+    }
+    text.iter()
+        .try_fold(0u64, |value, &digit| Some(value << 4 | hex_value(digit)? as u64))
+}
+
+// Valor de registrador: bytes em ordem little-endian
+fn decode_le(text: &[u8]) -> Option<u64> {
+    if text.is_empty() || text.len() > 16 || !text.len().is_multiple_of(2) {
+        return None;
+    }
+    text.chunks(2)
+        .enumerate()
+        .try_fold(0u64, |value, (index, pair)| {
+            Some(value | (decode_byte(pair)? as u64) << (index * 8))
+        })
+}
+
+fn split_at_byte(text: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let index = text.iter().position(|&byte| byte == separator)?;
+    Some((&text[..index], &text[index + 1..]))
+}
+
+fn parse_address_length(args: &[u8]) -> Option<(u64, u64)> {
+    let (address, len) = split_at_byte(args, b',')?;
+    Some((parse_hex(address)?, parse_hex(len)?))
+}
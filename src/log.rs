@@ -0,0 +1,299 @@
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU8, Ordering};
+use crate::arch::multicore::get_core_id;
+use crate::arch::multicore::sync::IrqMutex;
+use crate::cmdline;
+use crate::time;
+
+// Subsistema de log do kernel: níveis, filtros por módulo, prefixo
+// "[tempo][core]" e um buffer circular (dmesg) que guarda as mensagens
+// recentes mesmo com a saída no console desabilitada.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl Level {
+    fn from_u8(value: u8) -> Option<Level> {
+        match value {
+            1 => Some(Level::Error),
+            2 => Some(Level::Warn),
+            3 => Some(Level::Info),
+            4 => Some(Level::Debug),
+            5 => Some(Level::Trace),
+            _ => None,
+        }
+    }
+
+    // Aceita o nome ("debug") ou o número ("4")
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => name.parse().ok().and_then(Level::from_u8),
+        }
+    }
+
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Level::Error => "E",
+            Level::Warn => "W",
+            Level::Info => "I",
+            Level::Debug => "D",
+            Level::Trace => "T",
+        }
+    }
+}
+
+const MAX_FILTERS: usize = 8;
+const DMESG_SIZE: usize = 16 * 1024;
+const MAX_LINE: usize = 256;
+
+// Bytes copiados do dmesg por vez (ver dmesg)
+const DMESG_CHUNK: usize = 1024;
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+// Nível máximo enviado ao console (0 = console desabilitado)
+static CONSOLE_LEVEL: AtomicU8 = AtomicU8::new(Level::Trace as u8);
+
+// Filtros por prefixo de módulo (ex.: "rust_minix::drivers::gic").
+// IrqMutex: log() também roda em contexto de interrupção
+static FILTERS: IrqMutex<[Option<(&'static str, Level)>; MAX_FILTERS]> =
+    IrqMutex::new([None; MAX_FILTERS]);
+
+// Buffer circular do dmesg: guarda linhas completas, sobrescrevendo as
+// mais antigas. `written` conta todos os bytes já gravados.
+struct LogBuffer {
+    data: [u8; DMESG_SIZE],
+    written: usize,
+}
+
+impl LogBuffer {
+    fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.data[self.written % DMESG_SIZE] = byte;
+            self.written += 1;
+        }
+    }
+
+    fn byte_at(&self, position: usize) -> u8 {
+        self.data[position % DMESG_SIZE]
+    }
+
+    // Primeira posição ainda válida, alinhada no início de uma linha
+    fn first_line(&self) -> usize {
+        if self.written <= DMESG_SIZE {
+            return 0;
+        }
+
+        let mut position = self.written - DMESG_SIZE;
+        while position < self.written && self.byte_at(position - 1) != b'\n' {
+            position += 1;
+        }
+        position
+    }
+}
+
+static DMESG: IrqMutex<LogBuffer> = IrqMutex::new(LogBuffer {
+    data: [0; DMESG_SIZE],
+    written: 0,
+});
+
+// Linha montada na pilha; trunca em vez de falhar
+struct LineBuffer {
+    data: [u8; MAX_LINE],
+    len: usize,
+}
+
+impl LineBuffer {
+    fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl Write for LineBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Reserva um byte para o '\n' final; não corta um caractere UTF-8
+        // ao meio (a linha inteira viraria "<invalid utf-8>")
+        let room = MAX_LINE - 1 - self.len;
+        let mut count = s.len().min(room);
+        while !s.is_char_boundary(count) {
+            count -= 1;
+        }
+        self.data[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
+
+pub fn max_level() -> Level {
+    Level::from_u8(MAX_LEVEL.load(Ordering::Relaxed)).unwrap_or(Level::Info)
+}
+
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+// Nível específico para módulos com o prefixo dado
+pub fn set_module_level(prefix: &'static str, level: Level) -> bool {
+    let mut filters = FILTERS.lock();
+
+    if let Some(slot) = filters
+        .iter_mut()
+        .find(|f| matches!(f, Some((p, _)) if *p == prefix))
+    {
+        *slot = Some((prefix, level));
+        return true;
+    }
+
+    match filters.iter_mut().find(|f| f.is_none()) {
+        Some(slot) => {
+            *slot = Some((prefix, level));
+            true
+        }
+        None => false,
+    }
+}
+
+// O filtro de prefixo mais longo vence; sem filtro vale o nível global
+fn level_for(module: &str) -> Level {
+    let filters = FILTERS.lock();
+
+    filters
+        .iter()
+        .flatten()
+        .filter(|(prefix, _)| module_matches(module, prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|&(_, level)| level)
+        .unwrap_or_else(max_level)
+}
+
+// Aceita o caminho completo ou relativo à crate ("drivers::gic")
+fn module_matches(module: &str, prefix: &str) -> bool {
+    let relative = module.split_once("::").map_or("", |(_, rest)| rest);
+    module.starts_with(prefix) || relative.starts_with(prefix)
+}
+
+pub fn enabled(level: Level, module: &str) -> bool {
+    level <= level_for(module)
+}
+
+// None desabilita a saída no console; o dmesg continua recebendo tudo
+pub fn set_console_level(level: Option<Level>) {
+    CONSOLE_LEVEL.store(level.map_or(0, |l| l as u8), Ordering::Relaxed);
+}
+
+#[allow(dead_code)]
+pub fn console_level() -> Option<Level> {
+    Level::from_u8(CONSOLE_LEVEL.load(Ordering::Relaxed))
+}
+
+pub fn log(level: Level, module: &str, args: fmt::Arguments) {
+    if !enabled(level, module) {
+        return;
+    }
+
+    let uptime = time::uptime();
+    let mut line = LineBuffer {
+        data: [0; MAX_LINE],
+        len: 0,
+    };
+    let _ = write!(
+        line,
+        "[{:5}.{:06}][{}] {}: ",
+        uptime.as_secs(),
+        uptime.subsec_micros(),
+        get_core_id(),
+        level.tag()
+    );
+    let _ = line.write_fmt(args);
+    line.data[line.len] = b'\n';
+    line.len += 1;
+
+    DMESG.lock().push(line.as_bytes());
+
+    if level as u8 <= CONSOLE_LEVEL.load(Ordering::Relaxed) {
+        let text = core::str::from_utf8(line.as_bytes()).unwrap_or("<invalid utf-8>\n");
+        crate::drivers::console::_print(format_args!("{}", text));
+    }
+}
+
+// Percorre as linhas guardadas no dmesg, da mais antiga para a mais nova.
+// As linhas são copiadas em blocos e `f` roda com o DMESG liberado: um
+// console lento não deixa as IRQs mascaradas durante o dump inteiro.
+pub fn dmesg<F: FnMut(&str)>(mut f: F) {
+    let mut chunk = [0u8; DMESG_CHUNK];
+    let (mut position, end) = {
+        let buffer = DMESG.lock();
+        (buffer.first_line(), buffer.written)
+    };
+
+    while position < end {
+        let len = {
+            let buffer = DMESG.lock();
+            // Limpo no meio do dump
+            if buffer.written < end {
+                return;
+            }
+            // Sobrescrito enquanto o bloco anterior era mostrado
+            position = position.max(buffer.first_line());
+
+            let available = end.saturating_sub(position).min(DMESG_CHUNK);
+            let mut len = 0;
+            for (index, byte) in chunk[..available].iter_mut().enumerate() {
+                *byte = buffer.byte_at(position + index);
+                if *byte == b'\n' {
+                    len = index + 1;
+                }
+            }
+            // Só linhas inteiras, a não ser que nenhuma caiba no bloco
+            if len == 0 { available } else { len }
+        };
+        if len == 0 {
+            return;
+        }
+        position += len;
+
+        for line in chunk[..len].split_inclusive(|&byte| byte == b'\n') {
+            f(core::str::from_utf8(line).unwrap_or("<invalid utf-8>\n"));
+        }
+    }
+}
+
+pub fn dmesg_clear() {
+    let mut buffer = DMESG.lock();
+    buffer.written = 0;
+}
+
+// Configuração pela linha de comando:
+//   loglevel=debug            nível global
+//   log=drivers::gic=trace,arch=warn   filtros por módulo
+//   quiet                     só erros no console (o dmesg guarda tudo)
+pub fn init() {
+    if let Some(level) = cmdline::param("loglevel").and_then(Level::from_name) {
+        set_max_level(level);
+    }
+
+    if let Some(filters) = cmdline::param("log") {
+        for filter in filters.split(',') {
+            if let Some((prefix, level)) = filter.rsplit_once('=') {
+                if let Some(level) = Level::from_name(level) {
+                    set_module_level(prefix, level);
+                }
+            }
+        }
+    }
+
+    if cmdline::has_flag("quiet") {
+        set_console_level(Some(Level::Error));
+    }
+}
@@ -1,10 +1,8 @@
 #[macro_export]
 macro_rules! print {
-    ($($arg:tt)*) => {{
-        use core::fmt::Write;
-        let mut uart = $crate::drivers::uart::UART.lock();
-        let _ = write!(uart, $($arg)*);
-    }};
+    ($($arg:tt)*) => {
+        $crate::drivers::console::_print(format_args!($($arg)*))
+    };
 }
 
 #[macro_export]
@@ -12,8 +10,83 @@ macro_rules! println {
     () => {
         $crate::print!("\n")
     };
-    ($($arg:tt)*) => {{
-        $crate::print!($($arg)*);
-        $crate::print!("\n");
+    ($($arg:tt)*) => {
+        // Texto e quebra de linha sob o mesmo lock
+        $crate::drivers::console::_print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+// Saída direta no PL011 pelo earlycon (sem locks nem estado), para o
+// início do boot
+#[macro_export]
+macro_rules! early_print {
+    ($($arg:tt)*) => {
+        $crate::drivers::earlycon::write_fmt(format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! early_println {
+    () => {
+        $crate::early_print!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::early_print!("{}\n", format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::log::log($level, module_path!(), format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log!($crate::log::Level::Error, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log!($crate::log::Level::Warn, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log!($crate::log::Level::Info, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log!($crate::log::Level::Debug, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::log!($crate::log::Level::Trace, $($arg)*)
+    };
+}
+
+// Trace compacto: binário com "trace=binary", texto (nível info) no resto.
+// Os argumentos são gravados como u64.
+#[macro_export]
+macro_rules! ktrace {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => {{
+        const ID: u32 = $crate::trace::format_id($fmt);
+        if $crate::trace::binary() {
+            $crate::trace::emit(ID, &[$(($arg) as u64),*]);
+        } else {
+            $crate::info!($fmt $(, $arg)*);
+        }
     }};
-}
\ No newline at end of file
+}
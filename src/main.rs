@@ -11,10 +11,18 @@ extern crate alloc;
 
 mod arch;
 mod boot;
+mod cmdline;
 mod drivers;
+mod gdb;
+mod log;
 mod macros;
 mod memory;
+mod monitor;
 mod panic;
+mod random;
+mod time;
+mod trace;
+mod tty;
 
 // Símbolos definidos no linker script
 extern "C" {
@@ -24,16 +32,19 @@ extern "C" {
     static __stack_end: u8;
 }
 
-// Função de atraso para garantir estabilidade do console
-unsafe fn delay(cycles: usize) {
-    for _ in 0..cycles {
-        core::arch::asm!("nop", options(nomem, nostack));
-    }
-}
+// Tempo máximo sem alimentar o watchdog antes do reset da placa
+const WATCHDOG_TIMEOUT: time::Duration = time::Duration::from_secs(15);
+
+// Atraso entre um panic e o reboot (feature "panic-reboot")
+#[cfg(feature = "panic-reboot")]
+const PANIC_REBOOT_DELAY: time::Duration = time::Duration::from_secs(5);
 
 // Ponto de entrada principal em Rust
 #[no_mangle]
-pub extern "C" fn rust_main() -> ! {
+pub extern "C" fn rust_main(dtb: usize) -> ! {
+    early_println!("Rust MINIX: early console on PL011");
+    early_println!("Device tree at 0x{:x}", dtb);
+    
     // Limpeza da seção BSS
     let bss_size = unsafe {
         (&__bss_end as *const u8 as usize) - (&__bss_start as *const u8 as usize)
@@ -42,20 +53,35 @@ pub extern "C" fn rust_main() -> ! {
         memory::memzero(&__bss_start as *const u8 as *mut u8, bss_size);
     }
 
-    // Inicializa drivers (UART primeiro para habilitar prints)
+    // Base de tempo do sistema (contador ARM) antes de qualquer driver
+    time::init();
+    
+    // Linha de comando do firmware (seleção de console, nível de log, etc.)
+    cmdline::init(dtb);
+    log::init();
+    trace::init();
+
+    // Até aqui a saída vai pelo earlycon; drivers::init passa o console
+    // para o driver completo
     drivers::init();
+
+    // Gerador aleatório do kernel, semeado pelo RNG de hardware e jitter
+    random::init();
+    
+    // Terminais sobre os UARTs (/dev/tty, /dev/ttyAMA0, /dev/ttyS0)
+    tty::init();
     
     // Aguarda estabilização do console
-    unsafe {
-        println!("Waiting for console to stabilize...");
-        delay(50_000_000); // Ajuste conforme a velocidade do seu CPU
-    }
+    println!("Waiting for console to stabilize...");
+    time::mdelay(100);
     
     println!("=== Rust MINIX (ARM64) ===");
     println!("Version: {}", KERNEL_VERSION);
     println!("Target: {} ({})", TARGET_ARCH, TARGET_CPU);
     println!("Build: {}", BUILD_TIME);
     println!("BSS cleared: {} bytes", bss_size);
+    println!("Command line: \"{}\"", cmdline::get());
+    println!("Console: {}", drivers::console::device().name());
     
     let stack_start = unsafe { &__stack_start as *const u8 as usize };
     println!("Stack start: 0x{:x}", stack_start);
@@ -63,16 +89,16 @@ pub extern "C" fn rust_main() -> ! {
     // Inicializa subsistemas de memória (incluindo alocador)
     memory::init();
     
-    println!("DEBUG: Memory initialization completed");
+    debug!("Memory initialization completed");
     
     // Inicializar multi-core antes de outras inicializações
     println!("Initializing multi-core system...");
     arch::multicore::init_multicore();
     
-    println!("DEBUG: Multi-core initialization completed");
+    debug!("Multi-core initialization completed");
     
     // Aguardar um pouco para cores secundários inicializarem
-    unsafe { delay(10_000_000) };
+    time::mdelay(20);
     
     // Mostrar status multi-core detalhado
     arch::multicore::print_cores_status();
@@ -83,6 +109,7 @@ pub extern "C" fn rust_main() -> ! {
     // Inicializa sistema de exceções
     println!("Setting up exception handling...");
     arch::exceptions::init();
+    gdb::init();
     
     // Habilita interrupções após tudo estar configurado
     println!("Enabling interrupts...");
@@ -105,6 +132,11 @@ pub extern "C" fn rust_main() -> ! {
     
     println!("System ready for Phase 2");
     
+    // Watchdog de hardware: alimentado pelo loop principal e pelo idle
+    drivers::watchdog::start(WATCHDOG_TIMEOUT);
+    #[cfg(feature = "panic-reboot")]
+    drivers::watchdog::set_panic_reboot(Some(PANIC_REBOOT_DELAY));
+    
     // Informações do sistema multi-core
     let online_cores = arch::multicore::get_online_cores();
     println!("Multi-core system ready: {} cores online", online_cores);
@@ -122,6 +154,8 @@ pub extern "C" fn rust_main() -> ! {
     // Demonstra o sistema funcionando sem sleep
     println!("Timer tick count before: {}", drivers::timer::get_tick_count());
     println!("System is ready and running!");
+    println!("Press Enter for the kernel monitor");
+    monitor::init();
     
     // Loop principal do kernel
     let mut counter = 0;
@@ -130,10 +164,12 @@ pub extern "C" fn rust_main() -> ! {
     loop {
         // Mostra informações do sistema a cada 1M iterações
         if counter % 1000000 == 0 {
+            drivers::watchdog::pet();
+            
             let time = arch::aarch64::get_time();
             let tick_count = drivers::timer::get_tick_count();
             
-            println!("Counter: {}, System time: {} ms, Timer ticks: {}", 
+            ktrace!("Counter: {}, System time: {} ms, Timer ticks: {}", 
                      counter / 1000000, time, tick_count);
             
             // Demonstração multi-core em fases
@@ -158,7 +194,7 @@ pub extern "C" fn rust_main() -> ! {
                     // Fases intermediárias - apenas monitorar
                     if multicore_demo_phase % 2 == 1 {
                         let stats = arch::multicore::get_core_workload_stats();
-                        println!("📊 Core workloads: [{}] [{}] [{}] [{}]", 
+                        ktrace!("📊 Core workloads: [{}] [{}] [{}] [{}]", 
                                 stats[0], stats[1], stats[2], stats[3]);
                     }
                 }
@@ -166,17 +202,20 @@ pub extern "C" fn rust_main() -> ! {
             
             multicore_demo_phase += 1;
             
-            // Atualizar display visual
-            drivers::display::draw_kernel_status(time, tick_count, counter / 1000000);
-            
             // Se tivemos algum tick, o timer está funcionando!
             if tick_count > 0 {
-                println!("🎉 Timer interrupts are working! Tick count: {}", tick_count);
+                ktrace!("🎉 Timer interrupts are working! Tick count: {}", tick_count);
             }
         }
         
         counter += 1;
         
+        // Enter no console abre o monitor; trabalho adiado dos drivers
+        if counter % 1024 == 0 {
+            monitor::poll();
+            drivers::poll();
+        }
+        
         // Permite que interrupções sejam processadas
         core::hint::spin_loop();
         
@@ -188,14 +227,11 @@ pub extern "C" fn rust_main() -> ! {
             println!("🛑 Shutting down secondary cores...");
             arch::multicore::shutdown_secondary_cores();
             
-            drivers::display::draw_kernel_status(
-                arch::aarch64::get_time(), 
-                drivers::timer::get_tick_count(), 
-                counter / 1000000
-            );
-            println!("Entering infinite loop...");
+            println!("Entering idle loop...");
             loop {
-                core::hint::spin_loop();
+                // Nada para rodar: idle sem tick até a próxima interrupção
+                time::idle::idle();
+                monitor::poll();
             }
         }
     }
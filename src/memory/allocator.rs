@@ -7,6 +7,18 @@ struct AllocState {
     next: usize,
     heap_start: usize,
     heap_size: usize,
+    allocations: usize,
+    failures: usize,
+}
+
+// Estatísticas do heap (usadas pelo monitor)
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    pub start: usize,
+    pub size: usize,
+    pub used: usize,
+    pub allocations: usize,
+    pub failures: usize,
 }
 
 pub struct SimpleAllocator {
@@ -20,6 +32,8 @@ impl SimpleAllocator {
                 next: 0,
                 heap_start: 0,
                 heap_size: 0,
+                allocations: 0,
+                failures: 0,
             }),
         }
     }
@@ -30,6 +44,17 @@ impl SimpleAllocator {
         state.heap_start = heap_start;
         state.heap_size = heap_size;
     }
+    
+    pub fn stats(&self) -> HeapStats {
+        let state = self.state.lock();
+        HeapStats {
+            start: state.heap_start,
+            size: state.heap_size,
+            used: state.next - state.heap_start,
+            allocations: state.allocations,
+            failures: state.failures,
+        }
+    }
 }
 
 unsafe impl GlobalAlloc for SimpleAllocator {
@@ -39,9 +64,11 @@ unsafe impl GlobalAlloc for SimpleAllocator {
         let alloc_end = alloc_start + layout.size();
         
         if alloc_end > state.heap_start + state.heap_size {
+            state.failures += 1;
             null_mut()
         } else {
             state.next = alloc_end;
+            state.allocations += 1;
             alloc_start as *mut u8
         }
     }
@@ -0,0 +1,424 @@
+use crate::arch::multicore;
+use crate::drivers::block::{self, SECTOR_SIZE};
+use crate::drivers::{console, dma, gic, timer, usb};
+use crate::log::{self, Level};
+use crate::memory::allocator::ALLOCATOR;
+use crate::random;
+use crate::time;
+use crate::{print, println};
+
+// Monitor interativo do kernel pelo console serial. Entra-se nele
+// apertando Enter no console (ou com "monitor" na linha de comando) e
+// sai-se com "exit".
+
+const MAX_LINE: usize = 128;
+const MAX_ARGS: usize = 8;
+
+// Tecla que abre o monitor a partir do loop principal
+const ENTER_KEY: u8 = b'\r';
+
+// Limite de palavras mostradas por um único "peek"
+const MAX_PEEK_WORDS: usize = 64;
+
+const PROMPT: &str = "monitor> ";
+
+struct Command {
+    name: &'static str,
+    usage: &'static str,
+    help: &'static str,
+    run: fn(&[&str]),
+}
+
+const COMMANDS: &[Command] = &[
+    Command { name: "help", usage: "", help: "list commands", run: cmd_help },
+    Command { name: "cores", usage: "", help: "multi-core status", run: cmd_cores },
+    Command { name: "stats", usage: "", help: "per-core workload counters", run: cmd_stats },
+    Command { name: "irqs", usage: "", help: "interrupt counters", run: cmd_irqs },
+    Command { name: "uptime", usage: "", help: "timer ticks, uptime and idle time", run: cmd_uptime },
+    Command { name: "heap", usage: "", help: "heap usage", run: cmd_heap },
+    Command { name: "peek", usage: "<addr> [words]", help: "read 32-bit words", run: cmd_peek },
+    Command { name: "poke", usage: "<addr> <value>", help: "write a 32-bit word", run: cmd_poke },
+    Command { name: "compute", usage: "", help: "put secondary cores in compute mode", run: cmd_compute },
+    Command { name: "idle", usage: "", help: "put secondary cores in idle mode", run: cmd_idle },
+    Command { name: "balance", usage: "", help: "check workload balance", run: cmd_balance },
+    Command { name: "shutdown", usage: "", help: "stop secondary cores", run: cmd_shutdown },
+    Command { name: "dmesg", usage: "[clear]", help: "kernel log buffer", run: cmd_dmesg },
+    Command { name: "gdb", usage: "[panic on|off]", help: "stop in the GDB stub", run: cmd_gdb },
+    Command { name: "loglevel", usage: "[level]", help: "show or set the log level", run: cmd_loglevel },
+    Command { name: "disks", usage: "", help: "list block devices", run: cmd_disks },
+    Command { name: "sector", usage: "<dev> <lba>", help: "dump a 512-byte sector", run: cmd_sector },
+    Command { name: "random", usage: "[bytes]", help: "entropy pool state and random bytes", run: cmd_random },
+    Command { name: "dma", usage: "[bench [KB]]", help: "DMA channels, CPU vs DMA copy", run: cmd_dma },
+    Command { name: "usb", usage: "", help: "enumerated USB devices", run: cmd_usb },
+];
+
+// Chamado pelo loop principal: abre o monitor se Enter foi apertado
+pub fn poll() {
+    if console::read_byte() == Some(ENTER_KEY) {
+        run();
+    }
+}
+
+// Abre o monitor já no boot quando "monitor" está na linha de comando
+pub fn init() {
+    if crate::cmdline::has_flag("monitor") {
+        run();
+    }
+}
+
+pub fn run() {
+    println!();
+    println!("Kernel monitor - type 'help' for commands, 'exit' to leave");
+
+    let mut line = [0u8; MAX_LINE];
+    loop {
+        print!("{}", PROMPT);
+        let Some(len) = read_line(&mut line) else {
+            continue;
+        };
+
+        let text = core::str::from_utf8(&line[..len]).unwrap_or("");
+        let mut args = [""; MAX_ARGS];
+        let mut argc = 0;
+        for word in text.split_ascii_whitespace().take(MAX_ARGS) {
+            args[argc] = word;
+            argc += 1;
+        }
+
+        match args[0] {
+            "" => {}
+            "exit" | "quit" => break,
+            name => match COMMANDS.iter().find(|command| command.name == name) {
+                Some(command) => (command.run)(&args[1..argc]),
+                None => println!("Unknown command: {} (try 'help')", name),
+            },
+        }
+    }
+
+    println!("Leaving monitor");
+}
+
+// Lê uma linha com eco e edição básica. None se cancelada com ^C.
+fn read_line(buffer: &mut [u8]) -> Option<usize> {
+    let mut len = 0;
+
+    loop {
+        match console::read_byte_blocking() {
+            b'\r' | b'\n' => {
+                println!();
+                return Some(len);
+            }
+            // Backspace / DEL
+            0x08 | 0x7F => {
+                if len > 0 {
+                    len -= 1;
+                    print!("\x08 \x08");
+                }
+            }
+            // ^C
+            0x03 => {
+                println!("^C");
+                return None;
+            }
+            byte @ 0x20..=0x7E if len < buffer.len() => {
+                buffer[len] = byte;
+                len += 1;
+                print!("{}", byte as char);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Aceita hexadecimal com "0x" ou decimal
+fn parse_number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn parse_address(text: &str) -> Option<usize> {
+    let address = parse_number(text)? as usize;
+    if !address.is_multiple_of(4) {
+        println!("Address must be 4-byte aligned");
+        return None;
+    }
+    Some(address)
+}
+
+fn cmd_help(_args: &[&str]) {
+    for command in COMMANDS {
+        println!("  {:<9} {:<15} {}", command.name, command.usage, command.help);
+    }
+    println!("  {:<9} {:<15} {}", "exit", "", "leave the monitor");
+}
+
+fn cmd_cores(_args: &[&str]) {
+    multicore::print_cores_status();
+}
+
+fn cmd_stats(_args: &[&str]) {
+    let stats = multicore::get_core_workload_stats();
+    for (core, work) in stats.iter().enumerate() {
+        let state = if multicore::is_core_online(core as u8) { "online" } else { "offline" };
+        println!("  Core {}: {:>10} ({})", core, work, state);
+    }
+}
+
+fn cmd_irqs(_args: &[&str]) {
+    println!("  IRQ      count  source");
+    for irq in 0..gic::MAX_IRQS as u32 {
+        let count = gic::irq_count(irq);
+        if count != 0 {
+            println!("  {:>3} {:>10}  {}", irq, count, gic::irq_name(irq));
+        }
+    }
+    println!("  Spurious: {}", gic::spurious_count());
+}
+
+fn cmd_uptime(_args: &[&str]) {
+    let uptime = time::uptime();
+    let (idle_entries, idle_time) = time::idle::idle_stats();
+
+    println!("  Uptime: {}.{:03} s", uptime.as_secs(), uptime.subsec_millis());
+    println!("  Timer ticks: {}", timer::get_tick_count());
+    println!(
+        "  Idle: {} entries, {}.{:03} s",
+        idle_entries,
+        idle_time.as_secs(),
+        idle_time.subsec_millis()
+    );
+}
+
+fn cmd_heap(_args: &[&str]) {
+    let stats = ALLOCATOR.stats();
+    println!("  Heap: 0x{:x}-0x{:x}", stats.start, stats.start + stats.size);
+    println!(
+        "  Used: {} / {} bytes ({}%)",
+        stats.used,
+        stats.size,
+        stats.used * 100 / stats.size.max(1)
+    );
+    println!("  Allocations: {} (failed: {})", stats.allocations, stats.failures);
+}
+
+fn cmd_peek(args: &[&str]) {
+    let Some(address) = args.first().and_then(|arg| parse_address(arg)) else {
+        println!("usage: peek <addr> [words]");
+        return;
+    };
+    let words = args
+        .get(1)
+        .and_then(|arg| parse_number(arg))
+        .unwrap_or(1)
+        .min(MAX_PEEK_WORDS as u64) as usize;
+
+    for row in (0..words).step_by(4) {
+        print!("  {:016x}:", address + row * 4);
+        for word in row..(row + 4).min(words) {
+            let value = unsafe { core::ptr::read_volatile((address + word * 4) as *const u32) };
+            print!(" {:08x}", value);
+        }
+        println!();
+    }
+}
+
+fn cmd_poke(args: &[&str]) {
+    let address = args.first().and_then(|arg| parse_address(arg));
+    let value = args.get(1).and_then(|arg| parse_number(arg));
+
+    match (address, value) {
+        (Some(address), Some(value)) if value <= u32::MAX as u64 => {
+            unsafe { core::ptr::write_volatile(address as *mut u32, value as u32) };
+            println!("  {:016x} <- {:08x}", address, value);
+        }
+        _ => println!("usage: poke <addr> <value>"),
+    }
+}
+
+fn cmd_compute(_args: &[&str]) {
+    multicore::set_cores_compute_mode();
+}
+
+fn cmd_idle(_args: &[&str]) {
+    multicore::set_cores_idle_mode();
+}
+
+fn cmd_balance(_args: &[&str]) {
+    multicore::balance_core_workload();
+}
+
+fn cmd_shutdown(_args: &[&str]) {
+    multicore::shutdown_secondary_cores();
+}
+
+fn cmd_dmesg(args: &[&str]) {
+    if args.first() == Some(&"clear") {
+        log::dmesg_clear();
+        return;
+    }
+    log::dmesg(|line| print!("{}", line));
+}
+
+fn cmd_gdb(args: &[&str]) {
+    match args {
+        [] => crate::gdb::breakpoint(),
+        ["panic", "on"] => crate::gdb::set_panic_entry(true),
+        ["panic", "off"] => crate::gdb::set_panic_entry(false),
+        ["panic"] => println!("  Enter GDB on panic: {}", crate::gdb::panic_entry()),
+        _ => println!("usage: gdb [panic on|off]"),
+    }
+}
+
+fn cmd_loglevel(args: &[&str]) {
+    match args.first() {
+        None => println!("  Log level: {:?}", log::max_level()),
+        Some(name) => match Level::from_name(name) {
+            Some(level) => log::set_max_level(level),
+            None => println!("usage: loglevel error|warn|info|debug|trace"),
+        },
+    }
+}
+
+fn cmd_disks(_args: &[&str]) {
+    let mut found = false;
+    block::for_each(|device| {
+        found = true;
+        println!(
+            "  {:10} {:>12} blocks x {} bytes ({} MB){}",
+            device.name(),
+            device.block_count(),
+            device.block_size(),
+            device.size_bytes() >> 20,
+            if device.read_only() { ", read-only" } else { "" }
+        );
+    });
+    if !found {
+        println!("  No block devices");
+    }
+}
+
+fn cmd_sector(args: &[&str]) {
+    let device = args.first().and_then(|name| block::device(name));
+    let lba = args.get(1).and_then(|arg| parse_number(arg));
+    let (Some(device), Some(lba)) = (device, lba) else {
+        println!("usage: sector <dev> <lba>  (see \"disks\")");
+        return;
+    };
+    if device.block_size() != SECTOR_SIZE {
+        println!("  {}: block size {} not supported", device.name(), device.block_size());
+        return;
+    }
+
+    let mut sector = [0u8; SECTOR_SIZE];
+    if let Err(error) = device.read_blocks(lba, &mut sector) {
+        println!("  {}: read of block {} failed ({:?})", device.name(), lba, error);
+        return;
+    }
+
+    // 16 bytes por linha: offset, hexa e ASCII
+    for (row, bytes) in sector.chunks(16).enumerate() {
+        print!("  {:03x}:", row * 16);
+        for byte in bytes {
+            print!(" {:02x}", byte);
+        }
+        print!("  ");
+        for &byte in bytes {
+            let shown = if (0x20..0x7F).contains(&byte) { byte as char } else { '.' };
+            print!("{}", shown);
+        }
+        println!();
+    }
+}
+
+// Máximo de bytes mostrados por "random"
+const MAX_RANDOM_BYTES: usize = 256;
+
+fn cmd_random(args: &[&str]) {
+    let count = args.first().and_then(|arg| parse_number(arg)).unwrap_or(32) as usize;
+    if count > MAX_RANDOM_BYTES {
+        println!("usage: random [bytes]  (at most {})", MAX_RANDOM_BYTES);
+        return;
+    }
+
+    let stats = random::stats();
+    println!(
+        "  Pool: {}, {} bits credited, {} reseeds, {} interrupts pending",
+        if stats.seeded { "seeded" } else { "not seeded" },
+        stats.entropy_bits,
+        stats.reseeds,
+        stats.pending_interrupts
+    );
+
+    let mut bytes = [0u8; MAX_RANDOM_BYTES];
+    random::fill_bytes(&mut bytes[..count]);
+    for row in bytes[..count].chunks(32) {
+        print!(" ");
+        for byte in row {
+            print!("{:02x}", byte);
+        }
+        println!();
+    }
+}
+
+// Rodadas de cópia medidas por "dma bench"
+const DMA_BENCH_ROUNDS: u32 = 16;
+
+fn cmd_dma(args: &[&str]) {
+    match args {
+        [] => {
+            let (usable, allocated) = dma::channels();
+            println!("  Channels: usable 0x{:04x}, in use 0x{:04x}", usable, allocated);
+        }
+        ["bench", rest @ ..] => {
+            let max_kb = dma::bench_max_bytes() / 1024;
+            let kb = rest.first().and_then(|arg| parse_number(arg)).unwrap_or(max_kb as u64) as usize;
+            if kb == 0 || kb > max_kb {
+                println!("usage: dma bench [KB]  (1-{})", max_kb);
+                return;
+            }
+
+            match dma::benchmark(kb * 1024, DMA_BENCH_ROUNDS) {
+                Ok(result) => {
+                    let total = (result.bytes as u64) * result.rounds as u64;
+                    // bytes/µs == MB/s
+                    for (name, elapsed) in [("CPU", result.cpu), ("DMA", result.dma)] {
+                        let micros = (elapsed.as_micros() as u64).max(1);
+                        println!(
+                            "  {}: {} x {} KB in {} us ({} MB/s)",
+                            name,
+                            result.rounds,
+                            kb,
+                            micros,
+                            total / micros
+                        );
+                    }
+                }
+                Err(error) => println!("  DMA benchmark failed: {:?}", error),
+            }
+        }
+        _ => println!("usage: dma [bench [KB]]"),
+    }
+}
+
+fn cmd_usb(_args: &[&str]) {
+    let mut count = 0;
+    usb::for_each_device(|device| {
+        // Hub 0 é a porta raiz do controlador
+        println!(
+            "  {:>3}: {:04x}:{:04x} class 0x{:02x}, {} speed, hub {} port {}",
+            device.address,
+            device.vendor,
+            device.product,
+            device.class,
+            device.speed.name(),
+            device.parent,
+            device.port
+        );
+        count += 1;
+    });
+    if count == 0 {
+        println!("  No USB devices");
+    }
+}
@@ -3,6 +3,9 @@ use crate::println;
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
+    // Garante o console livre e cala os outros cores
+    crate::drivers::console::panic_takeover();
+    
     println!("--- KERNEL PANIC ---");
     
     // Informações de localização
@@ -20,5 +23,11 @@ fn panic(info: &PanicInfo) -> ! {
     
     println!("-------------------");
     
+    // Com "kgdb" na linha de comando, para no stub do GDB
+    crate::gdb::on_panic();
+    
+    // Reinicia a placa se houver política de reboot em panic
+    crate::drivers::watchdog::on_panic();
+    
     loop {}
 }
\ No newline at end of file
@@ -0,0 +1,60 @@
+// Função de bloco do ChaCha20 (RFC 8439): chave de 256 bits, contador de
+// 32 bits e nonce de 96 bits geram 64 bytes de saída
+
+pub const KEY_WORDS: usize = 8;
+pub const BLOCK_WORDS: usize = 16;
+
+// "expand 32-byte k"
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646E, 0x7962_2D32, 0x6B20_6574];
+
+const DOUBLE_ROUNDS: usize = 10;
+
+fn quarter_round(state: &mut [u32; BLOCK_WORDS], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+pub fn block(key: &[u32; KEY_WORDS], counter: u32, nonce: &[u32; 3]) -> [u32; BLOCK_WORDS] {
+    let mut input = [0u32; BLOCK_WORDS];
+    input[..4].copy_from_slice(&CONSTANTS);
+    input[4..12].copy_from_slice(key);
+    input[12] = counter;
+    input[13..].copy_from_slice(nonce);
+
+    let mut state = input;
+    for _ in 0..DOUBLE_ROUNDS {
+        // Colunas
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        // Diagonais
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    for (word, original) in state.iter_mut().zip(input) {
+        *word = word.wrapping_add(original);
+    }
+    state
+}
+
+// Vetor de teste da seção 2.3.2 da RFC 8439
+pub fn self_test() -> bool {
+    const EXPECTED: [u32; 4] = [0xE4E7_F110, 0x1559_3BD1, 0x1FDD_0F50, 0xC471_20A3];
+
+    // Chave 00 01 02 ... 1f
+    let key: [u32; KEY_WORDS] =
+        core::array::from_fn(|word| u32::from_le_bytes(core::array::from_fn(|byte| (word * 4 + byte) as u8)));
+    let nonce = [0x0900_0000, 0x4A00_0000, 0];
+
+    block(&key, 1, &nonce)[..4] == EXPECTED
+}
@@ -0,0 +1,269 @@
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use crate::arch::multicore::sync::IrqMutex;
+use crate::drivers::rng;
+use crate::println;
+use crate::time::{self, Duration, Instant};
+
+mod chacha;
+
+// Gerador de números aleatórios do kernel (CSPRNG baseado em ChaCha20).
+// As fontes de entropia (RNG de hardware, jitter do cntpct_el0 e instantes
+// das interrupções) são misturadas numa chave de 256 bits; a saída vem do
+// ChaCha20 com essa chave, que é trocada a cada pedido (apagamento rápido
+// da chave: uma chave vazada não revela saídas anteriores). Base para
+// /dev/random, stack canaries e ASLR.
+
+// Entropia creditada para considerar o gerador semeado
+const SEED_BITS: u32 = 256;
+
+// Ressemeadura periódica a partir do hardware e das interrupções
+const RESEED_INTERVAL: Duration = Duration::from_secs(60);
+
+// Palavras lidas do RNG de hardware por semeadura (32 bits creditados cada)
+const HARDWARE_WORDS: usize = 8;
+
+// Jitter: amostras coletadas no boot e em cada ressemeadura. Só as do boot
+// são creditadas (1 bit a cada JITTER_SAMPLES_PER_BIT amostras): sem RNG de
+// hardware, creditar as das ressemeaduras acabaria semeando o gerador só
+// com jitter depois de algumas chamadas
+const BOOT_JITTER_SAMPLES: usize = 256;
+const RESEED_JITTER_SAMPLES: usize = 16;
+const JITTER_SAMPLES_PER_BIT: u32 = 8;
+
+// Instantes de interrupção: 1 bit a cada IRQ_SAMPLES_PER_BIT interrupções
+const IRQ_SAMPLES_PER_BIT: u32 = 64;
+const IRQ_SLOTS: usize = 4;
+
+// Primeira palavra do nonce separa a mistura da geração de saída
+const DOMAIN_MIX: u32 = 0x6D69_7800;
+const DOMAIN_OUTPUT: u32 = 0x6F75_7400;
+
+struct Pool {
+    key: [u32; chacha::KEY_WORDS],
+    // Nonce da próxima saída
+    generation: u64,
+    entropy_bits: u32,
+    seeded: bool,
+    last_reseed: Option<Instant>,
+    reseeds: u32,
+}
+
+impl Pool {
+    // Chave nova = ChaCha20(chave, domínio): função de mão única da anterior
+    fn rekey(&mut self, domain: u32) {
+        let block = chacha::block(&self.key, 0, &[domain, 0, 0]);
+        self.key.copy_from_slice(&block[..chacha::KEY_WORDS]);
+    }
+
+    // Cada grupo de até 8 palavras é somado (XOR) à chave antes de trocá-la
+    fn mix(&mut self, words: &[u32]) {
+        for chunk in words.chunks(chacha::KEY_WORDS) {
+            for (key, word) in self.key.iter_mut().zip(chunk) {
+                *key ^= word;
+            }
+            self.rekey(DOMAIN_MIX);
+        }
+    }
+
+    fn credit(&mut self, bits: u32) {
+        self.entropy_bits = self.entropy_bits.saturating_add(bits);
+        if self.entropy_bits >= SEED_BITS {
+            self.seeded = true;
+        }
+    }
+
+    fn generate(&mut self, output: &mut [u8]) {
+        let nonce = [DOMAIN_OUTPUT, self.generation as u32, (self.generation >> 32) as u32];
+        self.generation += 1;
+
+        // Bloco 0: metade vira a próxima chave, a outra metade é saída
+        let key = self.key;
+        let mut block = chacha::block(&key, 0, &nonce);
+        self.key.copy_from_slice(&block[..chacha::KEY_WORDS]);
+
+        let mut counter = 1;
+        let mut offset = chacha::KEY_WORDS * 4;
+        for byte in output.iter_mut() {
+            if offset == chacha::BLOCK_WORDS * 4 {
+                block = chacha::block(&key, counter, &nonce);
+                counter += 1;
+                offset = 0;
+            }
+            *byte = (block[offset / 4] >> (8 * (offset % 4))) as u8;
+            offset += 1;
+        }
+    }
+}
+
+static POOL: IrqMutex<Pool> = IrqMutex::new(Pool {
+    key: [0; chacha::KEY_WORDS],
+    generation: 0,
+    entropy_bits: 0,
+    seeded: false,
+    last_reseed: None,
+    reseeds: 0,
+});
+
+// Instantes das interrupções, acumulados sem trava (chamado pelo GIC)
+static IRQ_MIX: [AtomicU64; IRQ_SLOTS] = [const { AtomicU64::new(0) }; IRQ_SLOTS];
+static IRQ_EVENTS: AtomicU32 = AtomicU32::new(0);
+
+pub fn add_interrupt_timestamp(irq: u32) {
+    let count = IRQ_EVENTS.fetch_add(1, Ordering::Relaxed);
+    let slot = &IRQ_MIX[count as usize % IRQ_SLOTS];
+    let sample = time::read_generic_counter() ^ (irq as u64) << 56;
+
+    // Uma corrida entre núcleos só perde uma amostra
+    let value = slot.load(Ordering::Relaxed);
+    slot.store(value.rotate_left(13) ^ sample, Ordering::Relaxed);
+}
+
+fn harvest_interrupts(pool: &mut Pool) {
+    let events = IRQ_EVENTS.swap(0, Ordering::Relaxed);
+    let mut words = [0u32; IRQ_SLOTS * 2];
+    for (slot, pair) in IRQ_MIX.iter().zip(words.chunks_mut(2)) {
+        let value = slot.swap(0, Ordering::Relaxed);
+        pair[0] = value as u32;
+        pair[1] = (value >> 32) as u32;
+    }
+    pool.mix(&words);
+    pool.credit(events / IRQ_SAMPLES_PER_BIT);
+}
+
+// Duração de um trabalho curto e de tamanho variável, medida no contador:
+// varia com caches, barramento e interrupções
+fn jitter_sample() -> u32 {
+    let start = time::read_generic_counter();
+    let mut value = start;
+    for round in 0..16 + (start & 0xF) {
+        value = core::hint::black_box(value.rotate_left(5) ^ round);
+    }
+    let elapsed = time::read_generic_counter().wrapping_sub(start);
+    (elapsed ^ value.rotate_left(17)) as u32
+}
+
+fn collect_jitter(pool: &mut Pool, samples: usize) {
+    let mut words = [0u32; chacha::KEY_WORDS];
+    for _ in 0..samples.div_ceil(words.len()) {
+        for word in words.iter_mut() {
+            *word = jitter_sample();
+        }
+        pool.mix(&words);
+    }
+}
+
+// Entropia do hardware: no boot espera pelas palavras, depois só usa as
+// que já estão na FIFO (pode rodar em contexto de interrupção)
+fn collect_hardware(pool: &mut Pool, wait: bool) -> usize {
+    let mut words = [0u32; HARDWARE_WORDS];
+    let count = if wait {
+        rng::fill(&mut words)
+    } else {
+        let mut count = 0;
+        while count < words.len() {
+            let Some(word) = rng::try_read_word() else {
+                break;
+            };
+            words[count] = word;
+            count += 1;
+        }
+        count
+    };
+
+    pool.mix(&words[..count]);
+    pool.credit(count as u32 * 32);
+    count
+}
+
+fn reseed(pool: &mut Pool) {
+    collect_hardware(pool, false);
+    harvest_interrupts(pool);
+    collect_jitter(pool, RESEED_JITTER_SAMPLES);
+    pool.last_reseed = Some(Instant::now());
+    pool.reseeds += 1;
+}
+
+// Nunca bloqueia: antes de semeado a saída vem do que já foi coletado
+// (ver is_seeded)
+pub fn fill_bytes(output: &mut [u8]) {
+    let mut pool = POOL.lock();
+    let due = pool
+        .last_reseed
+        .is_none_or(|last| (last + RESEED_INTERVAL).has_passed());
+    if !pool.seeded || due {
+        reseed(&mut pool);
+    }
+    pool.generate(output);
+}
+
+#[allow(dead_code)]
+pub fn next_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    fill_bytes(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
+
+// Entropia de outras fontes (drivers), com o crédito estimado em bits
+#[allow(dead_code)]
+pub fn add_entropy(data: &[u8], bits: u32) {
+    let mut words = [0u32; chacha::KEY_WORDS];
+    let mut pool = POOL.lock();
+    for chunk in data.chunks(words.len() * 4) {
+        words.fill(0);
+        for (index, &byte) in chunk.iter().enumerate() {
+            words[index / 4] |= (byte as u32) << (8 * (index % 4));
+        }
+        pool.mix(&words);
+    }
+    pool.credit(bits);
+}
+
+#[allow(dead_code)]
+pub fn is_seeded() -> bool {
+    POOL.lock().seeded
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    pub seeded: bool,
+    pub entropy_bits: u32,
+    pub reseeds: u32,
+    pub pending_interrupts: u32,
+}
+
+pub fn stats() -> Stats {
+    let pool = POOL.lock();
+    Stats {
+        seeded: pool.seeded,
+        entropy_bits: pool.entropy_bits,
+        reseeds: pool.reseeds,
+        pending_interrupts: IRQ_EVENTS.load(Ordering::Relaxed),
+    }
+}
+
+pub fn init() {
+    if !chacha::self_test() {
+        panic!("ChaCha20 self-test failed");
+    }
+
+    let mut pool = POOL.lock();
+    // Contador desde o reset: pouco imprevisível, mas não custa nada
+    let counter = time::read_generic_counter();
+    pool.mix(&[counter as u32, (counter >> 32) as u32]);
+
+    let hardware = collect_hardware(&mut pool, true);
+    harvest_interrupts(&mut pool);
+    collect_jitter(&mut pool, BOOT_JITTER_SAMPLES);
+    pool.credit(BOOT_JITTER_SAMPLES as u32 / JITTER_SAMPLES_PER_BIT);
+    pool.last_reseed = Some(Instant::now());
+
+    let (seeded, bits) = (pool.seeded, pool.entropy_bits);
+    drop(pool);
+
+    println!(
+        "Random: ChaCha20 pool {} ({} bits credited, {} hardware words)",
+        if seeded { "seeded" } else { "NOT seeded" },
+        bits,
+        hardware
+    );
+}
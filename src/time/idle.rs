@@ -0,0 +1,94 @@
+use crate::arch::exceptions::{restore_interrupts, save_and_disable_interrupts};
+use crate::drivers::{self, timer, watchdog};
+use crate::time::{self, queue, Duration, Instant};
+use core::arch::asm;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+// Idle sem tick (dynamic ticks): enquanto o core não tem nada para rodar,
+// o tick periódico é parado e o comparador fica programado direto para o
+// próximo timer da fila. Ao acordar, TICK_COUNT é atualizado com os ticks
+// que passaram e o tick volta a rodar.
+
+static IDLE_ENTRIES: AtomicU64 = AtomicU64::new(0);
+static IDLE_NANOS: AtomicU64 = AtomicU64::new(0);
+
+fn noop_wakeup(_id: queue::TimerId, _data: usize) {}
+
+// Dorme até a próxima interrupção deste core
+fn sleep() {
+    // IRQs mascaradas: WFI ainda acorda com uma IRQ pendente, e ela só é
+    // tratada depois que o tick foi reativado
+    let daif = save_and_disable_interrupts();
+
+    // Trabalho adiado sinalizado por uma IRQ depois do último poll
+    if drivers::poll_pending() {
+        restore_interrupts(daif);
+        return;
+    }
+
+    let tick_stopped = timer::tick_stop();
+    let start = Instant::now();
+
+    unsafe { asm!("wfi", options(nomem, nostack)) };
+
+    IDLE_ENTRIES.fetch_add(1, Ordering::Relaxed);
+    IDLE_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+
+    if tick_stopped {
+        timer::tick_restart();
+    }
+
+    restore_interrupts(daif);
+}
+
+// Idle até a próxima interrupção. Com o watchdog armado, o core acorda
+// a tempo de alimentá-lo mesmo sem nenhum outro timer pendente. Antes de
+// dormir roda o trabalho adiado dos drivers.
+pub fn idle() {
+    watchdog::pet();
+    drivers::poll();
+
+    match watchdog::pet_interval() {
+        Some(interval) => sleep_until(Instant::now() + interval),
+        None => sleep(),
+    }
+}
+
+// Idle até o prazo (ou até uma interrupção anterior)
+pub fn idle_until(deadline: Instant) {
+    watchdog::pet();
+    drivers::poll();
+
+    let deadline = match watchdog::pet_interval() {
+        Some(interval) => deadline.min(Instant::now() + interval),
+        None => deadline,
+    };
+    sleep_until(deadline);
+}
+
+fn sleep_until(deadline: Instant) {
+    if deadline.has_passed() {
+        return;
+    }
+
+    // Sem timer para acordar (fila cheia, ou core sem comparador no
+    // fallback do system timer) o WFI poderia não voltar: espera ativa
+    let Some(wakeup) = queue::arm_oneshot(deadline.as_nanos(), noop_wakeup, 0) else {
+        time::wait_until(deadline);
+        return;
+    };
+    sleep();
+    queue::cancel(wakeup);
+}
+
+pub fn idle_for(duration: Duration) {
+    idle_until(Instant::now() + duration);
+}
+
+// Número de entradas em idle e tempo total dormindo
+pub fn idle_stats() -> (u64, Duration) {
+    (
+        IDLE_ENTRIES.load(Ordering::Relaxed),
+        Duration::from_nanos(IDLE_NANOS.load(Ordering::Relaxed)),
+    )
+}
@@ -0,0 +1,265 @@
+use core::arch::asm;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use crate::drivers::systimer;
+
+pub mod idle;
+pub mod queue;
+
+pub use core::time::Duration;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+// Frequência usada caso o firmware não tenha programado CNTFRQ_EL0
+const FALLBACK_COUNTER_FREQUENCY: u64 = systimer::FREQUENCY;
+
+// Leitura do contador no momento em que time::init() foi chamado
+static BOOT_COUNTER: AtomicU64 = AtomicU64::new(0);
+static COUNTER_FREQUENCY: AtomicU64 = AtomicU64::new(0);
+static CLOCK_SOURCE: AtomicU8 = AtomicU8::new(ClockSource::GenericTimer as u8);
+
+// Fonte do contador monotônico
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSource {
+    GenericTimer = 0,  // CNTPCT_EL0 (ARM generic timer)
+    SystemTimer = 1,   // BCM2835 system timer (1MHz), usado como fallback
+}
+
+pub fn clock_source() -> ClockSource {
+    match CLOCK_SOURCE.load(Ordering::Relaxed) {
+        1 => ClockSource::SystemTimer,
+        _ => ClockSource::GenericTimer,
+    }
+}
+
+// O generic timer só é utilizável se o firmware programou CNTFRQ_EL0
+pub fn generic_timer_available() -> bool {
+    read_counter_frequency() != 0
+}
+
+// Leitura do contador do generic timer
+pub fn read_generic_counter() -> u64 {
+    let counter: u64;
+    unsafe {
+        // ISB garante que a leitura não seja antecipada pelo pipeline
+        asm!("isb", "mrs {}, cntpct_el0", out(reg) counter, options(nomem, nostack));
+    }
+    counter
+}
+
+// Funções de acesso ao contador do sistema
+fn read_counter() -> u64 {
+    match clock_source() {
+        ClockSource::GenericTimer => read_generic_counter(),
+        ClockSource::SystemTimer => systimer::read_counter(),
+    }
+}
+
+fn read_counter_frequency() -> u64 {
+    let freq: u64;
+    unsafe { asm!("mrs {}, cntfrq_el0", out(reg) freq, options(nomem, nostack)) };
+    freq & 0xFFFF_FFFF
+}
+
+// Frequência do contador em Hz
+pub fn counter_frequency() -> u64 {
+    let freq = COUNTER_FREQUENCY.load(Ordering::Relaxed);
+    if freq != 0 {
+        return freq;
+    }
+
+    // Ainda não inicializado: consulta diretamente o registrador
+    match read_counter_frequency() {
+        0 => FALLBACK_COUNTER_FREQUENCY,
+        f => f,
+    }
+}
+
+// Conversões entre ticks do contador e nanossegundos (sem overflow intermediário)
+pub fn ticks_to_nanos(ticks: u64) -> u64 {
+    let nanos = ticks as u128 * NANOS_PER_SEC as u128 / counter_frequency() as u128;
+    nanos.min(u64::MAX as u128) as u64
+}
+
+pub fn nanos_to_ticks(nanos: u64) -> u64 {
+    let freq = counter_frequency() as u128;
+    // Arredonda para cima para que esperas nunca terminem antes do prazo
+    let ticks = (nanos as u128 * freq).div_ceil(NANOS_PER_SEC as u128);
+    ticks.min(u64::MAX as u128) as u64
+}
+
+pub fn duration_to_ticks(duration: Duration) -> u64 {
+    let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+    nanos_to_ticks(nanos)
+}
+
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_nanos(ticks_to_nanos(ticks))
+}
+
+// Instante monotônico baseado no contador do sistema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    ticks: u64,
+}
+
+impl Instant {
+    pub fn now() -> Self {
+        Instant { ticks: read_counter() }
+    }
+
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Instant { ticks }
+    }
+
+    pub const fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    // Instante de boot (time::init)
+    pub fn boot() -> Self {
+        Instant { ticks: BOOT_COUNTER.load(Ordering::Relaxed) }
+    }
+
+    // Nanossegundos desde o boot
+    pub fn as_nanos(&self) -> u64 {
+        ticks_to_nanos(self.ticks.saturating_sub(BOOT_COUNTER.load(Ordering::Relaxed)))
+    }
+
+    pub fn from_nanos(nanos: u64) -> Self {
+        let boot = BOOT_COUNTER.load(Ordering::Relaxed);
+        Instant { ticks: boot.saturating_add(nanos_to_ticks(nanos)) }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().saturating_duration_since(*self)
+    }
+
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.ticks.checked_sub(earlier.ticks).map(ticks_to_duration)
+    }
+
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or(Duration::ZERO)
+    }
+
+    #[allow(dead_code)]
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.ticks
+            .checked_add(duration_to_ticks(duration))
+            .map(Instant::from_ticks)
+    }
+
+    #[allow(dead_code)]
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.ticks
+            .checked_sub(duration_to_ticks(duration))
+            .map(Instant::from_ticks)
+    }
+
+    pub fn saturating_add(&self, duration: Duration) -> Instant {
+        Instant::from_ticks(self.ticks.saturating_add(duration_to_ticks(duration)))
+    }
+
+    pub fn saturating_sub(&self, duration: Duration) -> Instant {
+        Instant::from_ticks(self.ticks.saturating_sub(duration_to_ticks(duration)))
+    }
+
+    pub fn has_passed(&self) -> bool {
+        Instant::now() >= *self
+    }
+}
+
+// Os operadores saturam em vez de causar panic em overflow
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        self.saturating_add(rhs)
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, rhs: Duration) -> Instant {
+        self.saturating_sub(rhs)
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Duration {
+        self.saturating_duration_since(rhs)
+    }
+}
+
+// Tempo decorrido desde o boot
+pub fn uptime() -> Duration {
+    Instant::boot().elapsed()
+}
+
+// Espera ativa até o prazo indicado
+pub fn wait_until(deadline: Instant) {
+    while !deadline.has_passed() {
+        core::hint::spin_loop();
+    }
+}
+
+// Espera até a condição ser verdadeira ou o prazo expirar.
+// Retorna true se a condição foi satisfeita.
+pub fn wait_until_or<F: FnMut() -> bool>(deadline: Instant, mut condition: F) -> bool {
+    loop {
+        if condition() {
+            return true;
+        }
+        if deadline.has_passed() {
+            return condition();
+        }
+        core::hint::spin_loop();
+    }
+}
+
+pub fn wait_for<F: FnMut() -> bool>(timeout: Duration, condition: F) -> bool {
+    wait_until_or(Instant::now() + timeout, condition)
+}
+
+// Atrasos calibrados pela frequência do contador
+#[allow(dead_code)]
+pub fn ndelay(ns: u64) {
+    wait_until(Instant::now() + Duration::from_nanos(ns));
+}
+
+#[allow(dead_code)]
+pub fn udelay(us: u64) {
+    wait_until(Instant::now() + Duration::from_micros(us));
+}
+
+pub fn mdelay(ms: u64) {
+    wait_until(Instant::now() + Duration::from_millis(ms));
+}
+
+pub fn init() {
+    let (source, freq) = if generic_timer_available() {
+        (ClockSource::GenericTimer, read_counter_frequency())
+    } else {
+        (ClockSource::SystemTimer, systimer::FREQUENCY)
+    };
+
+    CLOCK_SOURCE.store(source as u8, Ordering::Relaxed);
+    COUNTER_FREQUENCY.store(freq, Ordering::Relaxed);
+    BOOT_COUNTER.store(read_counter(), Ordering::Relaxed);
+}
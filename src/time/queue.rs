@@ -0,0 +1,349 @@
+use crate::arch::multicore::sync::IrqMutex;
+use crate::arch::multicore::{get_core_id, MAX_CORES};
+use crate::drivers::timer;
+use crate::time::{Duration, Instant};
+
+// Fila de timers por core, ordenada por prazo em um heap binário.
+// Os prazos são em nanossegundos desde o boot. Toda alteração da fila
+// acontece com IRQs mascaradas (IrqMutex), então armar, cancelar e
+// rearmar também são seguros a partir de handlers de interrupção. O
+// comparador é por core e só o próprio core o reprograma: de outro core,
+// cancelar sempre funciona e rearmar tem uma restrição (ver rearm).
+
+const MAX_TIMERS: usize = 32;
+const NO_INDEX: u8 = u8::MAX;
+
+// Callback executado em contexto de IRQ, com a fila destravada
+pub type TimerCallback = fn(TimerId, usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerId {
+    core: u8,
+    slot: u8,
+    generation: u16,
+}
+
+impl TimerId {
+    pub fn core(&self) -> u8 {
+        self.core
+    }
+}
+
+#[derive(Clone, Copy)]
+struct TimerSlot {
+    deadline_ns: u64,
+    period_ns: u64,
+    callback: Option<TimerCallback>,
+    data: usize,
+    generation: u16,
+    heap_index: u8,
+}
+
+impl TimerSlot {
+    const EMPTY: TimerSlot = TimerSlot {
+        deadline_ns: 0,
+        period_ns: 0,
+        callback: None,
+        data: 0,
+        generation: 0,
+        heap_index: NO_INDEX,
+    };
+}
+
+struct TimerQueue {
+    slots: [TimerSlot; MAX_TIMERS],
+    heap: [u8; MAX_TIMERS],
+    len: usize,
+}
+
+impl TimerQueue {
+    const fn new() -> Self {
+        TimerQueue {
+            slots: [TimerSlot::EMPTY; MAX_TIMERS],
+            heap: [0; MAX_TIMERS],
+            len: 0,
+        }
+    }
+
+    fn allocate(&mut self) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.callback.is_none())
+    }
+
+    fn lookup(&self, id: TimerId) -> Option<usize> {
+        let slot = id.slot as usize;
+        let entry = self.slots.get(slot)?;
+        if entry.callback.is_some() && entry.generation == id.generation {
+            Some(slot)
+        } else {
+            None
+        }
+    }
+
+    fn release(&mut self, slot: usize) {
+        self.remove(slot);
+        let generation = self.slots[slot].generation.wrapping_add(1);
+        self.slots[slot] = TimerSlot { generation, ..TimerSlot::EMPTY };
+    }
+
+    fn deadline_at(&self, index: usize) -> u64 {
+        self.slots[self.heap[index] as usize].deadline_ns
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.slots[self.heap[a] as usize].heap_index = a as u8;
+        self.slots[self.heap[b] as usize].heap_index = b as u8;
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if self.deadline_at(index) >= self.deadline_at(parent) {
+                break;
+            }
+            self.swap(index, parent);
+            index = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let left = 2 * index + 1;
+            let right = left + 1;
+            let mut smallest = index;
+
+            if left < self.len && self.deadline_at(left) < self.deadline_at(smallest) {
+                smallest = left;
+            }
+            if right < self.len && self.deadline_at(right) < self.deadline_at(smallest) {
+                smallest = right;
+            }
+            if smallest == index {
+                break;
+            }
+            self.swap(index, smallest);
+            index = smallest;
+        }
+    }
+
+    fn insert(&mut self, slot: usize) {
+        let index = self.len;
+        self.heap[index] = slot as u8;
+        self.slots[slot].heap_index = index as u8;
+        self.len += 1;
+        self.sift_up(index);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        let index = self.slots[slot].heap_index;
+        if index == NO_INDEX {
+            return;
+        }
+
+        let index = index as usize;
+        let last = self.len - 1;
+        if index != last {
+            self.swap(index, last);
+        }
+        self.len -= 1;
+        self.slots[slot].heap_index = NO_INDEX;
+
+        if index < self.len {
+            self.sift_down(index);
+            self.sift_up(index);
+        }
+    }
+
+    fn earliest(&self) -> Option<u64> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.deadline_at(0))
+        }
+    }
+}
+
+static QUEUES: [IrqMutex<TimerQueue>; MAX_CORES] =
+    [const { IrqMutex::new(TimerQueue::new()) }; MAX_CORES];
+
+fn current_core() -> usize {
+    get_core_id() as usize % MAX_CORES
+}
+
+// Nanossegundos desde o boot
+pub fn now_ns() -> u64 {
+    Instant::now().as_nanos()
+}
+
+// Reprograma o comparador deste core para o prazo mais próximo
+fn program_hardware(queue: &TimerQueue) {
+    match queue.earliest() {
+        Some(deadline_ns) => timer::program_event(Instant::from_nanos(deadline_ns)),
+        None => timer::stop_event(),
+    }
+}
+
+fn arm(deadline_ns: u64, period_ns: u64, callback: TimerCallback, data: usize) -> Option<TimerId> {
+    // Sem comparador neste core (fallback no system timer): nada o dispararia
+    if !timer::has_event_device() {
+        return None;
+    }
+
+    let core = current_core();
+    let mut queue = QUEUES[core].lock();
+
+    let slot = queue.allocate()?;
+    let generation = queue.slots[slot].generation;
+    queue.slots[slot] = TimerSlot {
+        deadline_ns,
+        period_ns,
+        callback: Some(callback),
+        data,
+        generation,
+        heap_index: NO_INDEX,
+    };
+    queue.insert(slot);
+    program_hardware(&queue);
+
+    Some(TimerId {
+        core: core as u8,
+        slot: slot as u8,
+        generation,
+    })
+}
+
+// Timer de disparo único no prazo absoluto (ns desde o boot)
+pub fn arm_oneshot(deadline_ns: u64, callback: TimerCallback, data: usize) -> Option<TimerId> {
+    arm(deadline_ns, 0, callback, data)
+}
+
+// Timer periódico: primeiro disparo em first_ns, depois a cada period_ns
+pub fn arm_periodic(
+    first_ns: u64,
+    period_ns: u64,
+    callback: TimerCallback,
+    data: usize,
+) -> Option<TimerId> {
+    if period_ns == 0 {
+        return None;
+    }
+    arm(first_ns, period_ns, callback, data)
+}
+
+#[allow(dead_code)]
+pub fn oneshot_after(delay: Duration, callback: TimerCallback, data: usize) -> Option<TimerId> {
+    arm_oneshot(now_ns().saturating_add(delay.as_nanos() as u64), callback, data)
+}
+
+pub fn periodic_every(period: Duration, callback: TimerCallback, data: usize) -> Option<TimerId> {
+    let period_ns = period.as_nanos() as u64;
+    arm_periodic(now_ns().saturating_add(period_ns), period_ns, callback, data)
+}
+
+// Cancela um timer. Retorna false se ele já expirou ou foi cancelado.
+// Um callback já retirado da fila pode ainda estar em execução. De outro
+// core, o comparador do dono ainda dispara no prazo antigo: a IRQ não
+// encontra nada vencido e só reprograma.
+pub fn cancel(id: TimerId) -> bool {
+    let core = id.core as usize % MAX_CORES;
+    let mut queue = QUEUES[core].lock();
+
+    let Some(slot) = queue.lookup(id) else {
+        return false;
+    };
+    queue.release(slot);
+
+    if core == current_core() {
+        program_hardware(&queue);
+    }
+    true
+}
+
+// Move um timer ativo para um novo prazo (mantendo o período). Retorna
+// false se o timer não está ativo, ou se o pedido vem de outro core e
+// anteciparia o próximo disparo do dono: o comparador dele (programado
+// para no máximo o prazo mais próximo da fila) dispararia tarde demais.
+#[allow(dead_code)]
+pub fn rearm(id: TimerId, deadline_ns: u64) -> bool {
+    let core = id.core as usize % MAX_CORES;
+    let mut queue = QUEUES[core].lock();
+
+    let Some(slot) = queue.lookup(id) else {
+        return false;
+    };
+    let remote = core != current_core();
+    if remote && queue.earliest().is_some_and(|earliest| deadline_ns < earliest) {
+        return false;
+    }
+    queue.remove(slot);
+    queue.slots[slot].deadline_ns = deadline_ns;
+    queue.insert(slot);
+
+    if !remote {
+        program_hardware(&queue);
+    }
+    true
+}
+
+#[allow(dead_code)]
+pub fn is_armed(id: TimerId) -> bool {
+    let core = id.core as usize % MAX_CORES;
+    QUEUES[core].lock().lookup(id).is_some()
+}
+
+// Prazo mais próximo da fila deste core
+#[allow(dead_code)]
+pub fn next_deadline() -> Option<u64> {
+    QUEUES[current_core()].lock().earliest()
+}
+
+#[allow(dead_code)]
+pub fn pending_timers() -> usize {
+    QUEUES[current_core()].lock().len
+}
+
+// Retira o próximo timer vencido, já reagendando os periódicos
+fn pop_expired(now: u64) -> Option<(TimerId, TimerCallback, usize)> {
+    let core = current_core();
+    let mut queue = QUEUES[core].lock();
+
+    let deadline = queue.earliest()?;
+    if deadline > now {
+        return None;
+    }
+
+    let slot = queue.heap[0] as usize;
+    let entry = queue.slots[slot];
+    let id = TimerId {
+        core: core as u8,
+        slot: slot as u8,
+        generation: entry.generation,
+    };
+    let callback = entry.callback?;
+
+    // Pula períodos perdidos em vez de disparar em rajada; período 0 é
+    // um timer de disparo único
+    match (now - deadline).checked_div(entry.period_ns) {
+        None => queue.release(slot),
+        Some(late) => {
+            let missed = late + 1;
+            queue.remove(slot);
+            queue.slots[slot].deadline_ns =
+                deadline.saturating_add(missed.saturating_mul(entry.period_ns));
+            queue.insert(slot);
+        }
+    }
+
+    Some((id, callback, entry.data))
+}
+
+// Chamado pelo handler de IRQ do timer
+pub fn handle_timer_interrupt() {
+    let now = now_ns();
+
+    while let Some((id, callback, data)) = pop_expired(now) {
+        callback(id, data);
+    }
+
+    program_hardware(&QUEUES[current_core()].lock());
+}
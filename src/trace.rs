@@ -0,0 +1,102 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::arch::multicore::get_core_id;
+use crate::cmdline;
+use crate::drivers::console;
+use crate::time;
+
+// Trace binário compacto para caminhos quentes (tick do timer, demo
+// multi-core). As strings de formato não vão para o UART: cada chamada
+// de ktrace! é identificada pelo hash FNV-1a da string, e o build.rs gera
+// a tabela id -> formato (target/trace_table.tsv) usada pelo decodificador
+// no host (scripts/trace_decode.py).
+//
+// Formato de um registro:
+//   0xFF                  início (nunca aparece em texto UTF-8)
+//   id                    u32 little-endian
+//   timestamp             µs desde o boot, varint LEB128
+//   core << 4 | nargs     um byte
+//   argumentos            nargs varints LEB128 (valores sem sinal)
+//
+// Sem "trace=binary" na linha de comando, ktrace! imprime texto pelo log,
+// como println!; a saída de texto do boot não muda.
+
+pub const FRAME_START: u8 = 0xFF;
+pub const MAX_ARGS: usize = 15;
+
+// id (4) + timestamp (10) + core/nargs (1) + args (10 cada)
+const MAX_FRAME: usize = 1 + 4 + 10 + 1 + MAX_ARGS * 10;
+
+static BINARY: AtomicBool = AtomicBool::new(false);
+
+// FNV-1a de 32 bits; o build.rs calcula o mesmo hash para a tabela
+pub const fn format_id(format: &str) -> u32 {
+    let bytes = format.as_bytes();
+    let mut hash = 0x811C_9DC5u32;
+    let mut index = 0;
+    while index < bytes.len() {
+        hash ^= bytes[index] as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+        index += 1;
+    }
+    hash
+}
+
+pub fn binary() -> bool {
+    BINARY.load(Ordering::Relaxed)
+}
+
+pub fn set_binary(enabled: bool) {
+    BINARY.store(enabled, Ordering::Relaxed);
+}
+
+struct Frame {
+    data: [u8; MAX_FRAME],
+    len: usize,
+}
+
+impl Frame {
+    fn push(&mut self, byte: u8) {
+        self.data[self.len] = byte;
+        self.len += 1;
+    }
+
+    fn push_varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.push(byte);
+                return;
+            }
+            self.push(byte | 0x80);
+        }
+    }
+}
+
+pub fn emit(id: u32, args: &[u64]) {
+    let args = &args[..args.len().min(MAX_ARGS)];
+    let mut frame = Frame {
+        data: [0; MAX_FRAME],
+        len: 0,
+    };
+
+    frame.push(FRAME_START);
+    for byte in id.to_le_bytes() {
+        frame.push(byte);
+    }
+    frame.push_varint(time::uptime().as_micros() as u64);
+    frame.push((get_core_id() << 4) | args.len() as u8);
+    for &arg in args {
+        frame.push_varint(arg);
+    }
+
+    // Um registro inteiro sob o lock do console
+    console::write_raw(console::active(), &frame.data[..frame.len]);
+}
+
+// "trace=binary" liga o formato binário; "trace=text" (padrão) mantém texto
+pub fn init() {
+    if let Some(mode) = cmdline::param("trace") {
+        set_binary(mode == "binary");
+    }
+}
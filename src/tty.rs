@@ -0,0 +1,721 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::arch::exceptions;
+use crate::arch::multicore::sync::IrqMutex;
+use crate::drivers::console::{self, ConsoleDevice};
+use crate::drivers::mini_uart;
+use crate::time::{self, Duration, Instant};
+use crate::{info, println};
+
+// Camada TTY sobre os UARTs, no estilo do servidor TTY do MINIX: disciplina
+// de linha (modo canônico ou raw), eco, edição com erase/kill e caracteres
+// de controle (^C, ^Z viram sinais, ^D é fim de arquivo). As estruturas
+// termios/winsize seguem o layout do Linux para aarch64, usado pelas
+// chamadas de sistema de processos de usuário.
+
+// --- termios ---
+
+pub const NCCS: usize = 19;
+
+// Índices de c_cc
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VTIME: usize = 5;
+pub const VMIN: usize = 6;
+pub const VSUSP: usize = 10;
+
+// c_iflag
+pub const ICRNL: u32 = 0o400;
+
+// c_oflag
+pub const OPOST: u32 = 0o1;
+pub const ONLCR: u32 = 0o4;
+
+// c_lflag
+pub const ISIG: u32 = 0o1;
+pub const ICANON: u32 = 0o2;
+pub const ECHO: u32 = 0o10;
+pub const ECHOE: u32 = 0o20;
+pub const ECHOK: u32 = 0o40;
+pub const ECHOCTL: u32 = 0o1000;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Termios {
+    pub c_iflag: u32,
+    pub c_oflag: u32,
+    pub c_cflag: u32,
+    pub c_lflag: u32,
+    pub c_line: u8,
+    pub c_cc: [u8; NCCS],
+}
+
+impl Termios {
+    // Modo "cooked" padrão de um terminal serial
+    pub const DEFAULT: Termios = {
+        let mut c_cc = [0u8; NCCS];
+        c_cc[VINTR] = 0x03;  // ^C
+        c_cc[VQUIT] = 0x1C;  // ^\
+        c_cc[VERASE] = 0x7F; // DEL
+        c_cc[VKILL] = 0x15;  // ^U
+        c_cc[VEOF] = 0x04;   // ^D
+        c_cc[VMIN] = 1;
+        c_cc[VSUSP] = 0x1A;  // ^Z
+
+        Termios {
+            c_iflag: ICRNL,
+            c_oflag: OPOST | ONLCR,
+            c_cflag: 0,
+            c_lflag: ISIG | ICANON | ECHO | ECHOE | ECHOK | ECHOCTL,
+            c_line: 0,
+            c_cc,
+        }
+    };
+
+    // Equivalente a cfmakeraw()
+    #[allow(dead_code)]
+    pub fn make_raw(&mut self) {
+        self.c_iflag &= !ICRNL;
+        self.c_oflag &= !OPOST;
+        self.c_lflag &= !(ISIG | ICANON | ECHO | ECHOE | ECHOK | ECHOCTL);
+        self.c_cc[VMIN] = 1;
+        self.c_cc[VTIME] = 0;
+    }
+
+    fn local(&self, flag: u32) -> bool {
+        self.c_lflag & flag != 0
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Winsize {
+    pub ws_row: u16,
+    pub ws_col: u16,
+    pub ws_xpixel: u16,
+    pub ws_ypixel: u16,
+}
+
+impl Winsize {
+    pub const DEFAULT: Winsize = Winsize {
+        ws_row: 24,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+}
+
+// --- Sinais e erros ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Interrupt = 2,  // SIGINT
+    Quit = 3,       // SIGQUIT
+    Suspend = 20,   // SIGTSTP
+    WindowSize = 28, // SIGWINCH
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TtyError {
+    NoDevice,
+    WouldBlock,
+    Interrupted,
+}
+
+impl TtyError {
+    // Valor de errno devolvido às chamadas de sistema
+    pub fn errno(&self) -> i64 {
+        match self {
+            TtyError::NoDevice => 19,     // ENODEV
+            TtyError::WouldBlock => 11,   // EAGAIN
+            TtyError::Interrupted => 4,   // EINTR
+        }
+    }
+}
+
+// Quem recebe os sinais gerados pelo teclado (o gerenciador de processos)
+pub type SignalHandler = fn(TtyId, Signal);
+
+static SIGNAL_HANDLER: IrqMutex<Option<SignalHandler>> = IrqMutex::new(None);
+
+#[allow(dead_code)]
+pub fn set_signal_handler(handler: SignalHandler) {
+    *SIGNAL_HANDLER.lock() = Some(handler);
+}
+
+// --- Fila de entrada ---
+
+const INPUT_SIZE: usize = 1024;
+const LINE_SIZE: usize = 256;
+
+// Marcadores na fila além dos bytes: fim de arquivo (^D numa linha vazia)
+// e fim de linha sem caractere (^D depois de texto)
+const MARK_EOF: u16 = 0x100;
+const MARK_PUSH: u16 = 0x101;
+
+struct InputQueue {
+    data: [u16; INPUT_SIZE],
+    head: usize,
+    len: usize,
+    // Linhas completas na fila (modo canônico)
+    lines: usize,
+}
+
+impl InputQueue {
+    const fn new() -> Self {
+        InputQueue {
+            data: [0; INPUT_SIZE],
+            head: 0,
+            len: 0,
+            lines: 0,
+        }
+    }
+
+    fn push(&mut self, item: u16) -> bool {
+        if self.len == INPUT_SIZE {
+            return false;
+        }
+        self.data[(self.head + self.len) % INPUT_SIZE] = item;
+        self.len += 1;
+        if is_delimiter(item) {
+            self.lines += 1;
+        }
+        true
+    }
+
+    fn pop(&mut self) -> Option<u16> {
+        if self.len == 0 {
+            return None;
+        }
+        let item = self.data[self.head];
+        self.head = (self.head + 1) % INPUT_SIZE;
+        self.len -= 1;
+        if is_delimiter(item) {
+            self.lines -= 1;
+        }
+        Some(item)
+    }
+
+    fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.lines = 0;
+    }
+
+    // Tira os marcadores de push, mantendo a ordem do resto
+    fn remove_pushes(&mut self) {
+        let count = self.len;
+        for _ in 0..count {
+            if let Some(item) = self.pop() {
+                if item != MARK_PUSH {
+                    self.push(item);
+                }
+            }
+        }
+    }
+}
+
+fn is_delimiter(item: u16) -> bool {
+    item == b'\n' as u16 || item == MARK_EOF || item == MARK_PUSH
+}
+
+// --- Dispositivos ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtyId(usize);
+
+struct Tty {
+    name: &'static str,
+    device: ConsoleDevice,
+    termios: Termios,
+    winsize: Winsize,
+    // Linha sendo editada (modo canônico)
+    line: [u8; LINE_SIZE],
+    line_len: usize,
+    input: InputQueue,
+    // Sinal gerado desde a última leitura (interrompe um read bloqueado)
+    signal: Option<Signal>,
+}
+
+impl Tty {
+    const fn new(name: &'static str, device: ConsoleDevice) -> Self {
+        Tty {
+            name,
+            device,
+            termios: Termios::DEFAULT,
+            winsize: Winsize::DEFAULT,
+            line: [0; LINE_SIZE],
+            line_len: 0,
+            input: InputQueue::new(),
+            signal: None,
+        }
+    }
+
+    fn output(&self, bytes: &[u8]) {
+        let translate = self.termios.c_oflag & (OPOST | ONLCR) == OPOST | ONLCR;
+        if !translate {
+            console::write_to(self.device, bytes);
+            return;
+        }
+
+        // ONLCR: "\n" vira "\r\n"
+        for chunk in bytes.split_inclusive(|&byte| byte == b'\n') {
+            match chunk.split_last() {
+                Some((b'\n', text)) => {
+                    console::write_to(self.device, text);
+                    console::write_to(self.device, b"\r\n");
+                }
+                _ => console::write_to(self.device, chunk),
+            }
+        }
+    }
+
+    fn echo(&self, byte: u8) {
+        if !self.termios.local(ECHO) {
+            return;
+        }
+
+        // Controles ecoados como ^X
+        if self.termios.local(ECHOCTL) && byte < 0x20 && byte != b'\n' && byte != b'\t' {
+            self.output(&[b'^', byte + 0x40]);
+        } else if byte == 0x7F && self.termios.local(ECHOCTL) {
+            self.output(b"^?");
+        } else {
+            self.output(&[byte]);
+        }
+    }
+
+    fn erase_char(&mut self) {
+        if self.line_len == 0 {
+            return;
+        }
+        self.line_len -= 1;
+        if self.termios.local(ECHO) && self.termios.local(ECHOE) {
+            self.output(b"\x08 \x08");
+        }
+    }
+
+    fn kill_line(&mut self) {
+        if self.termios.local(ECHOK) && !self.termios.local(ECHOE) {
+            self.line_len = 0;
+            self.output(b"\n");
+            return;
+        }
+        while self.line_len > 0 {
+            self.erase_char();
+        }
+    }
+
+    // Move a linha em edição para a fila de leitura
+    fn commit_line(&mut self, delimiter: u16) {
+        self.commit_pending();
+        self.input.push(delimiter);
+    }
+
+    fn commit_pending(&mut self) {
+        for index in 0..self.line_len {
+            self.input.push(self.line[index] as u16);
+        }
+        self.line_len = 0;
+    }
+
+    fn raise(&mut self, signal: Signal, byte: u8) {
+        self.echo(byte);
+        self.output(b"\n");
+
+        // Como no Linux, um sinal do teclado descarta a entrada pendente
+        self.line_len = 0;
+        self.input.clear();
+        self.signal = Some(signal);
+    }
+
+    // Disciplina de linha: processa um byte recebido
+    fn receive(&mut self, mut byte: u8) -> Option<Signal> {
+        let termios = self.termios;
+
+        if byte == b'\r' && termios.c_iflag & ICRNL != 0 {
+            byte = b'\n';
+        }
+
+        if termios.local(ISIG) {
+            let signal = match byte {
+                _ if byte == termios.c_cc[VINTR] => Some(Signal::Interrupt),
+                _ if byte == termios.c_cc[VQUIT] => Some(Signal::Quit),
+                _ if byte == termios.c_cc[VSUSP] => Some(Signal::Suspend),
+                _ => None,
+            };
+            if let Some(signal) = signal {
+                self.raise(signal, byte);
+                return Some(signal);
+            }
+        }
+
+        if !termios.local(ICANON) {
+            if self.input.push(byte as u16) {
+                self.echo(byte);
+            }
+            return None;
+        }
+
+        match byte {
+            b'\n' => {
+                self.echo(b'\n');
+                self.commit_line(b'\n' as u16);
+            }
+            0x08 => self.erase_char(),
+            _ if byte == termios.c_cc[VERASE] => self.erase_char(),
+            _ if byte == termios.c_cc[VKILL] => self.kill_line(),
+            _ if byte == termios.c_cc[VEOF] => {
+                let mark = if self.line_len == 0 { MARK_EOF } else { MARK_PUSH };
+                self.commit_line(mark);
+            }
+            _ => {
+                // Guarda espaço para as linhas já completas
+                if self.line_len < LINE_SIZE && self.input.len + self.line_len + 1 < INPUT_SIZE {
+                    self.line[self.line_len] = byte;
+                    self.line_len += 1;
+                    self.echo(byte);
+                }
+            }
+        }
+        None
+    }
+
+    // Lê o que o UART (e o teclado, no console) já recebeu
+    fn pump(&mut self) -> Option<Signal> {
+        let mut signal = None;
+        while let Some(byte) = console::read_byte_from(self.device) {
+            signal = self.receive(byte).or(signal);
+        }
+        signal
+    }
+
+    fn readable(&self, min: usize) -> bool {
+        if self.termios.local(ICANON) {
+            self.input.lines > 0
+        } else {
+            self.input.len >= min.max(1)
+        }
+    }
+
+    fn read_into(&mut self, buffer: &mut [u8]) -> usize {
+        let canonical = self.termios.local(ICANON);
+        let mut count = 0;
+
+        while count < buffer.len() {
+            let Some(item) = self.input.pop() else {
+                break;
+            };
+            match item {
+                // EOF no início devolve 0; depois de dados, fica para a próxima leitura
+                MARK_EOF if count > 0 => {
+                    self.restore_eof();
+                    break;
+                }
+                // Sem ICANON não há linhas: o push só separava leituras
+                MARK_PUSH if !canonical => continue,
+                MARK_EOF | MARK_PUSH => break,
+                _ => {
+                    buffer[count] = item as u8;
+                    count += 1;
+                    if canonical && item == b'\n' as u16 {
+                        break;
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    // Devolve o marcador de EOF para a frente da fila
+    fn restore_eof(&mut self) {
+        self.input.head = (self.input.head + INPUT_SIZE - 1) % INPUT_SIZE;
+        self.input.data[self.input.head] = MARK_EOF;
+        self.input.len += 1;
+        self.input.lines += 1;
+    }
+}
+
+// /dev/ttyAMA0 no PL011 e /dev/ttyS0 no mini UART; /dev/tty é o console
+const TTY_COUNT: usize = 2;
+
+static TTYS: [IrqMutex<Tty>; TTY_COUNT] = [
+    IrqMutex::new(Tty::new("ttyAMA0", ConsoleDevice::Pl011)),
+    IrqMutex::new(Tty::new("ttyS0", ConsoleDevice::MiniUart)),
+];
+
+// Intervalo de polling enquanto um read espera (o PL011 acorda antes pela IRQ)
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+fn tty(id: TtyId) -> Result<&'static IrqMutex<Tty>, TtyError> {
+    TTYS.get(id.0).ok_or(TtyError::NoDevice)
+}
+
+fn deliver(id: TtyId, signal: Option<Signal>) {
+    if let Some(signal) = signal {
+        let handler = *SIGNAL_HANDLER.lock();
+        match handler {
+            Some(handler) => handler(id, signal),
+            None => info!("{}: signal {:?} with no handler", TTYS[id.0].lock().name, signal),
+        }
+    }
+}
+
+// TTY ligado ao console ativo (/dev/tty)
+pub fn console_tty() -> TtyId {
+    match console::active() {
+        ConsoleDevice::Pl011 => TtyId(0),
+        ConsoleDevice::MiniUart => TtyId(1),
+    }
+}
+
+// Aceita "/dev/tty", "/dev/console", "/dev/ttyAMA0", "/dev/ttyS0"
+pub fn open(path: &str) -> Result<TtyId, TtyError> {
+    let name = path.strip_prefix("/dev/").unwrap_or(path);
+    let id = match name {
+        "tty" | "console" => console_tty(),
+        _ => TtyId(
+            TTYS.iter()
+                .position(|tty| tty.lock().name == name)
+                .ok_or(TtyError::NoDevice)?,
+        ),
+    };
+
+    if tty(id)?.lock().device == ConsoleDevice::MiniUart && !mini_uart::is_initialized() {
+        mini_uart::init();
+    }
+    Ok(id)
+}
+
+// read(): no modo canônico devolve no máximo uma linha; no modo raw espera
+// VMIN bytes, com VTIME (décimos de segundo) como limite de espera
+pub fn read(id: TtyId, buffer: &mut [u8], nonblock: bool) -> Result<usize, TtyError> {
+    let tty = tty(id)?;
+    if buffer.is_empty() {
+        return Ok(0);
+    }
+
+    let (min, timeout) = {
+        let tty = tty.lock();
+        let termios = &tty.termios;
+        let timeout = (!termios.local(ICANON) && termios.c_cc[VTIME] != 0)
+            .then(|| Instant::now() + Duration::from_millis(termios.c_cc[VTIME] as u64 * 100));
+        (termios.c_cc[VMIN] as usize, timeout)
+    };
+
+    loop {
+        let mut guard = tty.lock();
+        let signal = guard.pump();
+        guard.signal = guard.signal.or(signal);
+
+        if let Some(signal) = guard.signal.take() {
+            drop(guard);
+            deliver(id, Some(signal));
+            return Err(TtyError::Interrupted);
+        }
+
+        let timed_out = timeout.is_some_and(|deadline| deadline.has_passed());
+        if guard.readable(min.min(buffer.len())) || (timed_out && guard.input.len > 0) {
+            return Ok(guard.read_into(buffer));
+        }
+        // Modo raw com VMIN = 0 e VTIME = 0: não bloqueia
+        if timed_out || (min == 0 && timeout.is_none() && !guard.termios.local(ICANON)) {
+            return Ok(0);
+        }
+        if nonblock {
+            return Err(TtyError::WouldBlock);
+        }
+        drop(guard);
+
+        // Vindo de um SVC as IRQs estão mascaradas: sem liberá-las o WFI
+        // acordaria sem tratar nada, e nem o timer nem o teclado USB rodariam
+        let daif = exceptions::save_and_enable_interrupts();
+        time::idle::idle_for(READ_POLL_INTERVAL);
+        exceptions::restore_interrupts(daif);
+    }
+}
+
+pub fn write(id: TtyId, data: &[u8]) -> Result<usize, TtyError> {
+    let tty = tty(id)?.lock();
+    tty.output(data);
+    Ok(data.len())
+}
+
+// Processa a entrada pendente sem ler (ex.: para gerar sinais com ^C
+// enquanto nenhum processo está lendo)
+#[allow(dead_code)]
+pub fn poll(id: TtyId) -> Result<(), TtyError> {
+    let signal = {
+        let mut tty = tty(id)?.lock();
+        let signal = tty.pump();
+        tty.signal = tty.signal.or(signal);
+        signal
+    };
+    deliver(id, signal);
+    Ok(())
+}
+
+pub fn get_termios(id: TtyId) -> Result<Termios, TtyError> {
+    Ok(tty(id)?.lock().termios)
+}
+
+// Ao sair do modo canônico a linha em edição vira entrada comum, sem
+// marcador: em modo raw um marcador contaria em input.len e o próximo
+// read devolveria 0 (EOF para o processo)
+pub fn set_termios(id: TtyId, termios: &Termios) -> Result<(), TtyError> {
+    let mut tty = tty(id)?.lock();
+    if tty.termios.local(ICANON) && termios.c_lflag & ICANON == 0 {
+        tty.commit_pending();
+        tty.input.remove_pushes();
+    }
+    tty.termios = *termios;
+    Ok(())
+}
+
+pub fn get_winsize(id: TtyId) -> Result<Winsize, TtyError> {
+    Ok(tty(id)?.lock().winsize)
+}
+
+pub fn set_winsize(id: TtyId, winsize: &Winsize) -> Result<(), TtyError> {
+    let changed = {
+        let mut tty = tty(id)?.lock();
+        let changed = tty.winsize != *winsize;
+        tty.winsize = *winsize;
+        changed
+    };
+
+    if changed {
+        deliver(id, Some(Signal::WindowSize));
+    }
+    Ok(())
+}
+
+// --- Chamadas de sistema (números do Linux para aarch64) ---
+
+pub const SYS_IOCTL: u64 = 29;
+pub const SYS_OPENAT: u64 = 56;
+pub const SYS_READ: u64 = 63;
+pub const SYS_WRITE: u64 = 64;
+
+const TCGETS: u64 = 0x5401;
+const TCSETS: u64 = 0x5402;
+const TIOCGWINSZ: u64 = 0x5413;
+const TIOCSWINSZ: u64 = 0x5414;
+
+const O_NONBLOCK: u64 = 0o4000;
+
+const EBADF: i64 = 9;
+const EFAULT: i64 = 14;
+const EINVAL: i64 = 22;
+const ENOTTY: i64 = 25;
+
+// fds 0-2 são o console; 3 em diante, um por TTY aberto com openat()
+const FIRST_TTY_FD: u64 = 3;
+const MAX_PATH: usize = 64;
+
+// Sem processos ainda: o O_NONBLOCK de openat() vale para o TTY todo
+static NONBLOCK: [AtomicBool; TTY_COUNT] =
+    [const { AtomicBool::new(false) }; TTY_COUNT];
+
+fn fd_to_tty(fd: u64) -> Result<TtyId, i64> {
+    match fd {
+        0..=2 => Ok(console_tty()),
+        _ if fd >= FIRST_TTY_FD && ((fd - FIRST_TTY_FD) as usize) < TTY_COUNT => {
+            Ok(TtyId((fd - FIRST_TTY_FD) as usize))
+        }
+        _ => Err(EBADF),
+    }
+}
+
+fn user_slice<'a>(ptr: u64, len: u64) -> Result<&'a [u8], i64> {
+    if ptr == 0 && len != 0 {
+        return Err(EFAULT);
+    }
+    Ok(unsafe { core::slice::from_raw_parts(ptr as *const u8, len as usize) })
+}
+
+fn user_slice_mut<'a>(ptr: u64, len: u64) -> Result<&'a mut [u8], i64> {
+    if ptr == 0 && len != 0 {
+        return Err(EFAULT);
+    }
+    Ok(unsafe { core::slice::from_raw_parts_mut(ptr as *mut u8, len as usize) })
+}
+
+fn result(value: Result<usize, TtyError>) -> i64 {
+    match value {
+        Ok(count) => count as i64,
+        Err(error) => -error.errno(),
+    }
+}
+
+// Retorno no estilo do Linux: valor >= 0 ou -errno
+pub fn syscall(number: u64, args: [u64; 4]) -> Option<i64> {
+    let value = match number {
+        SYS_READ => sys_read(args[0], args[1], args[2]),
+        SYS_WRITE => sys_write(args[0], args[1], args[2]),
+        SYS_IOCTL => sys_ioctl(args[0], args[1], args[2]),
+        SYS_OPENAT => sys_openat(args[1], args[2]),
+        _ => return None,
+    };
+    Some(value.unwrap_or_else(|errno| -errno))
+}
+
+fn sys_read(fd: u64, buffer: u64, len: u64) -> Result<i64, i64> {
+    let id = fd_to_tty(fd)?;
+    let buffer = user_slice_mut(buffer, len)?;
+    let nonblock = NONBLOCK[id.0].load(Ordering::Relaxed);
+    Ok(result(read(id, buffer, nonblock)))
+}
+
+fn sys_write(fd: u64, buffer: u64, len: u64) -> Result<i64, i64> {
+    let id = fd_to_tty(fd)?;
+    Ok(result(write(id, user_slice(buffer, len)?)))
+}
+
+fn sys_openat(path: u64, flags: u64) -> Result<i64, i64> {
+    let bytes = user_slice(path, MAX_PATH as u64)?;
+    let len = bytes.iter().position(|&byte| byte == 0).ok_or(EINVAL)?;
+    let path = core::str::from_utf8(&bytes[..len]).map_err(|_| EINVAL)?;
+
+    let id = open(path).map_err(|error| error.errno())?;
+    NONBLOCK[id.0].store(flags & O_NONBLOCK != 0, Ordering::Relaxed);
+    Ok((FIRST_TTY_FD + id.0 as u64) as i64)
+}
+
+fn sys_ioctl(fd: u64, request: u64, argument: u64) -> Result<i64, i64> {
+    let id = fd_to_tty(fd)?;
+    if argument == 0 {
+        return Err(EFAULT);
+    }
+
+    let status = match request {
+        TCGETS => get_termios(id).map(|termios| unsafe {
+            core::ptr::write_unaligned(argument as *mut Termios, termios)
+        }),
+        TCSETS => {
+            let termios = unsafe { core::ptr::read_unaligned(argument as *const Termios) };
+            set_termios(id, &termios)
+        }
+        TIOCGWINSZ => get_winsize(id).map(|winsize| unsafe {
+            core::ptr::write_unaligned(argument as *mut Winsize, winsize)
+        }),
+        TIOCSWINSZ => {
+            let winsize = unsafe { core::ptr::read_unaligned(argument as *const Winsize) };
+            set_winsize(id, &winsize)
+        }
+        _ => return Err(ENOTTY),
+    };
+
+    status.map(|()| 0).map_err(|error| error.errno())
+}
+
+pub fn init() {
+    let console = console_tty();
+    println!(
+        "TTY: {} devices, /dev/tty -> /dev/{}",
+        TTY_COUNT,
+        TTYS[console.0].lock().name
+    );
+}